@@ -0,0 +1,316 @@
+use std::time;
+
+use crate::client::Element;
+
+/// A set of input action sequences, for use with
+/// [`Client::perform_actions`](crate::Client::perform_actions).
+///
+/// Each source (eg: a mouse or keyboard) has its own sequence of actions;
+/// the driver dispatches the n-th action of every source together, as one
+/// "tick".
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Actions {
+    actions: Vec<InputSource>,
+}
+
+/// A sequence of actions for a pointer device, eg: a mouse.
+#[derive(Debug, Clone)]
+pub struct PointerActions {
+    id: String,
+    pointer_type: PointerType,
+    actions: Vec<PointerAction>,
+}
+
+/// A sequence of actions for a keyboard.
+#[derive(Debug, Clone)]
+pub struct KeyActions {
+    id: String,
+    actions: Vec<KeyAction>,
+}
+
+/// The kind of pointer device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PointerType {
+    /// A mouse.
+    Mouse,
+    /// A pen or stylus.
+    Pen,
+    /// A touch screen.
+    Touch,
+}
+
+/// A mouse button.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseButton {
+    /// The primary (usually left) button.
+    Left,
+    /// The middle button, or wheel.
+    Middle,
+    /// The secondary (usually right) button.
+    Right,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum InputSource {
+    Pointer {
+        id: String,
+        parameters: PointerParameters,
+        actions: Vec<PointerAction>,
+    },
+    Key {
+        id: String,
+        actions: Vec<KeyAction>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PointerParameters {
+    pointer_type: PointerType,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum PointerAction {
+    Pause {
+        duration: u64,
+    },
+    PointerMove {
+        duration: u64,
+        origin: Origin,
+        x: i64,
+        y: i64,
+    },
+    PointerDown {
+        button: u64,
+    },
+    PointerUp {
+        button: u64,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+enum Origin {
+    Element(Element),
+    Named(&'static str),
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum KeyAction {
+    Pause { duration: u64 },
+    KeyDown { value: String },
+    KeyUp { value: String },
+}
+
+impl Actions {
+    /// An empty set of actions.
+    pub fn new() -> Self {
+        Actions::default()
+    }
+
+    /// Add a pointer action sequence.
+    pub fn pointer(mut self, pointer: PointerActions) -> Self {
+        self.actions.push(InputSource::Pointer {
+            id: pointer.id,
+            parameters: PointerParameters {
+                pointer_type: pointer.pointer_type,
+            },
+            actions: pointer.actions,
+        });
+        self
+    }
+
+    /// Add a keyboard action sequence.
+    pub fn keys(mut self, keys: KeyActions) -> Self {
+        self.actions.push(InputSource::Key {
+            id: keys.id,
+            actions: keys.actions,
+        });
+        self
+    }
+}
+
+impl PointerActions {
+    /// A new, empty, sequence for the pointer device named `id`.
+    pub fn new<S: Into<String>>(id: S, pointer_type: PointerType) -> Self {
+        PointerActions {
+            id: id.into(),
+            pointer_type,
+            actions: Vec::new(),
+        }
+    }
+
+    /// A new, empty, sequence for a mouse.
+    pub fn mouse() -> Self {
+        PointerActions::new("mouse", PointerType::Mouse)
+    }
+
+    /// Move to an offset from the in-view center of `elt`.
+    pub fn move_to_element(self, elt: &Element, x: i64, y: i64) -> Self {
+        self.push_move(
+            Origin::Element(elt.clone()),
+            x,
+            y,
+            time::Duration::default(),
+        )
+    }
+
+    /// Move to a position relative to the top-left of the viewport.
+    pub fn move_to(self, x: i64, y: i64) -> Self {
+        self.push_move(Origin::Named("viewport"), x, y, time::Duration::default())
+    }
+
+    /// Move by an offset relative to the current pointer position, taking
+    /// `duration` to get there.
+    pub fn move_by(self, x: i64, y: i64, duration: time::Duration) -> Self {
+        self.push_move(Origin::Named("pointer"), x, y, duration)
+    }
+
+    /// Press the given button.
+    pub fn down(mut self, button: MouseButton) -> Self {
+        self.actions.push(PointerAction::PointerDown {
+            button: button.code(),
+        });
+        self
+    }
+
+    /// Release the given button.
+    pub fn up(mut self, button: MouseButton) -> Self {
+        self.actions.push(PointerAction::PointerUp {
+            button: button.code(),
+        });
+        self
+    }
+
+    /// Press and release the given button.
+    pub fn click(self, button: MouseButton) -> Self {
+        self.down(button).up(button)
+    }
+
+    /// Do nothing for `duration`.
+    pub fn pause(mut self, duration: time::Duration) -> Self {
+        self.actions.push(PointerAction::Pause {
+            duration: duration.as_millis() as u64,
+        });
+        self
+    }
+
+    fn push_move(mut self, origin: Origin, x: i64, y: i64, duration: time::Duration) -> Self {
+        self.actions.push(PointerAction::PointerMove {
+            duration: duration.as_millis() as u64,
+            origin,
+            x,
+            y,
+        });
+        self
+    }
+}
+
+impl KeyActions {
+    /// A new, empty, sequence for the keyboard named `id`.
+    pub fn new<S: Into<String>>(id: S) -> Self {
+        KeyActions {
+            id: id.into(),
+            actions: Vec::new(),
+        }
+    }
+
+    /// Press the given key. Special keys are given by the codepoints listed
+    /// in the webdriver spec, eg: `'\u{E008}'` for shift.
+    pub fn down(mut self, key: char) -> Self {
+        self.actions.push(KeyAction::KeyDown {
+            value: key.to_string(),
+        });
+        self
+    }
+
+    /// Release the given key.
+    pub fn up(mut self, key: char) -> Self {
+        self.actions.push(KeyAction::KeyUp {
+            value: key.to_string(),
+        });
+        self
+    }
+
+    /// Do nothing for `duration`.
+    pub fn pause(mut self, duration: time::Duration) -> Self {
+        self.actions.push(KeyAction::Pause {
+            duration: duration.as_millis() as u64,
+        });
+        self
+    }
+}
+
+impl MouseButton {
+    fn code(self) -> u64 {
+        match self {
+            MouseButton::Left => 0,
+            MouseButton::Middle => 1,
+            MouseButton::Right => 2,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_to_spec_format() {
+        let elt: Element =
+            serde_json::from_value(json!({"element-6066-11e4-a52e-4f735466cecf": "abc"}))
+                .expect("element");
+        let actions = Actions::new()
+            .pointer(
+                PointerActions::mouse()
+                    .move_to_element(&elt, 0, 0)
+                    .down(MouseButton::Left)
+                    .move_by(10, -5, time::Duration::from_millis(100))
+                    .up(MouseButton::Left),
+            )
+            .keys(KeyActions::new("keyboard").down('\u{E008}').up('\u{E008}'));
+
+        assert_eq!(
+            serde_json::to_value(&actions).expect("serialize"),
+            json!({
+                "actions": [
+                    {
+                        "type": "pointer",
+                        "id": "mouse",
+                        "parameters": { "pointerType": "mouse" },
+                        "actions": [
+                            {
+                                "type": "pointerMove",
+                                "duration": 0,
+                                "origin": {"element-6066-11e4-a52e-4f735466cecf": "abc"},
+                                "x": 0,
+                                "y": 0
+                            },
+                            { "type": "pointerDown", "button": 0 },
+                            {
+                                "type": "pointerMove",
+                                "duration": 100,
+                                "origin": "pointer",
+                                "x": 10,
+                                "y": -5
+                            },
+                            { "type": "pointerUp", "button": 0 }
+                        ]
+                    },
+                    {
+                        "type": "key",
+                        "id": "keyboard",
+                        "actions": [
+                            { "type": "keyDown", "value": "\u{E008}" },
+                            { "type": "keyUp", "value": "\u{E008}" }
+                        ]
+                    }
+                ]
+            })
+        );
+    }
+}
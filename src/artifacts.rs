@@ -0,0 +1,205 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::SystemTime;
+
+use failure::Error;
+use failure::ResultExt;
+
+use crate::client::Client;
+use crate::har::rfc3339;
+
+/// The environment variable naming the root directory of an
+/// [`ArtifactSink::from_env`]; `target/sulfur-artifacts` by default.
+pub const ARTIFACTS_ENV: &str = "SULFUR_ARTIFACTS_DIR";
+const DEFAULT_ARTIFACTS_DIR: &str = "target/sulfur-artifacts";
+
+/// Where debugging artifacts for a run are saved: a timestamped directory
+/// per session, beneath a common root, eg:
+/// `target/sulfur-artifacts/2020-03-01T12-30-45.123Z-login_test/`.
+///
+/// Once set on a client (see [`Client::set_artifact_sink`] and
+/// [`ClientBuilder::artifact_sink`](crate::ClientBuilder::artifact_sink)),
+/// the screenshot, command log, driver log and so on are saved there if the
+/// client is dropped during a panic.
+///
+/// ```no_run
+/// # fn example() -> Result<(), failure::Error> {
+/// use sulfur::{ArtifactSink, Client};
+///
+/// let client = Client::builder()
+///     .url("http://localhost:4444/")
+///     .artifact_sink(ArtifactSink::from_env())
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArtifactSink {
+    root: PathBuf,
+}
+
+/// Captures debugging artifacts (see [`Client::capture_artifacts`]) if it is
+/// dropped while the current thread is panicking, eg: because a test
+/// assertion failed.
+#[derive(Debug)]
+pub struct ArtifactGuard<'c> {
+    client: &'c Client,
+    dir: PathBuf,
+}
+
+impl<'c> ArtifactGuard<'c> {
+    pub(crate) fn new(client: &'c Client, dir: PathBuf) -> Self {
+        ArtifactGuard { client, dir }
+    }
+}
+
+impl ArtifactSink {
+    /// A sink saving artifacts beneath `root`.
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        ArtifactSink { root: root.into() }
+    }
+
+    /// A sink saving artifacts beneath `$SULFUR_ARTIFACTS_DIR`, or
+    /// `target/sulfur-artifacts` if that is unset.
+    pub fn from_env() -> Self {
+        let root = env::var_os(ARTIFACTS_ENV)
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_ARTIFACTS_DIR));
+        ArtifactSink::new(root)
+    }
+
+    /// The directory session directories are created in.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// A fresh directory for the session called `name`, eg: the name of the
+    /// test, prefixed by the current time. The directory is created when
+    /// artifacts are first saved to it.
+    pub fn session_dir(&self, name: &str) -> PathBuf {
+        let timestamp = rfc3339(SystemTime::now()).replace(':', "-");
+        self.root.join(format!("{}-{}", timestamp, name))
+    }
+}
+
+impl<'c> Drop for ArtifactGuard<'c> {
+    fn drop(&mut self) {
+        capture_on_panic(self.client, &self.dir)
+    }
+}
+
+pub(crate) fn capture_on_panic(client: &Client, dir: &Path) {
+    if !thread::panicking() {
+        return;
+    }
+    match capture(client, dir) {
+        Ok(()) => info!("Saved failure artifacts to {:?}", dir),
+        Err(e) => error!("Saving failure artifacts to {:?}: {}", dir, e),
+    }
+}
+
+// Saves each artifact independently, so that eg: a crashed browser still
+// leaves us with the command log.
+pub(crate) fn capture(client: &Client, dir: &Path) -> Result<(), Error> {
+    fs::create_dir_all(dir).with_context(|_| format!("Creating artifact directory {:?}", dir))?;
+
+    let mut results = vec![
+        (
+            "screenshot.png",
+            client.screenshot_to(dir.join("screenshot.png")),
+        ),
+        (
+            "page.html",
+            write(dir, "page.html", || client.page_source()),
+        ),
+        ("url.txt", write(dir, "url.txt", || client.current_url())),
+        (
+            "cookies.json",
+            write(dir, "cookies.json", || {
+                Ok(serde_json::to_string_pretty(&client.cookies()?)?)
+            }),
+        ),
+        (
+            "commands.jsonl",
+            fs::File::create(dir.join("commands.jsonl"))
+                .map_err(Error::from)
+                .and_then(|f| client.command_log().write_json_lines(f)),
+        ),
+    ];
+    results.extend(client.capture_hook_artifacts(dir));
+    if let Some(log) = client.driver_log() {
+        results.push((
+            "driver.log",
+            fs::copy(log, dir.join("driver.log"))
+                .map(|_| ())
+                .with_context(|_| format!("Copying {:?}", log))
+                .map_err(Error::from),
+        ));
+    }
+
+    let failures = results
+        .into_iter()
+        .filter_map(|(name, res)| res.err().map(|e| format!("{}: {}", name, e)))
+        .collect::<Vec<_>>();
+    if !failures.is_empty() {
+        bail!("Failed to capture some artifacts: {}", failures.join("; "));
+    }
+    Ok(())
+}
+
+fn write<F: FnOnce() -> Result<String, Error>>(
+    dir: &Path,
+    name: &str,
+    content: F,
+) -> Result<(), Error> {
+    fs::write(dir.join(name), content()?)?;
+    Ok(())
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::*;
+    use crate::testing::MockWebDriver;
+
+    #[test]
+    fn saves_failure_artifacts_per_session() {
+        let mock = MockWebDriver::start().expect("start");
+        let root = tempfile::tempdir().expect("tempdir");
+        let sink = ArtifactSink::new(root.path());
+        let client = Client::builder()
+            .url(mock.url())
+            .artifact_sink(sink)
+            .build()
+            .expect("build");
+        let dir = client.artifacts_dir().expect("artifacts dir").to_owned();
+        assert_eq!(dir.parent(), Some(root.path()));
+        let name = dir.file_name().and_then(|n| n.to_str()).expect("name");
+        assert!(name.ends_with("Z-mock-session"), "{}", name);
+
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(move || {
+            client.title().expect("title");
+            drop(client);
+            panic!("Deliberate failure");
+        }));
+        assert!(res.is_err());
+        assert!(
+            !dir.exists(),
+            "Should only save when dropped while panicking"
+        );
+
+        let client = Client::builder()
+            .url(mock.url())
+            .artifact_sink(ArtifactSink::new(root.path()))
+            .build()
+            .expect("build");
+        let dir = client.artifacts_dir().expect("artifacts dir").to_owned();
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(move || {
+            let _client = client;
+            panic!("Deliberate failure");
+        }));
+        assert!(res.is_err());
+        assert!(dir.join("commands.jsonl").exists());
+    }
+}
@@ -0,0 +1,163 @@
+use std::marker::PhantomData;
+
+use failure::Error;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::client::{find_all_script, By, Client, ErrorKind, WdError};
+
+// Runs each query in `arguments[0]`, returning `{value: ...}` for each, or
+// `{missing: true}` where a query needs an element and none matched.
+const RUN_BATCH: &str = r#"
+return arguments[0].map(function (query) {
+    var found = findAll(query.by);
+    switch (query.kind) {
+    case "count":
+        return { value: found.length };
+    case "texts":
+        return { value: found.map(function (elt) { return elt.innerText; }) };
+    }
+    if (found.length === 0) {
+        return { missing: true };
+    }
+    switch (query.kind) {
+    case "text":
+        return { value: found[0].innerText };
+    case "attribute":
+        return { value: found[0].getAttribute(query.name) };
+    default:
+        throw new Error("Unknown query: " + query.kind);
+    }
+});
+"#;
+
+/// Bundles several read-only queries into a single script evaluation, to
+/// save a round trip per query, which can dominate when talking to a
+/// remote driver. Created with [`Client::batch`].
+///
+/// Texts are as per the `innerText` property (see
+/// [`TextMode::InnerText`](crate::TextMode::InnerText)).
+///
+/// ```no_run
+/// # fn example(client: &sulfur::Client) -> Result<(), failure::Error> {
+/// use sulfur::By;
+///
+/// let mut batch = client.batch();
+/// let heading = batch.text(&By::css("h1"));
+/// let rows = batch.count(&By::css("table tr"));
+/// let results = batch.run()?;
+/// println!("{}: {} rows", results.get(heading)?, results.get(rows)?);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Batch<'c> {
+    client: &'c Client,
+    queries: Vec<Value>,
+}
+
+/// A handle to the result of a query added to a [`Batch`].
+#[derive(Debug)]
+pub struct Query<T> {
+    index: usize,
+    result: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for Query<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Query<T> {}
+
+/// The results of running a [`Batch`].
+#[derive(Debug, Clone)]
+pub struct BatchResults {
+    queries: Vec<Value>,
+    results: Vec<Value>,
+}
+
+impl<'c> Batch<'c> {
+    pub(crate) fn new(client: &'c Client) -> Self {
+        Batch {
+            client,
+            queries: Vec::new(),
+        }
+    }
+
+    /// The text of the first element matching `by`.
+    pub fn text(&mut self, by: &By) -> Query<String> {
+        self.push("text", by, None)
+    }
+
+    /// The texts of all elements matching `by`.
+    pub fn texts(&mut self, by: &By) -> Query<Vec<String>> {
+        self.push("texts", by, None)
+    }
+
+    /// The value of `attribute` on the first element matching `by`.
+    pub fn attribute(&mut self, by: &By, attribute: &str) -> Query<Option<String>> {
+        self.push("attribute", by, Some(attribute))
+    }
+
+    /// The number of elements matching `by`.
+    pub fn count(&mut self, by: &By) -> Query<usize> {
+        self.push("count", by, None)
+    }
+
+    /// Runs all of the queries.
+    pub fn run(self) -> Result<BatchResults, Error> {
+        let script = find_all_script(RUN_BATCH);
+        let results: Vec<Value> = self
+            .client
+            .execute_script(&script, &[Value::Array(self.queries.clone())])?;
+        if results.len() != self.queries.len() {
+            bail!(
+                "Expected {} batch results, got {}",
+                self.queries.len(),
+                results.len()
+            );
+        }
+        Ok(BatchResults {
+            queries: self.queries,
+            results,
+        })
+    }
+
+    fn push<T>(&mut self, kind: &str, by: &By, name: Option<&str>) -> Query<T> {
+        self.queries.push(json!({
+            "kind": kind,
+            "by": by,
+            "name": name,
+        }));
+        Query {
+            index: self.queries.len() - 1,
+            result: PhantomData,
+        }
+    }
+}
+
+impl BatchResults {
+    /// The result of the given query. Fails with a `no such element` error
+    /// if the query needed an element, and none matched.
+    pub fn get<T: DeserializeOwned>(&self, query: Query<T>) -> Result<T, Error> {
+        let result = match self.results.get(query.index) {
+            Some(result) => result,
+            None => bail!("Query {} is not part of this batch", query.index),
+        };
+        if result["missing"].as_bool() == Some(true) {
+            let by = &self.queries[query.index]["by"];
+            return Err(WdError::new(
+                ErrorKind::NoSuchElement,
+                format!(
+                    "No element matches {} `{}`",
+                    by["using"].as_str().unwrap_or_default(),
+                    by["value"].as_str().unwrap_or_default()
+                ),
+            )
+            .into());
+        }
+        Ok(serde_json::from_value(result["value"].clone())?)
+    }
+}
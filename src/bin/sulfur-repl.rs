@@ -0,0 +1,188 @@
+//! An interactive prompt for driving a browser, eg: for trying out selectors
+//! before writing them into a test.
+//!
+//! Usage: `sulfur-repl [--gecko] [--headless]`. Type `help` at the prompt for
+//! a list of commands.
+
+use std::env;
+use std::io::{self, BufRead, Write};
+
+use failure::{bail, Error};
+use sulfur::{chrome, gecko, By, DriverHolder, Element};
+
+const HELP: &str = "\
+Commands:
+  visit URL        navigate to URL
+  find SELECTOR    find elements by CSS, or by XPath with an `xpath:` prefix
+  click N          click element N from the last `find`
+  text N           print the text of element N from the last `find`
+  exec SCRIPT      run JavaScript, and print the result as JSON
+  url              print the current URL
+  help             show this message
+  quit             close the browser and exit";
+
+#[derive(Debug, PartialEq, Eq)]
+enum Cmd {
+    Visit(String),
+    Find(String),
+    Click(usize),
+    Text(usize),
+    Exec(String),
+    Url,
+    Help,
+    Quit,
+}
+
+fn parse(line: &str) -> Result<Option<Cmd>, Error> {
+    let line = line.trim();
+    if line.is_empty() {
+        return Ok(None);
+    }
+    let (word, rest) = match line.find(char::is_whitespace) {
+        Some(i) => (&line[..i], line[i..].trim()),
+        None => (line, ""),
+    };
+    let arg = || {
+        if rest.is_empty() {
+            bail!("`{}` needs an argument; try `help`", word);
+        }
+        Ok(rest.to_string())
+    };
+    let index = || -> Result<usize, Error> {
+        match arg()?.parse() {
+            Ok(n) => Ok(n),
+            Err(_) => bail!("`{}` takes an element number, not {:?}", word, rest),
+        }
+    };
+    let cmd = match word {
+        "visit" => Cmd::Visit(arg()?),
+        "find" => Cmd::Find(arg()?),
+        "click" => Cmd::Click(index()?),
+        "text" => Cmd::Text(index()?),
+        "exec" => Cmd::Exec(arg()?),
+        "url" => Cmd::Url,
+        "help" | "?" => Cmd::Help,
+        "quit" | "exit" => Cmd::Quit,
+        other => bail!("Unknown command {:?}; try `help`", other),
+    };
+    Ok(Some(cmd))
+}
+
+fn selector(sel: &str) -> By {
+    match sel.strip_prefix("xpath:") {
+        Some(xpath) => By::xpath(xpath),
+        None => By::css(sel),
+    }
+}
+
+struct Repl {
+    session: DriverHolder,
+    found: Vec<Element>,
+}
+
+impl Repl {
+    fn element(&self, n: usize) -> Result<&Element, Error> {
+        match self.found.get(n) {
+            Some(elt) => Ok(elt),
+            None => bail!(
+                "No element {}; the last `find` found {}",
+                n,
+                self.found.len()
+            ),
+        }
+    }
+
+    fn run(&mut self, cmd: Cmd) -> Result<(), Error> {
+        match cmd {
+            Cmd::Visit(url) => self.session.visit(&url)?,
+            Cmd::Find(sel) => {
+                self.found = self.session.find_elements(&selector(&sel))?;
+                for (i, elt) in self.found.iter().enumerate() {
+                    let name = self.session.name(elt)?;
+                    let text = self.session.text(elt)?;
+                    println!("{}: <{}> {:?}", i, name, text);
+                }
+                println!("{} element(s)", self.found.len());
+            }
+            Cmd::Click(n) => self.session.click(self.element(n)?)?,
+            Cmd::Text(n) => println!("{}", self.session.text(self.element(n)?)?),
+            Cmd::Exec(script) => {
+                let result: serde_json::Value = self.session.execute_script(&script, &[])?;
+                println!("{}", serde_json::to_string_pretty(&result)?);
+            }
+            Cmd::Url => println!("{}", self.session.current_url()?),
+            Cmd::Help => println!("{}", HELP),
+            Cmd::Quit => unreachable!("handled by main loop"),
+        }
+        Ok(())
+    }
+}
+
+fn main() -> Result<(), Error> {
+    env_logger::try_init().unwrap_or_default();
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let headless = args.iter().any(|a| a == "--headless");
+    let session = if args.iter().any(|a| a == "--gecko") {
+        gecko::start(gecko::Config::default().headless(headless))?
+    } else {
+        chrome::start(chrome::Config::default().headless(headless))?
+    };
+    let mut repl = Repl {
+        session,
+        found: Vec::new(),
+    };
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("sulfur> ");
+        io::stdout().flush()?;
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => break,
+        };
+        match parse(&line) {
+            Ok(None) => {}
+            Ok(Some(Cmd::Quit)) => break,
+            Ok(Some(cmd)) => {
+                if let Err(e) = repl.run(cmd) {
+                    eprintln!("Error: {}", e);
+                }
+            }
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    repl.session.close()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(parse("  ").unwrap(), None);
+        assert_eq!(
+            parse("visit http://localhost/").unwrap(),
+            Some(Cmd::Visit("http://localhost/".into()))
+        );
+        assert_eq!(
+            parse("find  div > p.note").unwrap(),
+            Some(Cmd::Find("div > p.note".into()))
+        );
+        assert_eq!(parse("click 2").unwrap(), Some(Cmd::Click(2)));
+        assert_eq!(parse("quit").unwrap(), Some(Cmd::Quit));
+        assert!(parse("click first").is_err());
+        assert!(parse("visit").is_err());
+        assert!(parse("frobnicate").is_err());
+    }
+
+    #[test]
+    fn xpath_selectors_are_prefixed() {
+        let json = |by: By| serde_json::to_value(by).expect("to_value");
+        assert_eq!(json(selector("xpath://p")), json(By::xpath("//p")));
+        assert_eq!(json(selector("p")), json(By::css("p")));
+    }
+}
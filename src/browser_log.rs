@@ -0,0 +1,88 @@
+use failure::Error;
+
+/// An entry from one of the browser's own logs, eg: the console.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BrowserLogEntry {
+    /// The severity, eg: `INFO` or `SEVERE`.
+    pub level: String,
+    /// The message text.
+    pub message: String,
+    /// Where the entry came from, if reported, eg: `console-api` for calls
+    /// to `console.log`, or `javascript` for uncaught exceptions.
+    #[serde(default)]
+    pub source: Option<String>,
+    /// When the entry was logged, in milliseconds since the unix epoch.
+    pub timestamp: u64,
+}
+
+impl BrowserLogEntry {
+    /// Whether this entry represents an error, eg: an uncaught exception
+    /// or a call to `console.error`.
+    pub fn is_error(&self) -> bool {
+        self.level == "SEVERE"
+    }
+}
+
+/// A Chrome DevTools Protocol event, from Chrome's performance log.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PerformanceEvent {
+    /// The DevTools event name, eg: `Network.responseReceived`.
+    pub method: String,
+    /// The event parameters; see the DevTools protocol documentation.
+    pub params: serde_json::Value,
+    /// The page that the event relates to.
+    pub webview: Option<String>,
+    /// When the entry was logged, in milliseconds since the unix epoch.
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct PerformanceMessage {
+    message: DevtoolsEvent,
+    webview: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DevtoolsEvent {
+    method: String,
+    #[serde(default)]
+    params: serde_json::Value,
+}
+
+// Performance log messages are JSON encoded DevTools events.
+pub(crate) fn parse_performance_entry(entry: BrowserLogEntry) -> Result<PerformanceEvent, Error> {
+    let msg: PerformanceMessage = serde_json::from_str(&entry.message)?;
+    Ok(PerformanceEvent {
+        method: msg.message.method,
+        params: msg.message.params,
+        webview: msg.webview,
+        timestamp: entry.timestamp,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_performance_entries() {
+        let entry: BrowserLogEntry = serde_json::from_value(json!({
+            "level": "INFO",
+            "message": "{\"message\":{\"method\":\"Network.requestWillBeSent\",\
+                        \"params\":{\"requestId\":\"1\"}},\"webview\":\"ABC\"}",
+            "timestamp": 1_577_836_800_000u64,
+        }))
+        .expect("entry");
+
+        let event = parse_performance_entry(entry).expect("parse");
+        assert_eq!(
+            event,
+            PerformanceEvent {
+                method: "Network.requestWillBeSent".into(),
+                params: json!({"requestId": "1"}),
+                webview: Some("ABC".into()),
+                timestamp: 1_577_836_800_000,
+            }
+        );
+    }
+}
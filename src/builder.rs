@@ -0,0 +1,236 @@
+#[cfg(unix)]
+use std::path::PathBuf;
+
+use failure::{Error, ResultExt};
+
+use crate::artifacts::ArtifactSink;
+use crate::client::{Capabilities, Client, Timeouts, Transport};
+use crate::slow_motion::SlowMotion;
+
+// The base URL used when talking over a Unix domain socket, and no URL is
+// given; only the path is sent to the driver.
+#[cfg(unix)]
+const UNIX_SOCKET_BASE: &str = "http://localhost/";
+
+/// Configures and starts a webdriver session on an existing driver, eg: a
+/// Selenium Grid or a separately started chromedriver. See
+/// [`Client::builder`].
+///
+/// ```no_run
+/// # fn example() -> Result<(), failure::Error> {
+/// use std::time::Duration;
+/// use sulfur::{Client, Timeouts};
+///
+/// let client = Client::builder()
+///     .url("http://grid.example:4444/wd/hub/")
+///     .capability("browserName", "firefox")
+///     .timeouts(Timeouts::new().implicit(Duration::from_secs(2)))
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ClientBuilder {
+    url: Option<String>,
+    capabilities: Capabilities,
+    timeouts: Option<Timeouts>,
+    #[cfg(feature = "http-transport")]
+    http: Option<reqwest::Client>,
+    artifact_sink: Option<ArtifactSink>,
+    slow_motion: Option<SlowMotion>,
+    #[cfg(unix)]
+    unix_socket: Option<PathBuf>,
+}
+
+impl ClientBuilder {
+    /// A builder with no URL, and empty capabilities.
+    pub fn new() -> Self {
+        ClientBuilder::default()
+    }
+
+    /// The base URL of the driver, eg: `http://localhost:4444/wd/hub/`.
+    pub fn url<S: Into<String>>(&mut self, url: S) -> &mut Self {
+        self.url = Some(url.into());
+        self
+    }
+
+    /// Replace the capabilities requested.
+    pub fn capabilities(&mut self, capabilities: Capabilities) -> &mut Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Request a single capability; see [`Capabilities::capability`].
+    pub fn capability<K: Into<String>, V: Into<serde_json::Value>>(
+        &mut self,
+        name: K,
+        value: V,
+    ) -> &mut Self {
+        let capabilities = std::mem::take(&mut self.capabilities);
+        self.capabilities = capabilities.capability(name, value);
+        self
+    }
+
+    /// Timeouts to set once the session has started.
+    pub fn timeouts(&mut self, timeouts: Timeouts) -> &mut Self {
+        self.timeouts = Some(timeouts);
+        self
+    }
+
+    /// Use `http` to talk to the driver, eg: to configure proxies or TLS.
+    #[cfg(feature = "http-transport")]
+    pub fn http_client(&mut self, http: reqwest::Client) -> &mut Self {
+        self.http = Some(http);
+        self
+    }
+
+    /// Save failure artifacts for the session in `sink`; see
+    /// [`Client::set_artifact_sink`].
+    pub fn artifact_sink(&mut self, sink: ArtifactSink) -> &mut Self {
+        self.artifact_sink = Some(sink);
+        self
+    }
+
+    /// Slow the session down, eg: for watching a headed browser; see
+    /// [`Client::set_slow_motion`].
+    pub fn slow_motion(&mut self, slow_motion: SlowMotion) -> &mut Self {
+        self.slow_motion = Some(slow_motion);
+        self
+    }
+
+    /// Talk to a driver listening on the Unix domain socket at `path`,
+    /// rather than over TCP, eg: so that the driver is not reachable by
+    /// other users of the machine. Only the path of the [`url`](Self::url)
+    /// is used, and it defaults to `/`. chromedriver and geckodriver only
+    /// listen on TCP ports, so this needs a driver (or a proxy in front of
+    /// one) that listens on a socket.
+    #[cfg(unix)]
+    pub fn unix_socket<P: Into<PathBuf>>(&mut self, path: P) -> &mut Self {
+        self.unix_socket = Some(path.into());
+        self
+    }
+
+    /// Start the session.
+    pub fn build(&self) -> Result<Client, Error> {
+        let url = match self.url.as_deref().or_else(|| self.default_url()) {
+            Some(url) => url::Url::parse(url).with_context(|_| format!("Parsing {:?}", url))?,
+            None => bail!("No driver URL given to ClientBuilder"),
+        };
+        let mut client =
+            Client::new_with_transport(url, self.capabilities.clone(), self.transport()?)?;
+        if let Some(timeouts) = &self.timeouts {
+            client.set_timeouts(timeouts)?;
+        }
+        if let Some(sink) = &self.artifact_sink {
+            client.set_artifact_sink(sink);
+        }
+        client.set_slow_motion(self.slow_motion);
+        Ok(client)
+    }
+
+    #[cfg(unix)]
+    fn default_url(&self) -> Option<&str> {
+        self.unix_socket.as_ref().map(|_| UNIX_SOCKET_BASE)
+    }
+
+    #[cfg(not(unix))]
+    fn default_url(&self) -> Option<&str> {
+        None
+    }
+
+    fn transport(&self) -> Result<Transport, Error> {
+        #[cfg(unix)]
+        {
+            if let Some(path) = &self.unix_socket {
+                return Ok(Transport::UnixSocket(path.clone()));
+            }
+        }
+        #[cfg(feature = "http-transport")]
+        {
+            Ok(Transport::Http(
+                self.http.clone().unwrap_or_else(reqwest::Client::new),
+            ))
+        }
+        #[cfg(not(feature = "http-transport"))]
+        {
+            bail!("Talking to a driver over TCP needs the `http-transport` feature")
+        }
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::*;
+    use crate::testing::MockWebDriver;
+
+    #[test]
+    fn builds_clients() {
+        let mock = MockWebDriver::start().expect("start");
+        mock.set_title("Built");
+        let client = Client::builder()
+            .url(mock.url())
+            .capability("browserName", "mock")
+            .build()
+            .expect("build");
+        assert_eq!(client.title().expect("title"), "Built");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn talks_over_unix_sockets() {
+        use std::io::{BufRead, BufReader, Write};
+        use std::os::unix::net::UnixListener;
+
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("driver.sock");
+        let listener = UnixListener::bind(&path).expect("bind");
+        let driver = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for value in &[json!({"sessionId": "uds"}), json!("Socket"), json!(null)] {
+                let (conn, _) = listener.accept().expect("accept");
+                let mut reader = BufReader::new(conn);
+                let mut line = String::new();
+                reader.read_line(&mut line).expect("read");
+                requests.push(line.trim().to_string());
+                let mut len = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).expect("read");
+                    if header == "\r\n" {
+                        break;
+                    }
+                    if let Some(n) = header.strip_prefix("Content-Length: ") {
+                        len = n.trim().parse().expect("length");
+                    }
+                }
+                std::io::Read::read_exact(&mut reader, &mut vec![0; len]).expect("body");
+                let body = json!({ "value": value }).to_string();
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+                .expect("write");
+            }
+            requests
+        });
+
+        let client = Client::builder().unix_socket(&path).build().expect("build");
+        assert_eq!(client.title().expect("title"), "Socket");
+        drop(client);
+        assert_eq!(
+            driver.join().expect("join"),
+            vec![
+                "POST /session HTTP/1.1",
+                "GET /session/uds/title HTTP/1.1",
+                "DELETE /session/uds HTTP/1.1",
+            ]
+        );
+    }
+
+    #[test]
+    fn requires_a_url() {
+        assert!(ClientBuilder::new().build().is_err());
+    }
+}
@@ -0,0 +1,276 @@
+//! Recording the commands sent during a session, so that they can be replayed
+//! later without a browser, eg: for fast, deterministic tests of page
+//! objects.
+//!
+//! ```no_run
+//! # fn example(client: &mut sulfur::Client) -> Result<(), failure::Error> {
+//! use sulfur::cassette::{Cassette, CassetteRecorder};
+//! use sulfur::Client;
+//!
+//! let recorder = CassetteRecorder::new();
+//! recorder.attach(client);
+//! client.visit("https://example.com/")?;
+//! recorder.save("tests/cassettes/example.json")?;
+//!
+//! // Later, without a browser:
+//! let client = Client::replay(Cassette::load("tests/cassettes/example.json")?);
+//! client.visit("https://example.com/")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use failure::{Error, ResultExt};
+
+use crate::client::{Client, WdError};
+use crate::command::Command;
+use crate::command_log;
+
+/// A recorded session: the commands sent, and the driver's responses.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Cassette {
+    pub(crate) session_id: String,
+    interactions: Vec<Interaction>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Interaction {
+    command: String,
+    method: String,
+    path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    request: Option<serde_json::Value>,
+    response: Response,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum Response {
+    Value(serde_json::Value),
+    Error(WdError),
+    // Eg: a transport error, which we can only reproduce the message of.
+    Failure(String),
+}
+
+/// Records the commands sent by a [`Client`] into a [`Cassette`].
+#[derive(Debug, Clone, Default)]
+pub struct CassetteRecorder {
+    cassette: Arc<Mutex<Cassette>>,
+}
+
+// Replays a cassette in order, failing on the first unexpected command.
+#[derive(Debug)]
+pub(crate) struct Player {
+    remaining: Mutex<VecDeque<Interaction>>,
+}
+
+impl Cassette {
+    /// Reads a cassette saved with [`Cassette::save`].
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let content = fs::read(path).with_context(|_| format!("Reading cassette {:?}", path))?;
+        let cassette = serde_json::from_slice(&content)
+            .with_context(|_| format!("Parsing cassette {:?}", path))?;
+        Ok(cassette)
+    }
+
+    /// Writes the cassette as JSON.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        let content = serde_json::to_vec_pretty(self)?;
+        fs::write(path, content).with_context(|_| format!("Writing cassette {:?}", path))?;
+        Ok(())
+    }
+
+    /// The number of commands recorded.
+    pub fn len(&self) -> usize {
+        self.interactions.len()
+    }
+
+    /// Whether no commands have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.interactions.is_empty()
+    }
+
+    fn record(&mut self, cmd: &Command, mut response: Response) {
+        if let Response::Value(value) = &mut response {
+            command_log::redact_response_secrets(cmd.name, value);
+        }
+        self.interactions.push(Interaction {
+            command: cmd.name.to_string(),
+            method: cmd.method.to_string(),
+            path: cmd.path.clone(),
+            request: redacted_request(cmd),
+            response,
+        })
+    }
+}
+
+impl CassetteRecorder {
+    /// Creates a recorder with an empty cassette.
+    pub fn new() -> Self {
+        CassetteRecorder::default()
+    }
+
+    /// Records every subsequent command sent by `client`. Unlike the
+    /// [`Client::command_log`], responses are recorded in full, although
+    /// secrets (eg: typed text and cookie values) are still redacted.
+    pub fn attach(&self, client: &mut Client) {
+        if let Some(id) = client.session_id() {
+            self.cassette.lock().expect("unpoison").session_id = id.to_string();
+        }
+        let cassette = self.cassette.clone();
+        client.on_command(
+            |_: &Client, _: &Command| (),
+            move |_, cmd, outcome| {
+                let response = match outcome.result {
+                    Ok(value) => Response::Value(value.clone()),
+                    Err(e) => match e.downcast_ref::<WdError>() {
+                        Some(e) => Response::Error(e.clone()),
+                        None => Response::Failure(e.to_string()),
+                    },
+                };
+                cassette.lock().expect("unpoison").record(cmd, response);
+            },
+        );
+    }
+
+    /// A copy of the commands recorded so far.
+    pub fn cassette(&self) -> Cassette {
+        self.cassette.lock().expect("unpoison").clone()
+    }
+
+    /// Saves the commands recorded so far; see [`Cassette::save`].
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        self.cassette().save(path)
+    }
+}
+
+impl Player {
+    pub(crate) fn new(cassette: Cassette) -> Self {
+        Player {
+            remaining: Mutex::new(cassette.interactions.into()),
+        }
+    }
+
+    pub(crate) fn respond(&self, cmd: &Command) -> Result<serde_json::Value, Error> {
+        let mut remaining = self.remaining.lock().expect("unpoison");
+        let next = match remaining.pop_front() {
+            Some(next) => next,
+            // Recordings are usually saved before the client is dropped.
+            None if cmd.name == "close" => return Ok(serde_json::Value::Null),
+            None => bail!("Cassette exhausted; unexpected {} {}", cmd.method, cmd.path),
+        };
+        let request = redacted_request(cmd);
+        if next.method != cmd.method.as_str() || next.path != cmd.path || next.request != request {
+            bail!(
+                "Cassette mismatch: expected {} {} {:?}, got {} {} {:?}",
+                next.method,
+                next.path,
+                next.request,
+                cmd.method,
+                cmd.path,
+                request
+            );
+        }
+        match next.response {
+            Response::Value(value) => Ok(value),
+            Response::Error(e) => Err(e.into()),
+            Response::Failure(message) => Err(failure::err_msg(message)),
+        }
+    }
+}
+
+// The request body of `cmd`, in full, but for secrets such as typed text.
+fn redacted_request(cmd: &Command) -> Option<serde_json::Value> {
+    let mut body = cmd.body.clone()?;
+    command_log::redact_request_secrets(cmd.name, &mut body);
+    Some(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::client::ErrorKind;
+
+    fn interaction(cmd: &Command, response: Response) -> Cassette {
+        let mut cassette = Cassette::default();
+        cassette.record(cmd, response);
+        cassette
+    }
+
+    #[test]
+    fn replays_recorded_responses() {
+        let cmd = Command::get("title", &["session", "abc", "title"]);
+        let player = Player::new(interaction(&cmd, Response::Value(json!("Hello"))));
+        assert_eq!(player.respond(&cmd).expect("respond"), json!("Hello"));
+        assert!(
+            player.respond(&cmd).is_err(),
+            "Cassette should be exhausted"
+        );
+        let close = Command::delete("close", &["session", "abc"]);
+        assert_eq!(player.respond(&close).expect("close"), json!(null));
+    }
+
+    #[test]
+    fn replays_driver_errors() {
+        let cmd = Command::post("find_element", &["session", "abc", "element"], json!({}));
+        let err = WdError::new(ErrorKind::NoSuchElement, "nope");
+        let player = Player::new(interaction(&cmd, Response::Error(err)));
+        let res = player.respond(&cmd).expect_err("should fail");
+        assert_eq!(
+            res.downcast_ref::<WdError>().map(WdError::kind),
+            Some(ErrorKind::NoSuchElement)
+        );
+    }
+
+    #[test]
+    fn clients_replay_cassettes() {
+        let cmd = Command::get("title", &["session", "abc", "title"]);
+        let mut cassette = interaction(&cmd, Response::Value(json!("Hello")));
+        cassette.session_id = "abc".into();
+        let client = Client::replay(cassette);
+        assert_eq!(client.title().expect("title"), "Hello");
+        assert!(client.current_url().is_err());
+    }
+
+    #[test]
+    fn rejects_unexpected_commands() {
+        let cmd = Command::get("title", &["session", "abc", "title"]);
+        let player = Player::new(interaction(&cmd, Response::Value(json!("Hello"))));
+        let other = Command::get("current_url", &["session", "abc", "url"]);
+        assert!(player.respond(&other).is_err());
+    }
+
+    #[test]
+    fn redacts_secrets() {
+        let cmd = Command::post(
+            "send_keys",
+            &["session", "abc", "element", "e1", "value"],
+            json!({ "text": "hunter2" }),
+        );
+        let cassette = interaction(&cmd, Response::Value(json!(null)));
+        assert!(!serde_json::to_string(&cassette)
+            .expect("serialize")
+            .contains("hunter2"));
+        let player = Player::new(cassette);
+        assert_eq!(player.respond(&cmd).expect("respond"), json!(null));
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let cmd = Command::post("url", &["session", "abc", "url"], json!({"url": "/"}));
+        let cassette = interaction(&cmd, Response::Failure("connection refused".into()));
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("cassette.json");
+        cassette.save(&path).expect("save");
+        assert_eq!(Cassette::load(&path).expect("load"), cassette);
+    }
+}
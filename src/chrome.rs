@@ -1,6 +1,8 @@
 //! Functionality for starting a dedicated chromedriver and webdriver session for Chrome.
 
+use std::ffi::OsString;
 use std::fmt;
+use std::path::PathBuf;
 use std::process::{Child, Command};
 use std::time;
 
@@ -10,21 +12,33 @@ use reqwest;
 
 use crate::client::{Capabilities, Client};
 use crate::driver::{self, DriverHolder};
-use crate::junk_drawer::{self, unused_port_no};
+use crate::junk_drawer::{base_url, merge_json, unused_port_no};
+use crate::process::{self, DEFAULT_GRACE_PERIOD};
+use crate::wait::wait_until;
 
 const START_TIMEOUT: time::Duration = time::Duration::from_secs(120);
+// How many times to try starting the driver, as another process may take the
+// port we picked before the driver binds it.
+const START_ATTEMPTS: usize = 3;
+// How long to wait for chromedriver to exit after asking it to shut down,
+// before killing it.
+const SHUTDOWN_TIMEOUT: time::Duration = time::Duration::from_secs(10);
 
 /// Represents a running instance of `chromedriver`.
 pub struct Driver {
-    child: Child,
-    port: u16,
+    // Absent for drivers we attached to with `connect`.
+    child: Option<Child>,
+    url: String,
+    log_path: Option<PathBuf>,
     http: reqwest::Client,
+    grace_period: time::Duration,
 }
 
 /// Represents the log level passed to chromedriver.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub enum LogLevel {
     /// OFF
+    #[default]
     Off,
     /// SEVERE
     Severe,
@@ -42,21 +56,41 @@ pub enum LogLevel {
 #[derive(Clone, Default, Debug)]
 pub struct DriverConfig {
     log_level: LogLevel,
+    grace_period: Option<time::Duration>,
+    env: Vec<(String, String)>,
+    path: Option<PathBuf>,
+    log_path: Option<PathBuf>,
 }
 /// Allows extra configuration for chrome instances.
 #[derive(Clone, Default)]
 pub struct Config {
     headless: bool,
+    performance_logging: bool,
+    user_agent: Option<String>,
+    lang: Option<String>,
+    download_dir: Option<PathBuf>,
+    incognito: bool,
+    guest: bool,
+    browser_version: Option<String>,
+    platform_name: Option<String>,
+    proxy: Option<String>,
+    args: Vec<String>,
+    capabilities: serde_json::Map<String, serde_json::Value>,
+    host_rules: Vec<(String, String)>,
+    allow_insecure_localhost: bool,
+    container_flags: bool,
+    window_size: Option<(u32, u32)>,
 }
 
+// The window size used by `Config::ci_defaults`.
+const CI_WINDOW_SIZE: (u32, u32) = (1920, 1080);
+
 /// Start a chromedriver instance, along with a new browser session.
 pub fn start(config: &Config) -> Result<DriverHolder, Error> {
     let driver = Driver::start()?;
     let client = driver.new_session_config(config)?;
-    Ok(DriverHolder {
-        driver: Box::new(driver),
-        client: client,
-    })
+    let config = config.clone();
+    Ok(DriverHolder::new(Box::new(driver), client).restart_with(move || start(&config)))
 }
 
 impl Driver {
@@ -65,24 +99,81 @@ impl Driver {
         Self::driver_config(&DriverConfig::default())
     }
 
-    /// Start chromedriver with the given configuration.
+    /// Attach to a chromedriver that is already running at `url`, eg: one
+    /// managed by systemd or a container supervisor. The driver must be
+    /// responding to requests; it is left running when this is closed.
+    pub fn connect(url: &str) -> Result<Self, Error> {
+        let driver = Driver {
+            child: None,
+            url: base_url(url),
+            log_path: None,
+            http: reqwest::Client::new(),
+            grace_period: DEFAULT_GRACE_PERIOD,
+        };
+        if !driver.is_healthy() {
+            bail!("chromedriver at {} is not responding", driver.url);
+        }
+        info!("Attached to chromedriver at {}", driver.url);
+        Ok(driver)
+    }
+
+    /// Start chromedriver with the given configuration. If it exits before it
+    /// is ready (eg: because its port was taken in the meantime), it is
+    /// started again on another port.
     pub fn driver_config(config: &DriverConfig) -> Result<Self, Error> {
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let mut driver = Self::spawn(config)?;
+            match wait_until(START_TIMEOUT, || {
+                driver.ensure_still_alive()?;
+                Ok(driver.is_healthy())
+            }) {
+                Ok(true) => {
+                    info!("Setup done! running at {}", driver.url);
+                    return Ok(driver);
+                }
+                Ok(false) => bail!(
+                    "chromedriver at {} was not ready after {:?}",
+                    driver.url,
+                    START_TIMEOUT
+                ),
+                Err(e) if attempts < START_ATTEMPTS && driver.ensure_still_alive().is_err() => {
+                    warn!("chromedriver exited during startup, retrying: {}", e)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    // Spawns the driver on an unused port, without waiting for it.
+    fn spawn(config: &DriverConfig) -> Result<Self, Error> {
         let http = reqwest::Client::new();
         let port = unused_port_no()?;
         debug!("Spawning chrome driver on port: {:?}", port);
-        let mut cmd = Command::new("chromedriver");
+        let binary = config
+            .path
+            .as_deref()
+            .unwrap_or_else(|| "chromedriver".as_ref());
+        let mut cmd = Command::new(binary);
         cmd.arg(format!("--port={}", port));
         cmd.arg(format!("--log-level={}", config.log_level));
+        if let Some(path) = &config.log_path {
+            let mut arg = OsString::from("--log-path=");
+            arg.push(path);
+            cmd.arg(arg);
+        }
+        cmd.envs(config.env.iter().map(|(k, v)| (k, v)));
         debug!("Starting command: {:?}", cmd);
-        let child = cmd.spawn().context("Spawning chrome")?;
-
-        let mut driver = Driver { child, port, http };
+        let child = process::spawn(&mut cmd).context("Spawning chrome")?;
 
-        junk_drawer::wait_until(START_TIMEOUT, || {
-            driver.ensure_still_alive()?;
-            Ok(driver.is_healthy())
-        })?;
-        info!("Setup done! running on port {:?}", driver.port);
+        let driver = Driver {
+            child: Some(child),
+            url: format!("http://127.0.0.1:{}/", port),
+            log_path: config.log_path.clone(),
+            http,
+            grace_period: config.grace_period.unwrap_or(DEFAULT_GRACE_PERIOD),
+        };
 
         Ok(driver)
     }
@@ -94,35 +185,90 @@ impl Driver {
 
     /// Start a new webdriver session with the given config.
     pub fn new_session_config(&self, config: &Config) -> Result<Client, Error> {
-        info!("Starting new session from instance at {}", self.port);
-        let client =
-            Client::new_with_http(&self.url(), config.to_capabilities(), self.http.clone())?;
+        info!("Starting new session from instance at {}", self.url);
+        let mut client =
+            Client::new_with_http(&self.url, config.to_capabilities(), self.http.clone())?;
+        if let Some(path) = &self.log_path {
+            client.set_driver_log(path);
+        }
         Ok(client)
     }
 
-    /// Forcibly terminate the chromedriver instance. This assumes that the
-    /// webdriver client session has been shut down seperately.
+    /// Shut down the chromedriver instance. Any sessions that are still
+    /// open are deleted, and chromedriver is asked to exit, so that browser
+    /// profiles are not left locked. If it has not exited after a timeout,
+    /// it is terminated; see [`DriverConfig::grace_period`]. Drivers
+    /// attached to with [`connect`](Self::connect) are left running. On
+    /// Windows, browsers are not cleaned up if chromedriver has to be killed.
     pub fn close(&mut self) -> Result<(), Error> {
-        debug!("Closing child: {:?}", self.child);
-        match self.child.try_wait()? {
-            Some(status) => info!("Child already exited with status: {}", status),
+        let child = match &mut self.child {
+            Some(child) => child,
             None => {
-                self.child.kill()?;
-                // Err(e) if e.kind() == std::io::ErrorKind::InvalidInput => {}
-                self.child.wait()?;
-                debug!("Child killed: {:?}", self.child);
+                debug!("Leaving chromedriver at {} running", self.url);
+                return Ok(());
+            }
+        };
+        debug!("Closing child: {:?}", child);
+        if process::has_exited(child)? {
+            info!("Child {} already exited", child.id());
+        } else {
+            if let Err(e) = self.shutdown() {
+                warn!("Requesting chromedriver shutdown: {:?}", e);
+            }
+            let child = self.child.as_mut().expect("child");
+            let exited = wait_until(SHUTDOWN_TIMEOUT, || process::has_exited(child))?;
+            if !exited {
+                warn!(
+                    "chromedriver still running after {:?}; terminating it",
+                    SHUTDOWN_TIMEOUT
+                );
             }
         }
+        // Also cleans up any browser processes left behind.
+        let child = self.child.as_mut().expect("child");
+        process::terminate(child, self.grace_period)?;
+        debug!("Child terminated: {:?}", child);
         Ok(())
     }
 
-    fn url(&self) -> String {
-        format!("http://127.0.0.1:{}/", self.port)
+    // Deletes any outstanding sessions, then asks chromedriver to exit.
+    fn shutdown(&self) -> Result<(), Error> {
+        #[derive(Deserialize)]
+        struct Session {
+            id: String,
+        }
+        #[derive(Deserialize)]
+        struct Sessions {
+            value: Vec<Session>,
+        }
+
+        let url = format!("{}sessions", self.url);
+        match self
+            .http
+            .get(&url)
+            .send()
+            .and_then(|mut r| r.json::<Sessions>())
+        {
+            Ok(sessions) => {
+                for session in sessions.value {
+                    info!("Deleting outstanding session: {}", session.id);
+                    let url = format!("{}session/{}", self.url, session.id);
+                    if let Err(e) = self.http.delete(&url).send() {
+                        warn!("Deleting session {}: {:?}", session.id, e);
+                    }
+                }
+            }
+            Err(e) => debug!("Listing sessions: {:?}", e),
+        }
+
+        let url = format!("{}shutdown", self.url);
+        self.http.get(&url).send()?;
+        Ok(())
     }
 
     // §8.3 Status
     fn is_healthy(&self) -> bool {
-        let url = format!("{}status", self.url());
+        let url = format!("{}status", self.url);
         match self.http.get(&url).send() {
             Err(e) => {
                 warn!("Could not fetch {}: {:?}", url, e);
@@ -136,13 +282,15 @@ impl Driver {
     }
 
     fn ensure_still_alive(&mut self) -> Result<(), Error> {
-        match self.child.try_wait()? {
-            Some(status) => {
-                warn!("child exited with {}", status);
-                bail!("Child process failed: {:?}", status)
-            }
-            None => Ok(()),
+        let child = match &mut self.child {
+            Some(child) => child,
+            None => return Ok(()),
+        };
+        if process::has_exited(child)? {
+            warn!("child {} exited", child.id());
+            bail!("Child process {} exited", child.id())
         }
+        Ok(())
     }
 }
 
@@ -157,12 +305,49 @@ impl Drop for Driver {
 
 impl driver::Driver for Driver {
     fn close(&mut self) -> Result<(), Error> {
-        self.child.kill()?;
-        self.child.wait()?;
+        Driver::close(self)
+    }
+
+    fn check_health(&mut self) -> Result<(), Error> {
+        self.ensure_still_alive()?;
+        if !self.is_healthy() {
+            bail!("chromedriver at {} is not responding", self.url);
+        }
         Ok(())
     }
 }
 
+impl DriverConfig {
+    /// When chromedriver has to be terminated, how long to wait after
+    /// sending `SIGTERM` before killing it. Defaults to 5 seconds. On
+    /// platforms other than Unix, chromedriver is killed immediately.
+    pub fn grace_period(&mut self, grace_period: time::Duration) -> &mut Self {
+        self.grace_period = Some(grace_period);
+        self
+    }
+
+    /// Write chromedriver's log to `path` (as `--log-path`), rather than
+    /// stderr. The log is included in artifacts captured from sessions on
+    /// this driver; see [`Client::capture_artifacts`].
+    pub fn log_path<P: Into<PathBuf>>(&mut self, path: P) -> &mut Self {
+        self.log_path = Some(path.into());
+        self
+    }
+
+    /// Run the chromedriver binary at `path`, rather than finding it on `$PATH`.
+    pub fn path<P: Into<PathBuf>>(&mut self, path: P) -> &mut Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Set the environment variable `key` for chromedriver, and the browsers
+    /// it starts, eg: `DISPLAY` for use with [`Xvfb`](crate::xvfb::Xvfb).
+    pub fn env<K: Into<String>, V: Into<String>>(&mut self, key: K, value: V) -> &mut Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+}
+
 impl Config {
     /// Speciofy that if the session should be headless, ie: not show the UI.
     pub fn headless(&mut self, headless: bool) -> &mut Self {
@@ -170,38 +355,250 @@ impl Config {
         self
     }
 
+    /// Record DevTools events (eg: network activity) in the performance
+    /// log, for retrieval with [`Client::performance_logs`].
+    pub fn performance_logging(&mut self, enabled: bool) -> &mut Self {
+        self.performance_logging = enabled;
+        self
+    }
+
+    /// Report `user_agent` as the browser's user agent string.
+    pub fn user_agent<S: Into<String>>(&mut self, user_agent: S) -> &mut Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Use `locale` (eg: `fr-FR`) for the browser's UI, and request it via
+    /// the `Accept-Language` header and `navigator.language`.
+    pub fn lang<S: Into<String>>(&mut self, locale: S) -> &mut Self {
+        self.lang = Some(locale.into());
+        self
+    }
+
+    /// Save downloads to `dir` without prompting, eg: for use with
+    /// [`before_download`](crate::wait::before_download).
+    pub fn download_dir<P: Into<PathBuf>>(&mut self, dir: P) -> &mut Self {
+        self.download_dir = Some(dir.into());
+        self
+    }
+
+    /// Run in an incognito window, so that cookies and storage are never
+    /// written to the profile, and cannot leak between sessions.
+    pub fn incognito(&mut self, incognito: bool) -> &mut Self {
+        self.incognito = incognito;
+        self
+    }
+
+    /// Run in guest mode, ie: with a fresh, temporary profile, which is
+    /// discarded when the browser exits.
+    pub fn guest(&mut self, guest: bool) -> &mut Self {
+        self.guest = guest;
+        self
+    }
+
+    /// Send HTTP and HTTPS traffic via the proxy at `address` (ie:
+    /// `host:port`), including that for loopback addresses, which Chrome
+    /// otherwise never proxies. See eg:
+    /// [`RecordingProxy`](crate::proxy::RecordingProxy).
+    pub fn proxy<S: Into<String>>(&mut self, address: S) -> &mut Self {
+        self.proxy = Some(address.into());
+        self
+    }
+
+    /// Resolve hosts matching `pattern` (eg: `*.example.com`) to `target`
+    /// (eg: `127.0.0.1`), so that production hostnames can be pointed at a
+    /// local test server. Ports are unaffected. May be called repeatedly;
+    /// the first matching rule applies.
+    pub fn host_rule<P: Into<String>, T: Into<String>>(
+        &mut self,
+        pattern: P,
+        target: T,
+    ) -> &mut Self {
+        self.host_rules.push((pattern.into(), target.into()));
+        self
+    }
+
+    /// Accept invalid (eg: self-signed) TLS certificates for `localhost`
+    /// only, rather than for every site as with `acceptInsecureCerts`, eg:
+    /// for a test server on `127.0.0.1`. This is a command line switch, as
+    /// there is no capability scoped to a host. Firefox has no equivalent,
+    /// so use `acceptInsecureCerts` via `gecko::Config::capability` there.
+    pub fn allow_insecure_localhost(&mut self, allow: bool) -> &mut Self {
+        self.allow_insecure_localhost = allow;
+        self
+    }
+
+    /// Start the browser window at `width` by `height` pixels, rather than
+    /// a size that depends on the display (or lack of one).
+    pub fn window_size(&mut self, width: u32, height: u32) -> &mut Self {
+        self.window_size = Some((width, height));
+        self
+    }
+
+    /// Apply the flags needed to run reliably in a container, eg: on CI:
+    /// `--no-sandbox` (containers usually lack the namespaces Chrome's
+    /// sandbox needs), `--disable-dev-shm-usage` (as `/dev/shm` is often
+    /// tiny), `--disable-gpu`, and a fixed 1920x1080 window.
+    pub fn ci_defaults(&mut self) -> &mut Self {
+        self.container_flags = true;
+        let (width, height) = CI_WINDOW_SIZE;
+        self.window_size(width, height)
+    }
+
+    /// Pass `arg` on the Chrome command line, eg: for switches without a
+    /// dedicated setting.
+    pub fn arg<S: Into<String>>(&mut self, arg: S) -> &mut Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Request the capability `name`. Objects are merged into those this
+    /// configuration generates, eg: `"goog:chromeOptions"` with `{"binary": ...}`
+    /// keeps the arguments set here.
+    pub fn capability<K: Into<String>, V: Into<serde_json::Value>>(
+        &mut self,
+        name: K,
+        value: V,
+    ) -> &mut Self {
+        self.capabilities.insert(name.into(), value.into());
+        self
+    }
+
+    /// Require the given browser version, eg: `80`.
+    pub fn browser_version<S: Into<String>>(&mut self, version: S) -> &mut Self {
+        self.browser_version = Some(version.into());
+        self
+    }
+
+    /// Require the given platform, eg: `linux`.
+    pub fn platform_name<S: Into<String>>(&mut self, platform: S) -> &mut Self {
+        self.platform_name = Some(platform.into());
+        self
+    }
+
     fn to_capabilities(&self) -> Capabilities {
         let mut args = vec![];
         if self.headless {
-            args.push("--headless")
+            args.push("--headless".to_string())
         }
-        Capabilities {
-            always_match: json!({
-               "browserName": "chrome",
-               "goog:chromeOptions" : {
-                   "w3c" : true,
-                   "args": args,
-               }
-            }),
+        if self.incognito {
+            args.push("--incognito".to_string())
         }
-    }
-}
-
-impl Default for LogLevel {
-    fn default() -> Self {
-        LogLevel::Off
+        if self.guest {
+            args.push("--guest".to_string())
+        }
+        if self.allow_insecure_localhost {
+            args.push("--allow-insecure-localhost".to_string())
+        }
+        if self.container_flags {
+            args.push("--no-sandbox".to_string());
+            args.push("--disable-dev-shm-usage".to_string());
+            args.push("--disable-gpu".to_string());
+        }
+        if let Some((width, height)) = self.window_size {
+            args.push(format!("--window-size={},{}", width, height))
+        }
+        if let Some(user_agent) = &self.user_agent {
+            args.push(format!("--user-agent={}", user_agent))
+        }
+        if self.proxy.is_some() {
+            args.push("--proxy-bypass-list=<-loopback>".to_string())
+        }
+        if !self.host_rules.is_empty() {
+            let rules = self
+                .host_rules
+                .iter()
+                .map(|(pattern, target)| format!("MAP {} {}", pattern, target))
+                .collect::<Vec<_>>();
+            args.push(format!("--host-resolver-rules={}", rules.join(", ")))
+        }
+        args.extend(self.args.iter().cloned());
+        let mut prefs = json!({});
+        if let Some(lang) = &self.lang {
+            args.push(format!("--lang={}", lang));
+            prefs["intl.accept_languages"] = json!(lang);
+        }
+        if let Some(dir) = &self.download_dir {
+            prefs["download.default_directory"] = json!(dir);
+            prefs["download.prompt_for_download"] = json!(false);
+        }
+        let mut always_match = json!({
+           "browserName": "chrome",
+           "goog:chromeOptions" : {
+               "w3c" : true,
+               "args": args,
+               "prefs": prefs,
+           }
+        });
+        if self.performance_logging {
+            always_match["goog:loggingPrefs"] = json!({ "performance": "ALL" });
+        }
+        if let Some(version) = &self.browser_version {
+            always_match["browserVersion"] = json!(version);
+        }
+        if let Some(platform) = &self.platform_name {
+            always_match["platformName"] = json!(platform);
+        }
+        if let Some(proxy) = &self.proxy {
+            always_match["proxy"] = json!({
+                "proxyType": "manual",
+                "httpProxy": proxy,
+                "sslProxy": proxy,
+            });
+        }
+        for (name, value) in &self.capabilities {
+            merge_json(&mut always_match[name], value);
+        }
+        Capabilities { always_match }
     }
 }
 
 impl fmt::Display for LogLevel {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            &LogLevel::Off => write!(fmt, "OFF"),
-            &LogLevel::Severe => write!(fmt, "SEVERE"),
-            &LogLevel::Warning => write!(fmt, "WARNING"),
-            &LogLevel::Info => write!(fmt, "INFO"),
-            &LogLevel::Debug => write!(fmt, "DEBUG"),
-            &LogLevel::All => write!(fmt, "ALL"),
+        match *self {
+            LogLevel::Off => write!(fmt, "OFF"),
+            LogLevel::Severe => write!(fmt, "SEVERE"),
+            LogLevel::Warning => write!(fmt, "WARNING"),
+            LogLevel::Info => write!(fmt, "INFO"),
+            LogLevel::Debug => write!(fmt, "DEBUG"),
+            LogLevel::All => write!(fmt, "ALL"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(config: &Config) -> serde_json::Value {
+        config.to_capabilities().always_match["goog:chromeOptions"]["args"].clone()
+    }
+
+    #[test]
+    fn only_allows_insecure_certs_for_localhost() {
+        let mut config = Config::default();
+        assert_eq!(args(&config), json!([]));
+        config.allow_insecure_localhost(true);
+        assert_eq!(args(&config), json!(["--allow-insecure-localhost"]));
+        assert_eq!(
+            config
+                .to_capabilities()
+                .always_match
+                .get("acceptInsecureCerts"),
+            None
+        );
+    }
+
+    #[test]
+    fn incognito_is_a_switch() {
+        assert_eq!(
+            args(Config::default().incognito(true)),
+            json!(["--incognito"])
+        );
+    }
+
+    #[test]
+    fn guest_is_a_switch() {
+        assert_eq!(args(Config::default().guest(true)), json!(["--guest"]));
+    }
+}
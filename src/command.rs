@@ -0,0 +1,163 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+use std::time;
+
+use failure::Error;
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+
+use crate::client::Client;
+use crate::recovery::SessionRecovered;
+
+const QUERY_ENCODE_SET: &AsciiSet = &CONTROLS.add(b' ').add(b'"').add(b'<').add(b'>').add(b'`');
+const DEFAULT_ENCODE_SET: &AsciiSet = &QUERY_ENCODE_SET.add(b'`').add(b'?').add(b'{').add(b'}');
+const PATH_SEGMENT_ENCODE_SET: &AsciiSet = &DEFAULT_ENCODE_SET.add(b'%').add(b'/');
+
+/// A single webdriver command, as sent to the driver.
+#[derive(Debug, Clone)]
+pub struct Command {
+    /// The name of the command, eg: `find_element`.
+    pub name: &'static str,
+    /// The HTTP method used for the request.
+    pub method: http::Method,
+    /// The path of the endpoint, relative to the driver's base URL.
+    pub path: String,
+    /// The JSON request body, if any.
+    pub body: Option<serde_json::Value>,
+}
+
+/// Describes how a command completed, as passed to command hooks.
+#[derive(Debug)]
+pub struct CommandOutcome<'a> {
+    /// The `value` member of the driver's response, or the error.
+    pub result: Result<&'a serde_json::Value, &'a Error>,
+    /// How long the command took.
+    pub elapsed: time::Duration,
+}
+
+type BeforeHook = Arc<dyn Fn(&Client, &Command) + Send + Sync>;
+type AfterHook = Arc<dyn Fn(&Client, &Command, &CommandOutcome) + Send + Sync>;
+type RecoveredHook = Arc<dyn Fn(&Client, &SessionRecovered) + Send + Sync>;
+type CaptureHook = Arc<dyn Fn(&Client, &Path) -> Result<(), Error> + Send + Sync>;
+
+#[derive(Clone, Default)]
+pub(crate) struct Hooks {
+    // Hooks with a key replace any earlier hook with the same key.
+    before: Vec<(Option<&'static str>, BeforeHook)>,
+    after: Vec<(Option<&'static str>, AfterHook)>,
+    recovered: Vec<RecoveredHook>,
+    capture: Vec<(&'static str, CaptureHook)>,
+}
+
+impl Command {
+    pub(crate) fn get(name: &'static str, segments: &[&str]) -> Self {
+        Command::new(name, http::Method::GET, segments, None)
+    }
+
+    pub(crate) fn post(name: &'static str, segments: &[&str], body: serde_json::Value) -> Self {
+        Command::new(name, http::Method::POST, segments, Some(body))
+    }
+
+    pub(crate) fn delete(name: &'static str, segments: &[&str]) -> Self {
+        Command::new(name, http::Method::DELETE, segments, None)
+    }
+
+    fn new(
+        name: &'static str,
+        method: http::Method,
+        segments: &[&str],
+        body: Option<serde_json::Value>,
+    ) -> Self {
+        let mut path = String::new();
+        for (i, seg) in segments.iter().enumerate() {
+            let enc: Cow<'_, str> = utf8_percent_encode(seg, PATH_SEGMENT_ENCODE_SET).into();
+            if i > 0 {
+                path.push('/')
+            }
+            path.push_str(&enc);
+        }
+
+        Command {
+            name,
+            method,
+            path,
+            body,
+        }
+    }
+}
+
+impl Hooks {
+    pub(crate) fn add<B, A>(&mut self, before: B, after: A)
+    where
+        B: Fn(&Client, &Command) + Send + Sync + 'static,
+        A: Fn(&Client, &Command, &CommandOutcome) + Send + Sync + 'static,
+    {
+        self.before.push((None, Arc::new(before)));
+        self.after.push((None, Arc::new(after)));
+    }
+
+    // As `add`, but replaces any hooks previously added under `key`.
+    pub(crate) fn replace<B, A>(&mut self, key: &'static str, before: B, after: A)
+    where
+        B: Fn(&Client, &Command) + Send + Sync + 'static,
+        A: Fn(&Client, &Command, &CommandOutcome) + Send + Sync + 'static,
+    {
+        self.before.retain(|(k, _)| *k != Some(key));
+        self.after.retain(|(k, _)| *k != Some(key));
+        self.before.push((Some(key), Arc::new(before)));
+        self.after.push((Some(key), Arc::new(after)));
+    }
+
+    pub(crate) fn add_recovered<F>(&mut self, hook: F)
+    where
+        F: Fn(&Client, &SessionRecovered) + Send + Sync + 'static,
+    {
+        self.recovered.push(Arc::new(hook));
+    }
+
+    // Adds a hook that saves the artifact `name` into a directory of
+    // failure artifacts.
+    pub(crate) fn add_capture<F>(&mut self, name: &'static str, hook: F)
+    where
+        F: Fn(&Client, &Path) -> Result<(), Error> + Send + Sync + 'static,
+    {
+        self.capture.push((name, Arc::new(hook)));
+    }
+
+    pub(crate) fn before(&self, client: &Client, cmd: &Command) {
+        for (_, hook) in self.before.iter() {
+            hook(client, cmd)
+        }
+    }
+
+    pub(crate) fn after(&self, client: &Client, cmd: &Command, outcome: &CommandOutcome) {
+        for (_, hook) in self.after.iter() {
+            hook(client, cmd, outcome)
+        }
+    }
+
+    pub(crate) fn recovered(&self, client: &Client, event: &SessionRecovered) {
+        for hook in self.recovered.iter() {
+            hook(client, event)
+        }
+    }
+
+    pub(crate) fn capture(&self, client: &Client, dir: &Path) -> Vec<(&str, Result<(), Error>)> {
+        self.capture
+            .iter()
+            .map(|(name, hook)| (*name, hook(client, dir)))
+            .collect()
+    }
+}
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Hooks")
+            .field("before", &self.before.len())
+            .field("after", &self.after.len())
+            .field("recovered", &self.recovered.len())
+            .field("capture", &self.capture.len())
+            .finish()
+    }
+}
@@ -0,0 +1,113 @@
+use std::ops::Deref;
+
+use failure::Error;
+
+use crate::client::{Client, Window};
+
+/// Switches back to the parent frame when dropped, having been created by
+/// [`Client::enter_frame`]. This ensures that an early return (eg: via `?`)
+/// cannot leave the session in the wrong frame.
+///
+/// Derefs to the client, so commands may be run within the frame with eg:
+/// `frame.find(..)`.
+#[derive(Debug)]
+#[must_use = "the frame is exited as soon as the guard is dropped"]
+pub struct FrameGuard<'c> {
+    client: &'c Client,
+    active: bool,
+}
+
+impl<'c> FrameGuard<'c> {
+    pub(crate) fn new(client: &'c Client) -> Self {
+        FrameGuard {
+            client,
+            active: true,
+        }
+    }
+
+    /// Switches back to the parent frame, reporting any error, which would
+    /// otherwise only be logged when the guard is dropped.
+    pub fn exit(mut self) -> Result<(), Error> {
+        self.active = false;
+        self.client.switch_to_parent_frame()
+    }
+}
+
+impl<'c> Deref for FrameGuard<'c> {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        self.client
+    }
+}
+
+impl<'c> Drop for FrameGuard<'c> {
+    fn drop(&mut self) {
+        if !self.active {
+            return;
+        }
+        if let Err(e) = self.client.switch_to_parent_frame() {
+            warn!("Switching back to parent frame: {}", e);
+        }
+    }
+}
+
+/// Switches back to the previously current window when dropped, having been
+/// created by [`Client::enter_window`]. As with [`FrameGuard`], this derefs
+/// to the client.
+#[derive(Debug)]
+#[must_use = "the window is exited as soon as the guard is dropped"]
+pub struct WindowGuard<'c> {
+    client: &'c Client,
+    previous: Option<Window>,
+}
+
+impl<'c> WindowGuard<'c> {
+    pub(crate) fn new(client: &'c Client, previous: Window) -> Self {
+        WindowGuard {
+            client,
+            previous: Some(previous),
+        }
+    }
+
+    /// Switches back to the previous window, reporting any error, which
+    /// would otherwise only be logged when the guard is dropped.
+    pub fn exit(mut self) -> Result<(), Error> {
+        match self.previous.take() {
+            Some(previous) => self.client.switch_to_window(&previous),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'c> Deref for WindowGuard<'c> {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        self.client
+    }
+}
+
+impl<'c> Drop for WindowGuard<'c> {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            if let Err(e) = self.client.switch_to_window(&previous) {
+                warn!("Switching back to window {:?}: {}", previous, e);
+            }
+        }
+    }
+}
+
+// Combines the result of running code in a browsing context with that of
+// switching back out of it, preferring the former's error.
+pub(crate) fn restore<T>(result: Result<T, Error>, exited: Result<(), Error>) -> Result<T, Error> {
+    match (result, exited) {
+        (Ok(value), Ok(())) => Ok(value),
+        (Ok(_), Err(e)) => Err(e),
+        (Err(e), Ok(())) => Err(e),
+        (Err(e), Err(exit_err)) => {
+            warn!("Restoring browsing context after failure: {}", exit_err);
+            Err(e)
+        }
+    }
+}
@@ -0,0 +1,416 @@
+//! A direct connection to a page's DevTools endpoint, for the parts of the
+//! protocol that rely on events, which chromedriver does not pass on.
+
+use std::io;
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time;
+
+use failure::{Error, ResultExt};
+use tungstenite::{Message, WebSocket};
+
+use crate::browser_log::BrowserLogEntry;
+
+// How long to wait for the endpoint to answer a command.
+const COMMAND_TIMEOUT: time::Duration = time::Duration::from_secs(10);
+// How often the event loop checks whether it has been stopped.
+const POLL_INTERVAL: time::Duration = time::Duration::from_millis(200);
+
+/// Answers HTTP authentication challenges from a single origin in a page,
+/// until dropped.
+#[derive(Debug)]
+pub(crate) struct AuthHandler {
+    stop: Arc<AtomicBool>,
+}
+
+/// Forwards console messages and uncaught exceptions from a page to a
+/// channel, until dropped, or the receiver is.
+#[derive(Debug)]
+pub(crate) struct ConsoleListener {
+    stop: Arc<AtomicBool>,
+}
+
+struct Credentials {
+    origin: String,
+    username: String,
+    password: String,
+}
+
+struct Connection {
+    socket: WebSocket<TcpStream>,
+    next_id: u64,
+}
+
+impl AuthHandler {
+    /// Intercepts requests to `origin` in the page `target` of the browser
+    /// whose DevTools endpoint is at `debugger_address` (eg:
+    /// `localhost:9222`), and answers challenges from it.
+    pub(crate) fn start(
+        debugger_address: &str,
+        target: &str,
+        origin: &str,
+        username: &str,
+        password: &str,
+    ) -> Result<Self, Error> {
+        let origin = url::Url::parse(origin)
+            .with_context(|_| format!("Parsing origin {:?}", origin))?
+            .origin()
+            .ascii_serialization();
+        let creds = Credentials {
+            origin,
+            username: username.to_string(),
+            password: password.to_string(),
+        };
+
+        let mut conn = Connection::open_page(debugger_address, target)?;
+        conn.call(
+            "Fetch.enable",
+            json!({
+                "handleAuthRequests": true,
+                "patterns": [{ "urlPattern": format!("{}/*", creds.origin) }],
+            }),
+        )?;
+        debug!("Answering HTTP auth challenges from {}", creds.origin);
+
+        let stop = Arc::new(AtomicBool::new(false));
+        conn.spawn("sulfur-http-auth", stop.clone(), move |conn, event| {
+            if let Some((method, params)) = reply_to(event, &creds) {
+                if let Err(e) = conn.send(method, params) {
+                    warn!("Answering {}: {}", event, e);
+                    return false;
+                }
+            }
+            true
+        })?;
+        Ok(AuthHandler { stop })
+    }
+}
+
+impl Drop for AuthHandler {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+}
+
+impl ConsoleListener {
+    /// Sends console messages and uncaught exceptions in the page `target`
+    /// of the browser whose DevTools endpoint is at `debugger_address` to
+    /// `events`, as they happen.
+    pub(crate) fn start(
+        debugger_address: &str,
+        target: &str,
+        events: mpsc::Sender<BrowserLogEntry>,
+    ) -> Result<Self, Error> {
+        let mut conn = Connection::open_page(debugger_address, target)?;
+        conn.call("Runtime.enable", json!({}))?;
+        debug!("Listening for console events in {}", target);
+
+        let stop = Arc::new(AtomicBool::new(false));
+        conn.spawn(
+            "sulfur-console",
+            stop.clone(),
+            move |_, event| match console_entry(event) {
+                Some(entry) => events.send(entry).is_ok(),
+                None => true,
+            },
+        )?;
+        Ok(ConsoleListener { stop })
+    }
+}
+
+impl Drop for ConsoleListener {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+}
+
+impl Connection {
+    fn open(address: &str, url: &str) -> Result<Self, Error> {
+        let stream = TcpStream::connect(address)
+            .with_context(|_| format!("Connecting to DevTools at {}", address))?;
+        stream.set_read_timeout(Some(COMMAND_TIMEOUT))?;
+        let (socket, _) = tungstenite::client(url, stream)
+            .map_err(|e| format_err!("Connecting to DevTools at {}: {}", url, e))?;
+        Ok(Connection { socket, next_id: 0 })
+    }
+
+    fn open_page(debugger_address: &str, target: &str) -> Result<Self, Error> {
+        let url = format!("ws://{}/devtools/page/{}", debugger_address, target);
+        Connection::open(debugger_address, &url)
+    }
+
+    // Sends a command, and waits for its result.
+    fn call(
+        &mut self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value, Error> {
+        let id = self.send(method, params)?;
+        loop {
+            let msg = match self.socket.read_message()? {
+                Message::Text(text) => serde_json::from_str::<serde_json::Value>(&text)?,
+                _ => continue,
+            };
+            if msg["id"] != id {
+                continue;
+            }
+            if let Some(error) = msg.get("error") {
+                bail!("DevTools command {} failed: {}", method, error);
+            }
+            return Ok(msg["result"].clone());
+        }
+    }
+
+    fn send(&mut self, method: &str, params: serde_json::Value) -> Result<u64, Error> {
+        self.next_id += 1;
+        let msg = json!({ "id": self.next_id, "method": method, "params": params });
+        self.socket.write_message(Message::Text(msg.to_string()))?;
+        Ok(self.next_id)
+    }
+
+    // Passes each event to `handle` on a new thread, until it returns false,
+    // the connection closes, or `stop` is set.
+    fn spawn<F>(self, name: &str, stop: Arc<AtomicBool>, handle: F) -> Result<(), Error>
+    where
+        F: FnMut(&mut Connection, &serde_json::Value) -> bool + Send + 'static,
+    {
+        self.socket
+            .get_ref()
+            .set_read_timeout(Some(POLL_INTERVAL))?;
+        thread::Builder::new()
+            .name(name.into())
+            .spawn(move || self.handle_events(&stop, handle))?;
+        Ok(())
+    }
+
+    fn handle_events<F>(mut self, stop: &AtomicBool, mut handle: F)
+    where
+        F: FnMut(&mut Connection, &serde_json::Value) -> bool,
+    {
+        while !stop.load(Ordering::SeqCst) {
+            let event = match self.socket.read_message() {
+                Ok(Message::Text(text)) => text,
+                Ok(_) => continue,
+                Err(tungstenite::Error::Io(ref e))
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut =>
+                {
+                    continue
+                }
+                Err(e) => {
+                    debug!("DevTools connection closed: {}", e);
+                    return;
+                }
+            };
+            let event = match serde_json::from_str(&event) {
+                Ok(event) => event,
+                Err(_) => continue,
+            };
+            if !handle(&mut self, &event) {
+                break;
+            }
+        }
+        let _ = self.socket.close(None);
+    }
+}
+
+// The command that resumes the request paused by `event`, if any. Only
+// challenges from the server at `creds.origin` are answered; others (eg: from
+// a proxy) are left to the browser.
+fn reply_to(
+    event: &serde_json::Value,
+    creds: &Credentials,
+) -> Option<(&'static str, serde_json::Value)> {
+    let params = &event["params"];
+    let request_id = params["requestId"].clone();
+    match event["method"].as_str()? {
+        "Fetch.requestPaused" => {
+            Some(("Fetch.continueRequest", json!({ "requestId": request_id })))
+        }
+        "Fetch.authRequired" => {
+            let challenge = &params["authChallenge"];
+            let challenger = challenge["origin"]
+                .as_str()
+                .and_then(|origin| url::Url::parse(origin).ok())
+                .map(|origin| origin.origin().ascii_serialization());
+            let from_server = challenge["source"].as_str().unwrap_or("Server") == "Server";
+            let response = if from_server && challenger.as_ref() == Some(&creds.origin) {
+                json!({
+                    "response": "ProvideCredentials",
+                    "username": creds.username,
+                    "password": creds.password,
+                })
+            } else {
+                json!({ "response": "Default" })
+            };
+            Some((
+                "Fetch.continueWithAuth",
+                json!({ "requestId": request_id, "authChallengeResponse": response }),
+            ))
+        }
+        _ => None,
+    }
+}
+
+// The log entry for a console message or uncaught exception event, if
+// `event` is one. Levels follow chromedriver's browser log.
+fn console_entry(event: &serde_json::Value) -> Option<BrowserLogEntry> {
+    let params = &event["params"];
+    let timestamp = params["timestamp"].as_f64().unwrap_or_default() as u64;
+    match event["method"].as_str()? {
+        "Runtime.consoleAPICalled" => {
+            let level = match params["type"].as_str()? {
+                "error" | "assert" => "SEVERE",
+                "warning" => "WARNING",
+                "debug" => "DEBUG",
+                _ => "INFO",
+            };
+            let message = params["args"]
+                .as_array()?
+                .iter()
+                .map(remote_object_text)
+                .collect::<Vec<_>>()
+                .join(" ");
+            Some(BrowserLogEntry {
+                level: level.into(),
+                message,
+                source: Some("console-api".into()),
+                timestamp,
+            })
+        }
+        "Runtime.exceptionThrown" => {
+            let details = &params["exceptionDetails"];
+            let message = details["exception"]["description"]
+                .as_str()
+                .or_else(|| details["text"].as_str())
+                .unwrap_or_default();
+            Some(BrowserLogEntry {
+                level: "SEVERE".into(),
+                message: message.into(),
+                source: Some("javascript".into()),
+                timestamp,
+            })
+        }
+        _ => None,
+    }
+}
+
+// How a console argument would be printed, eg: `hello` for a string.
+fn remote_object_text(arg: &serde_json::Value) -> String {
+    match &arg["value"] {
+        serde_json::Value::String(text) => text.clone(),
+        serde_json::Value::Null => arg["description"]
+            .as_str()
+            .or_else(|| arg["type"].as_str())
+            .unwrap_or_default()
+            .to_string(),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn challenge(origin: &str, source: &str) -> serde_json::Value {
+        json!({
+            "method": "Fetch.authRequired",
+            "params": {
+                "requestId": "r1",
+                "authChallenge": { "source": source, "origin": origin, "scheme": "basic" },
+            },
+        })
+    }
+
+    #[test]
+    fn only_answers_challenges_from_the_origin() {
+        let creds = Credentials {
+            origin: "https://example.com".into(),
+            username: "user".into(),
+            password: "secret".into(),
+        };
+        let answer = |event| reply_to(&event, &creds).expect("reply");
+
+        assert_eq!(
+            answer(challenge("https://example.com", "Server")),
+            (
+                "Fetch.continueWithAuth",
+                json!({
+                    "requestId": "r1",
+                    "authChallengeResponse": {
+                        "response": "ProvideCredentials",
+                        "username": "user",
+                        "password": "secret",
+                    },
+                })
+            )
+        );
+        for other in &[
+            challenge("https://evil.example", "Server"),
+            challenge("http://example.com", "Server"),
+            challenge("https://example.com", "Proxy"),
+        ] {
+            assert_eq!(
+                answer(other.clone()).1["authChallengeResponse"],
+                json!({ "response": "Default" })
+            );
+        }
+
+        assert_eq!(
+            answer(json!({ "method": "Fetch.requestPaused", "params": { "requestId": "r2" } })),
+            ("Fetch.continueRequest", json!({ "requestId": "r2" }))
+        );
+        assert_eq!(
+            reply_to(&json!({ "method": "Network.requestWillBeSent" }), &creds),
+            None
+        );
+    }
+
+    #[test]
+    fn converts_console_events_to_log_entries() {
+        let logged = console_entry(&json!({
+            "method": "Runtime.consoleAPICalled",
+            "params": {
+                "type": "error",
+                "args": [
+                    { "type": "string", "value": "Failed:" },
+                    { "type": "number", "value": 42 },
+                    { "type": "object", "description": "Object" },
+                    { "type": "undefined" },
+                ],
+                "timestamp": 1_577_836_800_000.5,
+            },
+        }));
+        assert_eq!(
+            logged,
+            Some(BrowserLogEntry {
+                level: "SEVERE".into(),
+                message: "Failed: 42 Object undefined".into(),
+                source: Some("console-api".into()),
+                timestamp: 1_577_836_800_000,
+            })
+        );
+
+        let thrown = console_entry(&json!({
+            "method": "Runtime.exceptionThrown",
+            "params": {
+                "timestamp": 1_577_836_800_000.0,
+                "exceptionDetails": {
+                    "text": "Uncaught",
+                    "exception": { "description": "TypeError: x is undefined" },
+                },
+            },
+        }))
+        .expect("entry");
+        assert!(thrown.is_error());
+        assert_eq!(thrown.message, "TypeError: x is undefined");
+        assert_eq!(thrown.source.as_deref(), Some("javascript"));
+
+        assert_eq!(
+            console_entry(&json!({ "method": "Runtime.executionContextCreated" })),
+            None
+        );
+    }
+}
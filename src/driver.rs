@@ -1,36 +1,197 @@
+use std::borrow::{Borrow, BorrowMut};
 use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time;
 
 use failure::Error;
 
-use crate::client;
+use crate::client::{self, ErrorKind, WdError};
+
+type StartFn = dyn Fn() -> Result<DriverHolder, Error> + Send + Sync;
 
 /// This marks that something is a driver, that is it manages an instance of
-/// something used to remote control a browser.
-pub trait Driver {
+/// something used to remote control a browser. Drivers must be `Send`, as
+/// they may be checked on from another thread; see
+/// [`DriverHolder::monitor`].
+pub trait Driver: Send {
     /// Shut down the driver.
     fn close(&mut self) -> Result<(), Error>;
+
+    /// Checks that the driver is still running, and responding to requests.
+    fn check_health(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
 }
 
 /// This is designed to serve as a placeholder to make it easy to have the
 /// driver live as long as the client.
 pub struct DriverHolder {
     pub(crate) client: client::Client,
-    // This is only used so we can drop it _after_ we have dropped the client.
-    #[allow(dead_code)]
-    pub(crate) driver: Box<dyn Driver>,
+    // Declared before the driver, so the monitor thread has finished with
+    // the driver by the time it is dropped.
+    monitor: Option<Monitor>,
+    crashed: Arc<AtomicBool>,
+    restart: Option<Arc<StartFn>>,
+    // This is dropped _after_ we have dropped the client.
+    driver: Arc<Mutex<Box<dyn Driver>>>,
+}
+
+// Stops and waits for the monitor thread when dropped.
+struct Monitor {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
 }
 
 impl DriverHolder {
+    /// Pairs a session with the driver it was started on, eg: for drivers
+    /// other than those in the `chrome` and `gecko` modules.
+    pub fn new(driver: Box<dyn Driver>, client: client::Client) -> Self {
+        DriverHolder {
+            client,
+            monitor: None,
+            crashed: Arc::new(AtomicBool::new(false)),
+            restart: None,
+            driver: Arc::new(Mutex::new(driver)),
+        }
+    }
+
+    /// Use `start` to start a replacement driver and session on
+    /// [`restart`](Self::restart). Holders from `chrome::start` and
+    /// `gecko::start` restart with the same configuration by default.
+    pub fn restart_with<F>(mut self, start: F) -> Self
+    where
+        F: Fn() -> Result<DriverHolder, Error> + Send + Sync + 'static,
+    {
+        self.restart = Some(Arc::new(start));
+        self
+    }
+
+    /// Shuts down the session and driver, and starts fresh ones. Command
+    /// hooks, logs and settings are carried over to the new session, but
+    /// the [`monitor`](Self::monitor), if any, is stopped.
+    pub fn restart(&mut self) -> Result<(), Error> {
+        let start = match &self.restart {
+            Some(start) => start.clone(),
+            None => bail!("No way to restart this driver; see DriverHolder::restart_with"),
+        };
+        self.monitor = None;
+        if let Err(e) = self.client.close() {
+            warn!("Closing session before restart: {}", e);
+        }
+        if let Err(e) = self.driver.lock().expect("unpoison driver").close() {
+            warn!("Closing driver before restart: {}", e);
+        }
+
+        let DriverHolder {
+            mut client, driver, ..
+        } = start().map_err(|e| e.context("Restarting driver"))?;
+        client.inherit_settings(&mut self.client);
+        self.client = client;
+        self.driver = driver;
+        self.crashed = Arc::new(AtomicBool::new(false));
+        info!("Driver restarted");
+        Ok(())
+    }
+
+    /// Runs `f` against the session. If it fails because the driver or
+    /// browser has crashed, the driver is [restarted](Self::restart), and
+    /// `f` retried, up to `max_restarts` times. Useful for long-running jobs
+    /// that must survive eg: the browser running out of memory.
+    pub fn supervised<R, F>(&mut self, max_restarts: usize, mut f: F) -> Result<R, Error>
+    where
+        F: FnMut(&client::Client) -> Result<R, Error>,
+    {
+        let mut restarts = 0;
+        loop {
+            let err = match f(&self.client) {
+                Ok(res) => return Ok(res),
+                Err(e) => e,
+            };
+            if restarts >= max_restarts || !self.has_crashed(&err) {
+                return Err(err);
+            }
+            restarts += 1;
+            warn!(
+                "Session crashed ({}); restarting ({} of {})",
+                err, restarts, max_restarts
+            );
+            self.restart()?;
+        }
+    }
+
+    // Whether `err` was caused by the driver or browser going away.
+    fn has_crashed(&self, err: &Error) -> bool {
+        self.driver_crashed()
+            || err.downcast_ref::<WdError>().map(WdError::kind) == Some(ErrorKind::InvalidSessionId)
+            || self
+                .driver
+                .lock()
+                .expect("unpoison driver")
+                .check_health()
+                .is_err()
+    }
+
     /// This will shut down both the associated webdriver session, and driver.
     pub fn close(self) -> Result<(), Error> {
         let DriverHolder {
             mut client,
-            mut driver,
+            monitor,
+            driver,
+            ..
         } = self;
+        drop(monitor);
         client.close()?;
-        driver.close()?;
+        driver.lock().expect("unpoison driver").close()?;
         Ok(())
     }
+
+    /// Checks the driver every `interval` in a background thread, and
+    /// calls `on_crash` if it has exited, or stopped responding, eg: so
+    /// that a test can report the crash, rather than the connection errors
+    /// that follow. Replaces any previous monitor. The thread stops once a
+    /// crash has been seen, or the holder is closed or dropped.
+    pub fn monitor<F>(&mut self, interval: time::Duration, on_crash: F)
+    where
+        F: FnOnce(&Error) + Send + 'static,
+    {
+        self.monitor = None;
+        let (stop, stopped) = mpsc::channel();
+        let driver = self.driver.clone();
+        let crashed = self.crashed.clone();
+        let thread = thread::spawn(move || {
+            while let Err(mpsc::RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                let health = driver.lock().expect("unpoison driver").check_health();
+                if let Err(e) = health {
+                    error!("Driver crashed: {}", e);
+                    crashed.store(true, Ordering::SeqCst);
+                    on_crash(&e);
+                    return;
+                }
+            }
+        });
+        self.monitor = Some(Monitor {
+            stop: Some(stop),
+            thread: Some(thread),
+        });
+    }
+
+    /// Whether the [`monitor`](Self::monitor) has seen the driver crash.
+    pub fn driver_crashed(&self) -> bool {
+        self.crashed.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for Monitor {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                error!("Driver monitor panicked");
+            }
+        }
+    }
 }
 
 impl Deref for DriverHolder {
@@ -45,3 +206,95 @@ impl DerefMut for DriverHolder {
         &mut self.client
     }
 }
+
+impl Borrow<client::Client> for DriverHolder {
+    fn borrow(&self) -> &client::Client {
+        &self.client
+    }
+}
+
+impl BorrowMut<client::Client> for DriverHolder {
+    fn borrow_mut(&mut self) -> &mut client::Client {
+        &mut self.client
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::*;
+    use crate::testing::MockWebDriver;
+
+    struct FakeDriver(Arc<AtomicBool>);
+
+    impl Driver for FakeDriver {
+        fn close(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn check_health(&mut self) -> Result<(), Error> {
+            if self.0.load(Ordering::SeqCst) {
+                bail!("Child process failed: exit status: 1")
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn monitor_reports_crashes() {
+        let mock = MockWebDriver::start().expect("start");
+        let dead = Arc::new(AtomicBool::new(false));
+        let mut holder = DriverHolder::new(
+            Box::new(FakeDriver(dead.clone())),
+            mock.client().expect("client"),
+        );
+        let (tx, rx) = mpsc::channel();
+        holder.monitor(time::Duration::from_millis(10), move |e| {
+            tx.send(e.to_string()).expect("send")
+        });
+        assert!(!holder.driver_crashed());
+
+        dead.store(true, Ordering::SeqCst);
+        let reported = rx
+            .recv_timeout(time::Duration::from_secs(5))
+            .expect("crash reported");
+        assert_eq!(reported, "Child process failed: exit status: 1");
+        assert!(holder.driver_crashed());
+        holder.close().expect("close");
+    }
+
+    #[test]
+    fn supervised_jobs_restart_after_crashes() {
+        let mock = Arc::new(MockWebDriver::start().expect("start"));
+        mock.set_title("Scraped");
+        let start = {
+            let mock = mock.clone();
+            move || {
+                let driver = Box::new(FakeDriver(Arc::new(AtomicBool::new(false))));
+                Ok(DriverHolder::new(driver, mock.client()?))
+            }
+        };
+        let mut holder = start().expect("start").restart_with(start);
+
+        let mut attempts = 0;
+        let title = holder
+            .supervised(1, |client| {
+                attempts += 1;
+                if attempts == 1 {
+                    mock.crash();
+                }
+                client.title()
+            })
+            .expect("supervised");
+        assert_eq!(title, "Scraped");
+        assert_eq!(attempts, 2);
+
+        mock.crash();
+        let err = holder
+            .supervised(0, |client| client.title())
+            .expect_err("no restarts allowed");
+        assert_eq!(
+            err.downcast_ref::<WdError>().map(WdError::kind),
+            Some(ErrorKind::InvalidSessionId)
+        );
+    }
+}
@@ -0,0 +1,182 @@
+#[cfg(feature = "http-transport")]
+use std::path::Path;
+
+use failure::Error;
+
+use crate::client::{By, Client, Element, TextMode};
+use crate::scope::Scope;
+use crate::screenshot::Screenshot;
+
+/// An element, bound to the client it was found with. This saves having to
+/// pass the client around alongside the element, eg: `elt.click()?`.
+///
+/// The underlying [`Element`] can be retrieved for serialization, eg: for
+/// passing to a script.
+#[derive(Debug, Clone)]
+pub struct ElementRef<'c> {
+    client: &'c Client,
+    element: Element,
+}
+
+impl<'c> ElementRef<'c> {
+    /// Bind an existing element to `client`.
+    pub fn new(client: &'c Client, element: Element) -> Self {
+        ElementRef { client, element }
+    }
+
+    /// The client this element belongs to.
+    pub fn client(&self) -> &'c Client {
+        self.client
+    }
+
+    /// The raw element reference.
+    pub fn element(&self) -> &Element {
+        &self.element
+    }
+
+    /// Unbind the element from the client.
+    pub fn into_element(self) -> Element {
+        self.element
+    }
+
+    /// Find a single element beneath this one; see [`Client::find_element_from`].
+    pub fn find(&self, by: &By) -> Result<ElementRef<'c>, Error> {
+        let elt = self.client.find_element_from(&self.element, by)?;
+        Ok(ElementRef::new(self.client, elt))
+    }
+
+    /// Find all matching elements beneath this one; see [`Client::find_elements_from`].
+    pub fn find_all(&self, by: &By) -> Result<Vec<ElementRef<'c>>, Error> {
+        let elts = self.client.find_elements_from(&self.element, by)?;
+        Ok(elts
+            .into_iter()
+            .map(|elt| ElementRef::new(self.client, elt))
+            .collect())
+    }
+
+    /// A search context covering the descendants of this element.
+    pub fn scope(&self) -> Scope<'c> {
+        self.client.within(&self.element)
+    }
+
+    /// A search context covering the contents of this element's shadow
+    /// root; see [`Client::shadow_root`].
+    pub fn shadow_root(&self) -> Result<Scope<'c>, Error> {
+        let root = self.client.shadow_root(&self.element)?;
+        Ok(self.client.within_shadow(&root))
+    }
+
+    /// See [`Client::text`].
+    pub fn text(&self) -> Result<String, Error> {
+        self.client.text(&self.element)
+    }
+
+    /// See [`Client::text_with`].
+    pub fn text_with(&self, mode: TextMode) -> Result<String, Error> {
+        self.client.text_with(&self.element, mode)
+    }
+
+    /// See [`Client::inner_html`].
+    pub fn inner_html(&self) -> Result<String, Error> {
+        self.client.inner_html(&self.element)
+    }
+
+    /// See [`Client::outer_html`].
+    pub fn outer_html(&self) -> Result<String, Error> {
+        self.client.outer_html(&self.element)
+    }
+
+    /// See [`Client::name`].
+    pub fn name(&self) -> Result<String, Error> {
+        self.client.name(&self.element)
+    }
+
+    /// See [`Client::attribute`].
+    pub fn attribute(&self, attribute: &str) -> Result<Option<String>, Error> {
+        self.client.attribute(&self.element, attribute)
+    }
+
+    /// See [`Client::property`].
+    pub fn property(&self, property: &str) -> Result<serde_json::Value, Error> {
+        self.client.property(&self.element, property)
+    }
+
+    /// See [`Client::is_selected`].
+    pub fn is_selected(&self) -> Result<bool, Error> {
+        self.client.is_selected(&self.element)
+    }
+
+    /// See [`Client::is_displayed`].
+    pub fn is_displayed(&self) -> Result<bool, Error> {
+        self.client.is_displayed(&self.element)
+    }
+
+    /// See [`Client::is_enabled`].
+    pub fn is_enabled(&self) -> Result<bool, Error> {
+        self.client.is_enabled(&self.element)
+    }
+
+    /// See [`Client::click`].
+    pub fn click(&self) -> Result<(), Error> {
+        self.client.click(&self.element)
+    }
+
+    /// See [`Client::double_click`].
+    pub fn double_click(&self) -> Result<(), Error> {
+        self.client.double_click(&self.element)
+    }
+
+    /// See [`Client::context_click`].
+    pub fn context_click(&self) -> Result<(), Error> {
+        self.client.context_click(&self.element)
+    }
+
+    /// See [`Client::hover`].
+    pub fn hover(&self) -> Result<(), Error> {
+        self.client.hover(&self.element)
+    }
+
+    /// See [`Client::send_keys`].
+    pub fn send_keys(&self, keys: &str) -> Result<(), Error> {
+        self.client.send_keys(&self.element, keys)
+    }
+
+    /// See [`Client::send_file`].
+    #[cfg(feature = "http-transport")]
+    pub fn send_file(&self, path: &Path) -> Result<(), Error> {
+        self.client.send_file(&self.element, path)
+    }
+
+    /// See [`Client::clear`].
+    pub fn clear(&self) -> Result<(), Error> {
+        self.client.clear(&self.element)
+    }
+
+    /// See [`Client::submit`].
+    pub fn submit(&self) -> Result<(), Error> {
+        self.client.submit(&self.element)
+    }
+
+    /// See [`Client::element_screenshot`].
+    pub fn screenshot(&self) -> Result<Screenshot, Error> {
+        self.client.element_screenshot(&self.element)
+    }
+}
+
+impl<'c> AsRef<Element> for ElementRef<'c> {
+    fn as_ref(&self) -> &Element {
+        &self.element
+    }
+}
+
+impl<'c> From<ElementRef<'c>> for Element {
+    fn from(elt: ElementRef<'c>) -> Self {
+        elt.element
+    }
+}
+
+impl<'a, 'c> From<&'a ElementRef<'c>> for serde_json::Value {
+    fn from(elt: &'a ElementRef<'c>) -> Self {
+        serde_json::Value::from(&elt.element)
+    }
+}
@@ -0,0 +1,176 @@
+//! Starting sessions configured by environment variables, so that eg: each
+//! CI job can pick a browser without changes to the code under test.
+
+use std::env;
+use std::fmt;
+
+use failure::Error;
+
+use crate::driver::DriverHolder;
+use crate::profile::Profile;
+
+/// The environment variable naming the browser to use; see [`Browser`].
+pub const BROWSER_ENV: &str = "SULFUR_BROWSER";
+/// The environment variable that, when `false` (or `0`), shows the browser
+/// UI. Browsers are headless by default.
+pub const HEADLESS_ENV: &str = "SULFUR_HEADLESS";
+/// The environment variable holding the URL of an already running driver or
+/// grid to use, rather than starting one.
+pub const REMOTE_URL_ENV: &str = "SULFUR_REMOTE_URL";
+/// The environment variable holding the path of the driver binary, if it is
+/// not on `$PATH`.
+pub const DRIVER_PATH_ENV: &str = "SULFUR_DRIVER_PATH";
+// Read when `$SULFUR_BROWSER` is unset, for compatibility with older test
+// setups.
+const LEGACY_DRIVER_ENV: &str = "DRIVER";
+
+/// A browser that can be started with [`from_env`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Browser {
+    /// Chrome, via chromedriver.
+    Chrome,
+    /// Firefox, via geckodriver.
+    Firefox,
+}
+
+/// Start a browser session as configured by the environment:
+///
+/// * `$SULFUR_BROWSER`: `chrome` or `firefox`. Defaults to Chrome when the
+///   `chrome` feature is enabled, and Firefox otherwise.
+/// * `$SULFUR_HEADLESS`: `false` to show the browser UI; headless otherwise.
+/// * `$SULFUR_REMOTE_URL`: attach to the driver (or Selenium grid) at this
+///   URL, rather than starting one.
+/// * `$SULFUR_DRIVER_PATH`: the chromedriver or geckodriver binary to run.
+///
+/// ```no_run
+/// # fn example() -> Result<(), failure::Error> {
+/// let session = sulfur::from_env()?;
+/// session.visit("https://example.com/")?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn from_env() -> Result<DriverHolder, Error> {
+    let profile = profile_from_vars(|name| env::var(name).ok())?;
+    info!("Starting session with {:?}", profile);
+    profile.start()
+}
+
+impl Browser {
+    /// The browser named by `$SULFUR_BROWSER`, or the default; see
+    /// [`from_env`].
+    pub fn from_env() -> Result<Self, Error> {
+        Self::from_vars(|name| env::var(name).ok())
+    }
+
+    fn from_vars<F: Fn(&str) -> Option<String>>(var: F) -> Result<Self, Error> {
+        if let Some(name) = var(BROWSER_ENV) {
+            return name.parse();
+        }
+        if let Some(driver) = var(LEGACY_DRIVER_ENV) {
+            return driver.parse();
+        }
+        Ok(Browser::default())
+    }
+}
+
+impl Default for Browser {
+    // Chrome, where support for it is enabled.
+    fn default() -> Self {
+        if cfg!(feature = "chrome") {
+            Browser::Chrome
+        } else {
+            Browser::Firefox
+        }
+    }
+}
+
+impl std::str::FromStr for Browser {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match &*s.to_lowercase() {
+            "chrome" | "chromium" | "chromedriver" => Ok(Browser::Chrome),
+            "firefox" | "gecko" | "geckodriver" => Ok(Browser::Firefox),
+            other => bail!("Unsupported browser: {:?}", other),
+        }
+    }
+}
+
+impl fmt::Display for Browser {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Browser::Chrome => write!(fmt, "chrome"),
+            Browser::Firefox => write!(fmt, "firefox"),
+        }
+    }
+}
+
+// Reads a profile from the environment variables described on `from_env`.
+fn profile_from_vars<F: Fn(&str) -> Option<String>>(var: F) -> Result<Profile, Error> {
+    let mut profile = Profile::new(Browser::from_vars(&var)?);
+    match var(HEADLESS_ENV).as_deref().map(str::to_lowercase) {
+        None => (),
+        Some(value) => match &*value {
+            "" | "1" | "true" | "yes" => (),
+            "0" | "false" | "no" => {
+                profile.headless(false);
+            }
+            other => bail!("Expected a boolean in ${}, got {:?}", HEADLESS_ENV, other),
+        },
+    }
+    if let Some(url) = var(REMOTE_URL_ENV).filter(|url| !url.is_empty()) {
+        profile.remote_url(url);
+    }
+    if let Some(path) = var(DRIVER_PATH_ENV).filter(|path| !path.is_empty()) {
+        profile.driver_path(path);
+    }
+    Ok(profile)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn settings(vars: &[(&str, &str)]) -> Result<Profile, Error> {
+        let vars = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<HashMap<_, _>>();
+        profile_from_vars(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn reads_settings_from_the_environment() {
+        let s = settings(&[
+            ("SULFUR_BROWSER", "Firefox"),
+            ("SULFUR_HEADLESS", "false"),
+            ("SULFUR_REMOTE_URL", "http://grid:4444/wd/hub"),
+            ("SULFUR_DRIVER_PATH", "/opt/bin/geckodriver"),
+        ])
+        .expect("settings");
+        let mut expected = Profile::new(Browser::Firefox);
+        expected
+            .headless(false)
+            .remote_url("http://grid:4444/wd/hub")
+            .driver_path("/opt/bin/geckodriver");
+        assert_eq!(s, expected);
+    }
+
+    #[test]
+    fn defaults_to_headless_and_local() {
+        let s = settings(&[("DRIVER", "chromedriver")]).expect("settings");
+        assert_eq!(s, Profile::new(Browser::Chrome));
+
+        let s =
+            settings(&[("SULFUR_BROWSER", "chrome"), ("DRIVER", "geckodriver")]).expect("settings");
+        assert_eq!(s.browser(), Browser::Chrome);
+    }
+
+    #[test]
+    fn rejects_bad_values() {
+        assert!(settings(&[("SULFUR_BROWSER", "netscape")]).is_err());
+        assert!(settings(&[("SULFUR_HEADLESS", "sometimes")]).is_err());
+    }
+}
@@ -1,5 +1,7 @@
 //! Functionality for starting a dedicated geckodriver and webdriver session for firefox.
 
+use std::fs::File;
+use std::path::PathBuf;
 use std::process::{Child, Command};
 use std::time;
 
@@ -9,53 +11,169 @@ use reqwest;
 
 use crate::client::{Capabilities, Client};
 use crate::driver::{self, DriverHolder};
-use crate::junk_drawer::{self, unused_port_no};
+use crate::junk_drawer::{base_url, merge_json, unused_port_no};
+use crate::process::{self, DEFAULT_GRACE_PERIOD};
+use crate::wait::wait_until;
 
 const START_TIMEOUT: time::Duration = time::Duration::from_secs(120);
+// How many times to try starting the driver, as another process may take the
+// port we picked before the driver binds it.
+const START_ATTEMPTS: usize = 3;
+
+// Content types that are saved to the download directory without prompting.
+const DOWNLOAD_MIME_TYPES: &[&str] = &[
+    "application/octet-stream",
+    "application/pdf",
+    "application/zip",
+    "application/json",
+    "application/xml",
+    "application/vnd.ms-excel",
+    "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+    "text/csv",
+    "text/plain",
+    "image/png",
+    "image/jpeg",
+];
 
 /// Represents a `geckodriver` process.
 pub struct Driver {
-    child: Child,
-    port: u16,
+    // Absent for drivers we attached to with `connect`.
+    child: Option<Child>,
+    url: String,
+    log_file: Option<PathBuf>,
     http: reqwest::Client,
+    grace_period: time::Duration,
+}
+
+/// Allows extra configuration for geckodriver instances.
+#[derive(Clone, Default, Debug)]
+pub struct DriverConfig {
+    grace_period: Option<time::Duration>,
+    env: Vec<(String, String)>,
+    path: Option<PathBuf>,
+    log_file: Option<PathBuf>,
+    marionette_port: Option<u16>,
 }
 /// Allows extra configuration for chrome instances.
 #[derive(Clone, Default)]
 pub struct Config {
     headless: bool,
+    user_agent: Option<String>,
+    lang: Option<String>,
+    download_dir: Option<PathBuf>,
+    private_browsing: bool,
+    browser_version: Option<String>,
+    platform_name: Option<String>,
+    proxy: Option<String>,
+    args: Vec<String>,
+    capabilities: serde_json::Map<String, serde_json::Value>,
 }
 
 /// Start a chromedriver instance, along with a new browser session.
 pub fn start(config: &Config) -> Result<DriverHolder, Error> {
     let driver = Driver::start()?;
     let client = driver.new_session_config(config)?;
-    Ok(DriverHolder {
-        driver: Box::new(driver),
-        client: client,
-    })
+    let config = config.clone();
+    Ok(DriverHolder::new(Box::new(driver), client).restart_with(move || start(&config)))
 }
 
 impl Driver {
     /// Start a geckodriver instance on an automatically assigned port.
     pub fn start() -> Result<Self, Error> {
+        Self::driver_config(&DriverConfig::default())
+    }
+
+    /// Start geckodriver, connected to a Firefox that is already running,
+    /// eg: a developer's logged in browser, for exploratory automation.
+    /// Firefox must have been started with `--marionette`, and listen on
+    /// `marionette_port` (by default, 2828).
+    pub fn connect_existing(marionette_port: u16) -> Result<Self, Error> {
+        Self::driver_config(DriverConfig::default().connect_existing(marionette_port))
+    }
+
+    /// Attach to a geckodriver that is already running at `url`, eg: one
+    /// managed by systemd or a container supervisor. The driver must be
+    /// responding to requests; it is left running when this is closed.
+    pub fn connect(url: &str) -> Result<Self, Error> {
+        let driver = Driver {
+            child: None,
+            url: base_url(url),
+            log_file: None,
+            http: reqwest::Client::new(),
+            grace_period: DEFAULT_GRACE_PERIOD,
+        };
+        if !driver.is_healthy() {
+            bail!("geckodriver at {} is not responding", driver.url);
+        }
+        info!("Attached to geckodriver at {}", driver.url);
+        Ok(driver)
+    }
+
+    /// Start geckodriver with the given configuration. If it exits before it
+    /// is ready (eg: because its port was taken in the meantime), it is
+    /// started again on another port.
+    pub fn driver_config(config: &DriverConfig) -> Result<Self, Error> {
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let mut driver = Self::spawn(config)?;
+            match wait_until(START_TIMEOUT, || {
+                driver.ensure_still_alive()?;
+                Ok(driver.is_healthy())
+            }) {
+                Ok(true) => {
+                    info!("Setup done! running at {}", driver.url);
+                    return Ok(driver);
+                }
+                Ok(false) => bail!(
+                    "geckodriver at {} was not ready after {:?}",
+                    driver.url,
+                    START_TIMEOUT
+                ),
+                Err(e) if attempts < START_ATTEMPTS && driver.ensure_still_alive().is_err() => {
+                    warn!("geckodriver exited during startup, retrying: {}", e)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    // Spawns the driver on an unused port, without waiting for it.
+    fn spawn(config: &DriverConfig) -> Result<Self, Error> {
         let http = reqwest::Client::new();
         let port = unused_port_no()?;
         debug!("Spawning chrome driver on port: {:?}", port);
-        let mut cmd = Command::new("geckodriver");
+        let binary = config
+            .path
+            .as_deref()
+            .unwrap_or_else(|| "geckodriver".as_ref());
+        let mut cmd = Command::new(binary);
         cmd.arg(format!("--port={}", port));
+        if let Some(marionette_port) = config.marionette_port {
+            cmd.arg("--connect-existing");
+            cmd.arg(format!("--marionette-port={}", marionette_port));
+        }
         // cmd.arg("--silent");
         // cmd.arg("--verbose");
+        cmd.envs(config.env.iter().map(|(k, v)| (k, v)));
+        if let Some(path) = &config.log_file {
+            // geckodriver only logs to stderr, so redirect its output.
+            let file = File::create(path)
+                .with_context(|_| format!("Creating geckodriver log {:?}", path))?;
+            cmd.stdout(file.try_clone()?);
+            cmd.stderr(file);
+        }
         debug!("Starting command: {:?}", cmd);
-        let child = cmd.spawn().context("Spawning geckodriver")?;
+        let child = process::spawn(&mut cmd).context("Spawning geckodriver")?;
 
-        let mut driver = Driver { child, port, http };
-
-        junk_drawer::wait_until(START_TIMEOUT, || {
-            driver.ensure_still_alive()?;
-            Ok(driver.is_healthy())
-        })?;
-
-        info!("Setup done! running on port {:?}", driver.port);
+        let driver = Driver {
+            child: Some(child),
+            url: format!("http://127.0.0.1:{}/", port),
+            log_file: config.log_file.clone(),
+            http,
+            grace_period: config.grace_period.unwrap_or(DEFAULT_GRACE_PERIOD),
+        };
 
         Ok(driver)
     }
@@ -67,28 +185,36 @@ impl Driver {
 
     /// Build a new webdriver session with the specified configuration.
     pub fn new_session_config(&self, config: &Config) -> Result<Client, Error> {
-        info!("Starting new session from instance at {}", self.port);
-        let client =
-            Client::new_with_http(&self.url(), config.to_capabilities(), self.http.clone())?;
+        info!("Starting new session from instance at {}", self.url);
+        let mut client =
+            Client::new_with_http(&self.url, config.to_capabilities(), self.http.clone())?;
+        if let Some(path) = &self.log_file {
+            client.set_driver_log(path);
+        }
         Ok(client)
     }
 
-    /// Shut down the geckodriver process. This assumes that the session has
-    /// been shut down seperately.
+    /// Shut down the geckodriver process, giving it a chance to shut down
+    /// firefox first; see [`DriverConfig::grace_period`]. This assumes that
+    /// the session has been shut down seperately. Drivers attached to with
+    /// [`connect`](Self::connect) are left running. On Windows, firefox is
+    /// not cleaned up if geckodriver has to be killed.
     pub fn close(&mut self) -> Result<(), Error> {
-        debug!("Closing child: {:?}", self.child);
-        self.child.kill()?;
-        self.child.wait()?;
-        Ok(())
-    }
-
-    fn url(&self) -> String {
-        format!("http://127.0.0.1:{}/", self.port)
+        match &mut self.child {
+            Some(child) => {
+                debug!("Closing child: {:?}", child);
+                process::terminate(child, self.grace_period)
+            }
+            None => {
+                debug!("Leaving geckodriver at {} running", self.url);
+                Ok(())
+            }
+        }
     }
 
     // §8.3 Status
     fn is_healthy(&self) -> bool {
-        let url = format!("{}status", self.url());
+        let url = format!("{}status", self.url);
         match self.http.get(&url).send() {
             Err(e) => {
                 warn!("Could not fetch {}: {:?}", url, e);
@@ -102,13 +228,15 @@ impl Driver {
     }
 
     fn ensure_still_alive(&mut self) -> Result<(), Error> {
-        match self.child.try_wait()? {
-            Some(status) => {
-                warn!("child exited with {}", status);
-                bail!("Child process failed: {:?}", status)
-            }
-            None => Ok(()),
+        let child = match &mut self.child {
+            Some(child) => child,
+            None => return Ok(()),
+        };
+        if process::has_exited(child)? {
+            warn!("child {} exited", child.id());
+            bail!("Child process {} exited", child.id())
         }
+        Ok(())
     }
 }
 
@@ -123,11 +251,57 @@ impl Drop for Driver {
 
 impl driver::Driver for Driver {
     fn close(&mut self) -> Result<(), Error> {
-        self.child.kill()?;
+        Driver::close(self)
+    }
+
+    fn check_health(&mut self) -> Result<(), Error> {
+        self.ensure_still_alive()?;
+        if !self.is_healthy() {
+            bail!("geckodriver at {} is not responding", self.url);
+        }
         Ok(())
     }
 }
 
+impl DriverConfig {
+    /// How long to wait after sending geckodriver `SIGTERM` before killing
+    /// it. Defaults to 5 seconds. On platforms other than Unix, geckodriver
+    /// is killed immediately.
+    pub fn grace_period(&mut self, grace_period: time::Duration) -> &mut Self {
+        self.grace_period = Some(grace_period);
+        self
+    }
+
+    /// Drive the Firefox already listening for Marionette connections on
+    /// `marionette_port`, rather than starting a new one; see
+    /// [`Driver::connect_existing`].
+    pub fn connect_existing(&mut self, marionette_port: u16) -> &mut Self {
+        self.marionette_port = Some(marionette_port);
+        self
+    }
+
+    /// Write geckodriver's output, including its log, to `path`. The log is
+    /// included in artifacts captured from sessions on this driver; see
+    /// [`Client::capture_artifacts`].
+    pub fn log_file<P: Into<PathBuf>>(&mut self, path: P) -> &mut Self {
+        self.log_file = Some(path.into());
+        self
+    }
+
+    /// Run the geckodriver binary at `path`, rather than finding it on `$PATH`.
+    pub fn path<P: Into<PathBuf>>(&mut self, path: P) -> &mut Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Set the environment variable `key` for geckodriver, and the browsers
+    /// it starts, eg: `DISPLAY` for use with [`Xvfb`](crate::xvfb::Xvfb).
+    pub fn env<K: Into<String>, V: Into<String>>(&mut self, key: K, value: V) -> &mut Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+}
+
 impl Config {
     /// Specifies if the firefox instance should be headless, or whether
     /// it should show the UI.
@@ -136,16 +310,141 @@ impl Config {
         self
     }
 
+    /// Report `user_agent` as the browser's user agent string.
+    pub fn user_agent<S: Into<String>>(&mut self, user_agent: S) -> &mut Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Request `locale` (eg: `fr-FR`) via the `Accept-Language` header and
+    /// `navigator.language`, and use it for the UI where a language pack is
+    /// installed.
+    pub fn lang<S: Into<String>>(&mut self, locale: S) -> &mut Self {
+        self.lang = Some(locale.into());
+        self
+    }
+
+    /// Save downloads to `dir` without prompting, eg: for use with
+    /// [`before_download`](crate::wait::before_download). PDFs are
+    /// downloaded, rather than opened in the built-in viewer.
+    pub fn download_dir<P: Into<PathBuf>>(&mut self, dir: P) -> &mut Self {
+        self.download_dir = Some(dir.into());
+        self
+    }
+
+    /// Run in permanent private browsing mode, so that cookies and storage
+    /// are never written to the profile, and cannot leak between sessions.
+    pub fn private_browsing(&mut self, private_browsing: bool) -> &mut Self {
+        self.private_browsing = private_browsing;
+        self
+    }
+
+    /// Send HTTP and HTTPS traffic via the proxy at `address` (ie:
+    /// `host:port`), including that for loopback addresses, which Firefox
+    /// otherwise never proxies. See eg:
+    /// [`RecordingProxy`](crate::proxy::RecordingProxy).
+    pub fn proxy<S: Into<String>>(&mut self, address: S) -> &mut Self {
+        self.proxy = Some(address.into());
+        self
+    }
+
+    /// Pass `arg` on the Firefox command line, eg: for switches without a
+    /// dedicated setting.
+    pub fn arg<S: Into<String>>(&mut self, arg: S) -> &mut Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Request the capability `name`. Objects are merged into those this
+    /// configuration generates, eg: `"moz:firefoxOptions"` with `{"binary": ...}`
+    /// keeps the arguments set here.
+    pub fn capability<K: Into<String>, V: Into<serde_json::Value>>(
+        &mut self,
+        name: K,
+        value: V,
+    ) -> &mut Self {
+        self.capabilities.insert(name.into(), value.into());
+        self
+    }
+
+    /// Require the given browser version, eg: `80`.
+    pub fn browser_version<S: Into<String>>(&mut self, version: S) -> &mut Self {
+        self.browser_version = Some(version.into());
+        self
+    }
+
+    /// Require the given platform, eg: `linux`.
+    pub fn platform_name<S: Into<String>>(&mut self, platform: S) -> &mut Self {
+        self.platform_name = Some(platform.into());
+        self
+    }
+
     fn to_capabilities(&self) -> Capabilities {
         let mut args = vec![];
         if self.headless {
             args.push("--headless")
         }
-        Capabilities {
-            always_match: json!({
-               "browserName": "firefox",
-               "moz:firefoxOptions": { "args": args },
-            }),
+        args.extend(self.args.iter().map(String::as_str));
+        let mut prefs = json!({});
+        if let Some(user_agent) = &self.user_agent {
+            prefs["general.useragent.override"] = json!(user_agent);
+        }
+        if let Some(lang) = &self.lang {
+            prefs["intl.accept_languages"] = json!(lang);
+            prefs["intl.locale.requested"] = json!(lang);
+        }
+        if let Some(dir) = &self.download_dir {
+            prefs["browser.download.folderList"] = json!(2);
+            prefs["browser.download.dir"] = json!(dir);
+            prefs["browser.download.useDownloadDir"] = json!(true);
+            prefs["browser.download.manager.showWhenStarting"] = json!(false);
+            prefs["browser.helperApps.neverAsk.saveToDisk"] = json!(DOWNLOAD_MIME_TYPES.join(","));
+            prefs["pdfjs.disabled"] = json!(true);
+        }
+        if self.private_browsing {
+            prefs["browser.privatebrowsing.autostart"] = json!(true);
         }
+        if self.proxy.is_some() {
+            prefs["network.proxy.allow_hijacking_localhost"] = json!(true);
+        }
+        let mut always_match = json!({
+           "browserName": "firefox",
+           "moz:firefoxOptions": { "args": args, "prefs": prefs },
+        });
+        if let Some(version) = &self.browser_version {
+            always_match["browserVersion"] = json!(version);
+        }
+        if let Some(platform) = &self.platform_name {
+            always_match["platformName"] = json!(platform);
+        }
+        if let Some(proxy) = &self.proxy {
+            always_match["proxy"] = json!({
+                "proxyType": "manual",
+                "httpProxy": proxy,
+                "sslProxy": proxy,
+            });
+        }
+        for (name, value) in &self.capabilities {
+            merge_json(&mut always_match[name], value);
+        }
+        Capabilities { always_match }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prefs(config: &Config) -> serde_json::Value {
+        config.to_capabilities().always_match["moz:firefoxOptions"]["prefs"].clone()
+    }
+
+    #[test]
+    fn private_browsing_is_a_pref() {
+        assert_eq!(prefs(&Config::default()), json!({}));
+        assert_eq!(
+            prefs(Config::default().private_browsing(true)),
+            json!({ "browser.privatebrowsing.autostart": true })
+        );
     }
 }
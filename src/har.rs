@@ -0,0 +1,362 @@
+//! Builds HAR 1.2 documents (see <http://www.softwareishard.com/blog/har-12-spec/>)
+//! from captured network traffic.
+
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde_json::Value;
+
+use crate::browser_log::PerformanceEvent;
+
+// A single request and its response, however they were captured.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Entry {
+    pub(crate) started: Option<SystemTime>,
+    pub(crate) duration: Duration,
+    pub(crate) method: String,
+    pub(crate) url: String,
+    pub(crate) http_version: String,
+    pub(crate) request_headers: Vec<(String, String)>,
+    pub(crate) request_body: Option<String>,
+    pub(crate) status: u16,
+    pub(crate) status_text: String,
+    pub(crate) response_headers: Vec<(String, String)>,
+    pub(crate) mime_type: String,
+    // Bytes transferred for the response body, or -1 if unknown.
+    pub(crate) body_size: i64,
+    pub(crate) text: Option<String>,
+}
+
+// Wraps `entries` in a HAR document.
+pub(crate) fn document(entries: &[Entry]) -> Value {
+    json!({
+        "log": {
+            "version": "1.2",
+            "creator": { "name": "sulfur", "version": env!("CARGO_PKG_VERSION") },
+            "pages": [],
+            "entries": entries.iter().map(entry).collect::<Vec<_>>(),
+        }
+    })
+}
+
+fn entry(entry: &Entry) -> Value {
+    let time = entry.duration.as_secs_f64() * 1000.0;
+    let query = url::Url::parse(&entry.url)
+        .map(|url| {
+            url.query_pairs()
+                .map(|(name, value)| json!({ "name": name, "value": value }))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let mut request = json!({
+        "method": entry.method,
+        "url": entry.url,
+        "httpVersion": entry.http_version,
+        "cookies": [],
+        "headers": headers(&entry.request_headers),
+        "queryString": query,
+        "headersSize": -1,
+        "bodySize": entry.request_body.as_ref().map_or(0, |body| body.len() as i64),
+    });
+    if let Some(body) = &entry.request_body {
+        let mime_type = header(&entry.request_headers, "content-type").unwrap_or_default();
+        request["postData"] = json!({ "mimeType": mime_type, "text": body });
+    }
+    let mut content = json!({
+        "size": entry.text.as_ref().map_or(entry.body_size, |text| text.len() as i64),
+        "mimeType": entry.mime_type,
+    });
+    if let Some(text) = &entry.text {
+        content["text"] = json!(text);
+    }
+    json!({
+        "startedDateTime": entry.started.map(rfc3339).unwrap_or_default(),
+        "time": time,
+        "request": request,
+        "response": {
+            "status": entry.status,
+            "statusText": entry.status_text,
+            "httpVersion": entry.http_version,
+            "cookies": [],
+            "headers": headers(&entry.response_headers),
+            "content": content,
+            "redirectURL": header(&entry.response_headers, "location").unwrap_or_default(),
+            "headersSize": -1,
+            "bodySize": entry.body_size,
+        },
+        "cache": {},
+        "timings": { "send": 0, "wait": time, "receive": 0 },
+    })
+}
+
+fn headers(headers: &[(String, String)]) -> Vec<Value> {
+    headers
+        .iter()
+        .map(|(name, value)| json!({ "name": name, "value": value }))
+        .collect()
+}
+
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+// Rebuilds the requests described by the DevTools `Network` events in
+// Chrome's performance log. Requests still in flight are included, with
+// whatever is known about them.
+pub(crate) fn from_devtools(events: &[PerformanceEvent]) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    // Indexes into `entries`, and the monotonic time the request was sent.
+    let mut in_flight = BTreeMap::<String, (usize, f64)>::new();
+    for event in events {
+        let params = &event.params;
+        let id = match params["requestId"].as_str() {
+            Some(id) => id.to_string(),
+            None => continue,
+        };
+        let timestamp = params["timestamp"].as_f64().unwrap_or_default();
+        match event.method.as_str() {
+            "Network.requestWillBeSent" => {
+                // Redirects re-use the request id, and carry the response
+                // for the previous hop.
+                if let Some(&(idx, sent)) = in_flight.get(&id) {
+                    if params["redirectResponse"].is_object() {
+                        set_response(&mut entries[idx], &params["redirectResponse"]);
+                        entries[idx].duration = seconds(timestamp - sent);
+                    }
+                }
+                let request = &params["request"];
+                // Fall back to when the event was logged, as a HAR entry
+                // must have a start time.
+                let started = params["wallTime"]
+                    .as_f64()
+                    .map(|wall| UNIX_EPOCH + seconds(wall))
+                    .unwrap_or_else(|| UNIX_EPOCH + Duration::from_millis(event.timestamp));
+                entries.push(Entry {
+                    started: Some(started),
+                    method: request["method"].as_str().unwrap_or_default().to_string(),
+                    url: request["url"].as_str().unwrap_or_default().to_string(),
+                    request_headers: header_map(&request["headers"]),
+                    request_body: request["postData"].as_str().map(str::to_string),
+                    body_size: -1,
+                    ..Entry::default()
+                });
+                in_flight.insert(id, (entries.len() - 1, timestamp));
+            }
+            "Network.responseReceived" => {
+                if let Some(&(idx, _)) = in_flight.get(&id) {
+                    set_response(&mut entries[idx], &params["response"]);
+                }
+            }
+            "Network.loadingFinished" | "Network.loadingFailed" => {
+                if let Some((idx, sent)) = in_flight.remove(&id) {
+                    let entry = &mut entries[idx];
+                    entry.duration = seconds(timestamp - sent);
+                    if let Some(size) = params["encodedDataLength"].as_f64() {
+                        entry.body_size = size as i64;
+                    }
+                    if let Some(error) = params["errorText"].as_str() {
+                        entry.status = 0;
+                        entry.status_text = error.to_string();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    entries
+}
+
+fn set_response(entry: &mut Entry, response: &Value) {
+    entry.status = response["status"].as_u64().unwrap_or_default() as u16;
+    entry.status_text = response["statusText"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+    entry.response_headers = header_map(&response["headers"]);
+    entry.mime_type = response["mimeType"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+    entry.http_version = response["protocol"]
+        .as_str()
+        .unwrap_or_default()
+        .to_uppercase();
+}
+
+// DevTools reports headers as an object, with repeated headers joined by
+// newlines.
+fn header_map(headers: &Value) -> Vec<(String, String)> {
+    headers
+        .as_object()
+        .map(|headers| {
+            headers
+                .iter()
+                .flat_map(|(name, value)| {
+                    value
+                        .as_str()
+                        .unwrap_or_default()
+                        .split('\n')
+                        .map(move |value| (name.clone(), value.to_string()))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn seconds(secs: f64) -> Duration {
+    Duration::from_secs_f64(secs.max(0.0))
+}
+
+// Formats `time` as eg: `2020-01-01T00:00:00.000Z`.
+pub(crate) fn rfc3339(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+
+    // Converts days since the epoch to a civil date, after Howard Hinnant's
+    // `civil_from_days`.
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(method: &str, params: Value) -> PerformanceEvent {
+        PerformanceEvent {
+            method: method.into(),
+            params,
+            webview: None,
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn starts_entries_without_a_wall_time_when_they_were_logged() {
+        let mut sent = event(
+            "Network.requestWillBeSent",
+            json!({
+                "requestId": "1",
+                "timestamp": 10.0,
+                "request": { "method": "GET", "url": "http://example.com/", "headers": {} },
+            }),
+        );
+        sent.timestamp = 1_577_836_800_500;
+
+        let har = document(&from_devtools(&[sent]));
+        assert_eq!(
+            har["log"]["entries"][0]["startedDateTime"],
+            "2020-01-01T00:00:00.500Z"
+        );
+    }
+
+    #[test]
+    fn formats_timestamps() {
+        assert_eq!(rfc3339(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            rfc3339(UNIX_EPOCH + Duration::from_millis(1_582_983_045_123)),
+            "2020-02-29T13:30:45.123Z"
+        );
+    }
+
+    #[test]
+    fn rebuilds_requests_from_devtools_events() {
+        let events = vec![
+            event(
+                "Network.requestWillBeSent",
+                json!({
+                    "requestId": "1",
+                    "timestamp": 10.0,
+                    "wallTime": 1_577_836_800.0,
+                    "request": {
+                        "method": "GET",
+                        "url": "http://example.com/old?q=1",
+                        "headers": { "Accept": "text/html" },
+                    },
+                }),
+            ),
+            event(
+                "Network.requestWillBeSent",
+                json!({
+                    "requestId": "1",
+                    "timestamp": 10.25,
+                    "wallTime": 1_577_836_800.25,
+                    "request": { "method": "GET", "url": "http://example.com/new", "headers": {} },
+                    "redirectResponse": {
+                        "status": 301,
+                        "statusText": "Moved Permanently",
+                        "headers": { "Location": "/new" },
+                        "mimeType": "",
+                        "protocol": "http/1.1",
+                    },
+                }),
+            ),
+            event(
+                "Network.responseReceived",
+                json!({
+                    "requestId": "1",
+                    "timestamp": 10.5,
+                    "response": {
+                        "status": 200,
+                        "statusText": "OK",
+                        "headers": { "Content-Type": "text/html", "Set-Cookie": "a=1\nb=2" },
+                        "mimeType": "text/html",
+                        "protocol": "http/1.1",
+                    },
+                }),
+            ),
+            event(
+                "Network.loadingFinished",
+                json!({ "requestId": "1", "timestamp": 10.75, "encodedDataLength": 512.0 }),
+            ),
+        ];
+
+        let har = document(&from_devtools(&events));
+        let entries = har["log"]["entries"].as_array().expect("entries");
+        assert_eq!(entries.len(), 2);
+
+        assert_eq!(entries[0]["startedDateTime"], "2020-01-01T00:00:00.000Z");
+        assert_eq!(entries[0]["time"], 250.0);
+        assert_eq!(
+            entries[0]["request"]["queryString"],
+            json!([{ "name": "q", "value": "1" }])
+        );
+        assert_eq!(entries[0]["response"]["status"], 301);
+        assert_eq!(entries[0]["response"]["redirectURL"], "/new");
+
+        assert_eq!(entries[1]["request"]["url"], "http://example.com/new");
+        assert_eq!(entries[1]["time"], 500.0);
+        assert_eq!(entries[1]["response"]["httpVersion"], "HTTP/1.1");
+        assert_eq!(entries[1]["response"]["bodySize"], 512);
+        assert_eq!(
+            entries[1]["response"]["headers"],
+            json!([
+                { "name": "Content-Type", "value": "text/html" },
+                { "name": "Set-Cookie", "value": "a=1" },
+                { "name": "Set-Cookie", "value": "b=2" },
+            ])
+        );
+    }
+}
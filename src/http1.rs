@@ -0,0 +1,138 @@
+//! Just enough HTTP/1.1 parsing for talking to drivers over Unix sockets, and
+//! for the recording proxy.
+
+use std::str;
+
+use failure::{Error, ResultExt};
+
+// The start line and headers of a request or response.
+#[derive(Debug, Clone)]
+pub(crate) struct Head {
+    pub(crate) start_line: String,
+    pub(crate) headers: Vec<(String, String)>,
+}
+
+impl Head {
+    // The value of the first header called `name`, ignoring case.
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    // Whether the body uses the chunked transfer encoding.
+    pub(crate) fn is_chunked(&self) -> bool {
+        self.header("transfer-encoding")
+            .is_some_and(|v| v.eq_ignore_ascii_case("chunked"))
+    }
+
+    pub(crate) fn content_length(&self) -> Result<Option<usize>, Error> {
+        match self.header("content-length") {
+            Some(len) => {
+                let len = len
+                    .parse::<usize>()
+                    .with_context(|_| format!("Bad content-length: {:?}", len))?;
+                Ok(Some(len))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+// Splits `raw` into the head, and whatever follows it. Returns `None` if the
+// head is incomplete.
+pub(crate) fn split_head(raw: &[u8]) -> Option<(&[u8], &[u8])> {
+    find(raw, b"\r\n\r\n").map(|split| (&raw[..split], &raw[split + 4..]))
+}
+
+pub(crate) fn parse_head(head: &[u8]) -> Result<Head, Error> {
+    let head = str::from_utf8(head)?;
+    let mut lines = head.split("\r\n");
+    let start_line = lines.next().unwrap_or_default().to_string();
+    let mut headers = Vec::new();
+    for line in lines {
+        match line.find(':') {
+            Some(idx) => {
+                headers.push((line[..idx].to_string(), line[idx + 1..].trim().to_string()))
+            }
+            None => bail!("Bad HTTP header: {:?}", line),
+        }
+    }
+    Ok(Head {
+        start_line,
+        headers,
+    })
+}
+
+// Decodes a body in the chunked transfer encoding.
+pub(crate) fn dechunk(mut raw: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut body = Vec::new();
+    loop {
+        let eol = find(raw, b"\r\n").ok_or_else(|| format_err!("Truncated chunk header"))?;
+        let size = str::from_utf8(&raw[..eol])?;
+        let size = size.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16)
+            .with_context(|_| format!("Bad chunk size: {:?}", size))?;
+        raw = &raw[eol + 2..];
+        if size == 0 {
+            return Ok(body);
+        }
+        if raw.len() < size + 2 {
+            bail!("Truncated chunk");
+        }
+        body.extend_from_slice(&raw[..size]);
+        raw = &raw[size + 2..];
+    }
+}
+
+// The length of the chunked body at the start of `raw`, including any
+// trailers, or `None` if it is incomplete.
+#[cfg(feature = "proxy")]
+pub(crate) fn chunked_len(raw: &[u8]) -> Result<Option<usize>, Error> {
+    let mut pos = 0;
+    loop {
+        let eol = match find(&raw[pos..], b"\r\n") {
+            Some(eol) => pos + eol,
+            None => return Ok(None),
+        };
+        let size = str::from_utf8(&raw[pos..eol])?;
+        let size = size.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16)
+            .with_context(|_| format!("Bad chunk size: {:?}", size))?;
+        pos = eol + 2;
+        if size == 0 {
+            let trailers = &raw[pos..];
+            if trailers.starts_with(b"\r\n") {
+                return Ok(Some(pos + 2));
+            }
+            return Ok(find(trailers, b"\r\n\r\n").map(|end| pos + end + 4));
+        }
+        if raw.len() < pos + size + 2 {
+            return Ok(None);
+        }
+        pos += size + 2;
+    }
+}
+
+pub(crate) fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[cfg(all(test, feature = "proxy"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_end_of_chunked_bodies() {
+        let body = b"4\r\nping\r\n0\r\n\r\nnext";
+        assert_eq!(chunked_len(body).expect("len"), Some(body.len() - 4));
+        assert_eq!(dechunk(body).expect("dechunk"), b"ping");
+        let trailers = b"0\r\nExpires: never\r\n\r\n";
+        assert_eq!(chunked_len(trailers).expect("len"), Some(trailers.len()));
+        for partial in &[&b"4\r\npi"[..], b"4\r\nping\r\n", b"4\r\nping\r\n0\r\n"] {
+            assert_eq!(chunked_len(partial).expect("len"), None, "{:?}", partial);
+        }
+        assert!(chunked_len(b"zz\r\n").is_err());
+    }
+}
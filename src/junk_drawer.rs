@@ -1,5 +1,4 @@
 use std::net::{SocketAddr, TcpListener};
-use std::{thread, time};
 
 use failure::Error;
 use failure::ResultExt;
@@ -13,7 +12,7 @@ use rand::Rng;
 pub fn unused_port_no() -> Result<u16, Error> {
     let mut rng = rand::thread_rng();
     loop {
-        let port = rng.gen_range(4444u16, u16::max_value());
+        let port = rng.gen_range(4444u16, u16::MAX);
         let a = SocketAddr::from(([127, 0, 0, 1], port));
         debug!("Trying to bind to address: {:?}", a);
         if let Some(l) = TcpListener::bind(a)
@@ -36,17 +35,24 @@ pub fn unused_port_no() -> Result<u16, Error> {
     }
 }
 
-pub(crate) fn wait_until<F: FnMut() -> Result<bool, Error>>(
-    deadline: time::Duration,
-    mut check: F,
-) -> Result<bool, Error> {
-    let mut pause_time = time::Duration::from_millis(1);
-    let started_at = time::Instant::now();
-    while started_at.elapsed() < deadline && !check()? {
-        debug!("Pausing for {:?}", pause_time);
-        thread::sleep(pause_time);
-        pause_time *= 2;
+// Ensures `url` ends with a slash, so paths can be appended to it.
+pub fn base_url(url: &str) -> String {
+    if url.ends_with('/') {
+        url.to_string()
+    } else {
+        format!("{}/", url)
     }
+}
 
-    Ok(check()?)
+// Merges `extra` into `target`: objects are merged key by key, and anything
+// else replaces what was there.
+pub fn merge_json(target: &mut serde_json::Value, extra: &serde_json::Value) {
+    match (target, extra) {
+        (serde_json::Value::Object(target), serde_json::Value::Object(extra)) => {
+            for (key, value) in extra {
+                merge_json(target.entry(key.clone()).or_insert(json!(null)), value);
+            }
+        }
+        (target, extra) => *target = extra.clone(),
+    }
 }
@@ -3,7 +3,16 @@
 //! Sulfur provides an implementation of the webdriver protocol,
 //! used for remote controlling a browser, as well as functionality for
 //! conveniently running a browser locally.
+//!
+//! By default, only the protocol client is built, with an HTTP transport
+//! (which may be left out via the `http-transport` feature). Support for
+//! spawning and managing local drivers is enabled via the `chrome` and `gecko`
+//! features, and decoding screenshots via the `image` feature. The `testing`
+//! feature adds helpers for writing browser tests, and `macros` the
+//! [`PageObject`](page/trait.PageObject.html) derive and the
+//! [`browser_test`](attr.browser_test.html) attribute.
 
+#[cfg(feature = "http-transport")]
 extern crate reqwest;
 #[macro_use]
 extern crate serde_json;
@@ -12,19 +21,105 @@ extern crate serde_derive;
 extern crate serde;
 #[macro_use]
 extern crate failure;
+extern crate http;
 extern crate url;
 #[macro_use]
 extern crate log;
 extern crate base64;
 extern crate percent_encoding;
+#[cfg(any(feature = "chrome", feature = "gecko"))]
 extern crate rand;
+#[cfg(feature = "toml")]
+extern crate toml;
+#[cfg(feature = "http-transport")]
+extern crate zip;
 
+#[cfg(any(feature = "chrome", feature = "gecko"))]
 mod junk_drawer;
+#[cfg(any(feature = "chrome", feature = "gecko"))]
+mod process;
 
+mod actions;
+mod artifacts;
+mod batch;
+mod browser_log;
+mod builder;
+pub mod cassette;
+#[cfg(feature = "chrome")]
 pub mod chrome;
 mod client;
+mod command;
+mod command_log;
+mod context;
+#[cfg(feature = "devtools")]
+mod devtools;
 mod driver;
+mod element_ref;
+#[cfg(any(feature = "chrome", feature = "gecko"))]
+pub mod environment;
+mod events;
+#[cfg(feature = "gecko")]
 pub mod gecko;
+mod har;
+#[cfg(any(unix, feature = "proxy"))]
+mod http1;
+mod metrics;
+pub mod multi;
+pub mod page;
+mod permissions;
+pub mod pool;
+mod presets;
+#[cfg(any(feature = "chrome", feature = "gecko"))]
+pub mod profile;
+#[cfg(feature = "proxy")]
+pub mod proxy;
+mod recording;
+mod recovery;
+mod relative;
+pub mod remote;
+mod scope;
+mod screenshot;
+mod select;
+pub mod selectors;
+mod slow_motion;
+pub mod supervisor;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(unix)]
+mod unix_socket;
+#[cfg(feature = "http-transport")]
+mod upload;
+#[cfg(feature = "image")]
+pub mod visual;
+pub mod wait;
+pub mod webauthn;
+#[cfg(all(unix, any(feature = "chrome", feature = "gecko")))]
+pub mod xvfb;
 
+pub use crate::actions::{Actions, KeyActions, MouseButton, PointerActions, PointerType};
+pub use crate::artifacts::{ArtifactGuard, ArtifactSink};
+pub use crate::batch::{Batch, BatchResults, Query};
+pub use crate::browser_log::{BrowserLogEntry, PerformanceEvent};
+pub use crate::builder::ClientBuilder;
 pub use crate::client::*;
+pub use crate::command::{Command, CommandOutcome};
+pub use crate::command_log::{CommandLog, LogEntry};
+pub use crate::context::{FrameGuard, WindowGuard};
 pub use crate::driver::*;
+pub use crate::element_ref::ElementRef;
+#[cfg(any(feature = "chrome", feature = "gecko"))]
+pub use crate::environment::{from_env, Browser};
+pub use crate::events::{EventKind, EventStream, SessionEvent};
+pub use crate::metrics::{CommandStats, Metrics, LATENCY_BUCKETS_MS};
+pub use crate::permissions::{Permission, PermissionState};
+pub use crate::presets::CapabilityPresets;
+#[cfg(any(feature = "chrome", feature = "gecko"))]
+pub use crate::profile::start_profile;
+pub use crate::recording::{DomRecorder, ScreenRecorder};
+pub use crate::recovery::{SessionRecovered, SessionRecovery};
+pub use crate::scope::Scope;
+pub use crate::screenshot::Screenshot;
+pub use crate::select::Select;
+pub use crate::slow_motion::SlowMotion;
+#[cfg(feature = "macros")]
+pub use sulfur_macros::browser_test;
@@ -0,0 +1,144 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time;
+
+use crate::command::{Command, CommandOutcome};
+
+/// Upper bounds (in milliseconds) of the latency histogram buckets. Commands
+/// slower than the last bound are counted in a final overflow bucket.
+pub const LATENCY_BUCKETS_MS: &[u64] = &[1, 5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10000];
+
+/// Counters and latency statistics for a single kind of command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandStats {
+    /// How many times the command was sent.
+    pub count: u64,
+    /// How many of those failed.
+    pub errors: u64,
+    /// The total time spent on this command.
+    pub total: time::Duration,
+    /// The slowest single invocation.
+    pub max: time::Duration,
+    /// Latency histogram, where `buckets[i]` counts commands that took at
+    /// most `LATENCY_BUCKETS_MS[i]`, and the final entry counts the rest.
+    pub buckets: Vec<u64>,
+}
+
+/// A snapshot of the command statistics for a session.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metrics {
+    commands: BTreeMap<&'static str, CommandStats>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Collector {
+    metrics: Arc<Mutex<Metrics>>,
+}
+
+impl Default for CommandStats {
+    fn default() -> Self {
+        CommandStats {
+            count: 0,
+            errors: 0,
+            total: time::Duration::default(),
+            max: time::Duration::default(),
+            buckets: vec![0; LATENCY_BUCKETS_MS.len() + 1],
+        }
+    }
+}
+
+impl CommandStats {
+    /// The mean time taken by this command.
+    pub fn mean(&self) -> time::Duration {
+        if self.count == 0 {
+            return time::Duration::default();
+        }
+        self.total / self.count as u32
+    }
+
+    fn record(&mut self, elapsed: time::Duration, failed: bool) {
+        self.count += 1;
+        if failed {
+            self.errors += 1;
+        }
+        self.total += elapsed;
+        self.max = self.max.max(elapsed);
+        let millis = elapsed.as_millis() as u64;
+        let bucket = LATENCY_BUCKETS_MS
+            .iter()
+            .position(|&bound| millis <= bound)
+            .unwrap_or(LATENCY_BUCKETS_MS.len());
+        self.buckets[bucket] += 1;
+    }
+}
+
+impl Metrics {
+    /// Statistics for each command sent, keyed by command name.
+    pub fn commands(&self) -> &BTreeMap<&'static str, CommandStats> {
+        &self.commands
+    }
+
+    /// Statistics for the named command, eg: `find_element`.
+    pub fn command(&self, name: &str) -> Option<&CommandStats> {
+        self.commands.get(name)
+    }
+
+    /// The command names, ordered by the total time spent on them, slowest
+    /// first.
+    pub fn by_total_time(&self) -> Vec<(&'static str, &CommandStats)> {
+        let mut commands = self
+            .commands
+            .iter()
+            .map(|(name, stats)| (*name, stats))
+            .collect::<Vec<_>>();
+        commands.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.total));
+        commands
+    }
+}
+
+impl Collector {
+    pub(crate) fn record(&self, cmd: &Command, outcome: &CommandOutcome) {
+        let mut metrics = self.metrics.lock().expect("lock metrics");
+        metrics
+            .commands
+            .entry(cmd.name)
+            .or_default()
+            .record(outcome.elapsed, outcome.result.is_err());
+    }
+
+    pub(crate) fn snapshot(&self) -> Metrics {
+        self.metrics.lock().expect("lock metrics").clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_bucket_latencies_per_command() {
+        let collector = Collector::default();
+        let value = serde_json::Value::Null;
+        let err = failure::err_msg("boom");
+        let cmd = Command::post("click", &["session", "abc", "element"], json!({}));
+
+        for (millis, result) in &[(3, Ok(&value)), (7, Ok(&value)), (20_000, Err(&err))] {
+            collector.record(
+                &cmd,
+                &CommandOutcome {
+                    result: *result,
+                    elapsed: time::Duration::from_millis(*millis),
+                },
+            );
+        }
+
+        let metrics = collector.snapshot();
+        let stats = metrics.command("click").expect("click stats");
+        assert_eq!(stats.count, 3);
+        assert_eq!(stats.errors, 1);
+        assert_eq!(stats.max, time::Duration::from_millis(20_000));
+        assert_eq!(stats.buckets[1], 1);
+        assert_eq!(stats.buckets[2], 1);
+        assert_eq!(stats.buckets[LATENCY_BUCKETS_MS.len()], 1);
+    }
+}
@@ -0,0 +1,223 @@
+//! Managing several labelled sessions at once, eg: for testing two users
+//! chatting with each other.
+//!
+//! ```no_run
+//! # fn example(alice: sulfur::DriverHolder, bob: sulfur::DriverHolder) -> Result<(), failure::Error> {
+//! use sulfur::multi::Sessions;
+//! use sulfur::By;
+//!
+//! let mut sessions = Sessions::new();
+//! sessions.add("alice", alice);
+//! sessions.add("bob", bob);
+//!
+//! sessions.broadcast(|client| client.visit("https://chat.example/"))?;
+//! sessions["alice"].find(&By::css("textarea"))?.send_keys("Hi Bob")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::borrow::BorrowMut;
+use std::ops::Index;
+
+use failure::{Error, ResultExt};
+
+use crate::client::Client;
+use crate::command::{Command, CommandOutcome};
+use crate::driver::DriverHolder;
+
+// The key of the hook that logs commands with the session's label.
+const LABEL_HOOK: &str = "sessions-label";
+
+/// A set of sessions, each identified by a label. Sessions may be eg:
+/// [`DriverHolder`]s, plain [`Client`]s, or pooled sessions. Commands sent by
+/// each session are logged (at `debug` level) with its label.
+#[derive(Debug)]
+pub struct Sessions<S = DriverHolder> {
+    sessions: Vec<(String, S)>,
+}
+
+impl<S: BorrowMut<Client>> Sessions<S> {
+    /// Creates an empty set of sessions.
+    pub fn new() -> Self {
+        Sessions {
+            sessions: Vec::new(),
+        }
+    }
+
+    /// Adds `session` under `label`, replacing any existing session with
+    /// the same label. A session that was added before (eg: to another set)
+    /// is logged with its new label only.
+    pub fn add<L: Into<String>>(&mut self, label: L, mut session: S) -> &mut Self {
+        let label = label.into();
+        let (before, after) = (label.clone(), label.clone());
+        session.borrow_mut().replace_command_hook(
+            LABEL_HOOK,
+            move |_: &Client, cmd: &Command| debug!("[{}] {} {}", before, cmd.method, cmd.path),
+            move |_: &Client, cmd: &Command, outcome: &CommandOutcome| {
+                let status = if outcome.result.is_ok() {
+                    "ok"
+                } else {
+                    "failed"
+                };
+                debug!(
+                    "[{}] {} {} after {:?}",
+                    after, cmd.name, status, outcome.elapsed
+                )
+            },
+        );
+        self.sessions.retain(|(l, _)| *l != label);
+        self.sessions.push((label, session));
+        self
+    }
+
+    /// Looks up a session by label.
+    pub fn get(&self, label: &str) -> Option<&S> {
+        self.sessions
+            .iter()
+            .find(|(l, _)| l == label)
+            .map(|(_, session)| session)
+    }
+
+    /// Removes and returns the session with the given label.
+    pub fn remove(&mut self, label: &str) -> Option<S> {
+        let idx = self.sessions.iter().position(|(l, _)| l == label)?;
+        Some(self.sessions.remove(idx).1)
+    }
+
+    /// The labels of the sessions, in the order they were added.
+    pub fn labels(&self) -> impl Iterator<Item = &str> {
+        self.sessions.iter().map(|(l, _)| l.as_str())
+    }
+
+    /// The sessions and their labels, in the order they were added.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &S)> {
+        self.sessions.iter().map(|(l, s)| (l.as_str(), s))
+    }
+
+    /// The number of sessions.
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Whether there are no sessions.
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    /// Runs `f` against each session in turn, returning the results in the
+    /// order the sessions were added. Stops at the first failure, which is
+    /// annotated with the label of the session.
+    pub fn broadcast<R, F>(&self, mut f: F) -> Result<Vec<R>, Error>
+    where
+        F: FnMut(&Client) -> Result<R, Error>,
+    {
+        let mut results = Vec::with_capacity(self.sessions.len());
+        for (label, session) in self.sessions.iter() {
+            let result = f(session.borrow()).with_context(|_| format!("In session {:?}", label))?;
+            results.push(result);
+        }
+        Ok(results)
+    }
+}
+
+impl<S: BorrowMut<Client>> Default for Sessions<S> {
+    fn default() -> Self {
+        Sessions::new()
+    }
+}
+
+impl<S: BorrowMut<Client>> Index<&str> for Sessions<S> {
+    type Output = S;
+
+    /// Panics if there is no session with the given label.
+    fn index(&self, label: &str) -> &S {
+        self.get(label)
+            .unwrap_or_else(|| panic!("No session labelled {:?}", label))
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+    use crate::testing::MockWebDriver;
+
+    #[test]
+    fn broadcasts_to_each_session_in_order() {
+        let (alice, bob) = (
+            MockWebDriver::start().expect("start"),
+            MockWebDriver::start().expect("start"),
+        );
+        alice.set_title("Alice's page");
+        bob.set_title("Bob's page");
+
+        let mut sessions = Sessions::new();
+        sessions.add("alice", alice.client().expect("client"));
+        sessions.add("bob", bob.client().expect("client"));
+        assert_eq!(sessions.labels().collect::<Vec<_>>(), vec!["alice", "bob"]);
+
+        let titles = sessions.broadcast(|c| c.title()).expect("broadcast");
+        assert_eq!(titles, vec!["Alice's page", "Bob's page"]);
+        assert_eq!(sessions["bob"].title().expect("title"), "Bob's page");
+
+        drop(bob);
+        let err = sessions
+            .broadcast(|c| c.title())
+            .expect_err("bob has gone away");
+        assert!(
+            err.to_string().contains("\"bob\""),
+            "Error should name the session: {}",
+            err
+        );
+    }
+
+    #[test]
+    fn relabelling_a_session_replaces_its_logging() {
+        let mock = MockWebDriver::start().expect("start");
+        let mut sessions = Sessions::new();
+        sessions.add("alice", mock.client().expect("client"));
+        let client = sessions.remove("alice").expect("alice");
+        sessions.add("bob", client);
+
+        let logged = capture_logs(|| {
+            sessions["bob"].title().expect("title");
+        });
+        let labelled = logged
+            .iter()
+            .filter(|l| l.starts_with("[alice]") || l.starts_with("[bob]"))
+            .collect::<Vec<_>>();
+        assert_eq!(labelled.len(), 2, "One line before and after: {:?}", logged);
+        assert!(labelled[0].starts_with("[bob] GET "), "{:?}", logged);
+        assert!(labelled[1].starts_with("[bob] title ok "), "{:?}", logged);
+    }
+
+    // Records log messages from the current thread, which is where command
+    // hooks run.
+    struct CaptureLogs;
+
+    thread_local! {
+        static CAPTURED: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    }
+
+    impl log::Log for CaptureLogs {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            CAPTURED.with(|c| c.borrow_mut().push(record.args().to_string()));
+        }
+
+        fn flush(&self) {}
+    }
+
+    fn capture_logs<F: FnOnce()>(f: F) -> Vec<String> {
+        static LOGGER: CaptureLogs = CaptureLogs;
+        log::set_logger(&LOGGER).unwrap_or_default();
+        log::set_max_level(log::LevelFilter::Debug);
+        CAPTURED.with(|c| c.borrow_mut().clear());
+        f();
+        CAPTURED.with(|c| c.borrow_mut().drain(..).collect())
+    }
+}
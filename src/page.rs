@@ -0,0 +1,101 @@
+//! Support for structuring tests around page objects, ie: types that describe
+//! the elements of a page, and the operations on it.
+//!
+//! With the `macros` feature, `PageObject` can be derived for structs whose
+//! fields are annotated with a locator:
+//!
+//! ```ignore
+//! use sulfur::page::{LazyElement, PageObject};
+//!
+//! #[derive(PageObject)]
+//! struct LoginPage<'c> {
+//!     #[by(id = "username")]
+//!     username: LazyElement<'c>,
+//!     #[by(css = ".login .submit")]
+//!     submit: LazyElement<'c>,
+//! }
+//!
+//! let page: LoginPage = client.page();
+//! page.username.send_keys("alice")?;
+//! page.submit.click()?;
+//! ```
+
+use failure::Error;
+
+use crate::client::{By, Client};
+use crate::element_ref::ElementRef;
+
+#[cfg(feature = "macros")]
+pub use sulfur_macros::PageObject;
+
+/// A type describing a page, or a part of one, that can be created from a
+/// client; see [`Client::page`].
+pub trait PageObject<'c>: Sized {
+    /// Creates the page object. This should not send any commands, so that
+    /// page objects can be created before the page has loaded.
+    fn from_client(client: &'c Client) -> Self;
+}
+
+/// An element that is looked up each time it is used, so it never goes stale.
+#[derive(Debug, Clone)]
+pub struct LazyElement<'c> {
+    client: &'c Client,
+    by: By,
+}
+
+impl<'c> LazyElement<'c> {
+    /// An element found by `by`.
+    pub fn new(client: &'c Client, by: By) -> Self {
+        LazyElement { client, by }
+    }
+
+    /// The locator used to find the element.
+    pub fn by(&self) -> &By {
+        &self.by
+    }
+
+    /// Finds the element; see [`Client::find`].
+    pub fn find(&self) -> Result<ElementRef<'c>, Error> {
+        self.client.find(&self.by)
+    }
+
+    /// Finds all matching elements; see [`Client::find_all`].
+    pub fn find_all(&self) -> Result<Vec<ElementRef<'c>>, Error> {
+        self.client.find_all(&self.by)
+    }
+
+    /// Whether any matching element is currently present.
+    pub fn exists(&self) -> Result<bool, Error> {
+        Ok(!self.client.find_elements(&self.by)?.is_empty())
+    }
+
+    /// Finds and clicks the element.
+    pub fn click(&self) -> Result<(), Error> {
+        self.find()?.click()
+    }
+
+    /// Finds the element, and returns its text.
+    pub fn text(&self) -> Result<String, Error> {
+        self.find()?.text()
+    }
+
+    /// Finds the element, and types `keys` into it.
+    pub fn send_keys(&self, keys: &str) -> Result<(), Error> {
+        self.find()?.send_keys(keys)
+    }
+
+    /// Finds and clears the element.
+    pub fn clear(&self) -> Result<(), Error> {
+        self.find()?.clear()
+    }
+
+    /// Finds the element, and returns the named attribute.
+    pub fn attribute(&self, attribute: &str) -> Result<Option<String>, Error> {
+        self.find()?.attribute(attribute)
+    }
+
+    /// Finds the element, and checks whether it is displayed.
+    pub fn is_displayed(&self) -> Result<bool, Error> {
+        self.find()?.is_displayed()
+    }
+}
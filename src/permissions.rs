@@ -0,0 +1,54 @@
+/// A browser permission that would otherwise prompt the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Permission {
+    /// Showing notifications.
+    Notifications,
+    /// Reading the device's location.
+    Geolocation,
+    /// Reading from the clipboard.
+    ClipboardRead,
+    /// Writing to the clipboard.
+    ClipboardWrite,
+    /// Using the camera.
+    Camera,
+    /// Using the microphone.
+    Microphone,
+}
+
+/// The state to put a permission in; see [`Client::set_permission`](crate::Client::set_permission).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PermissionState {
+    /// Allowed, without prompting.
+    Granted,
+    /// Refused, without prompting.
+    Denied,
+    /// The user is prompted.
+    Prompt,
+}
+
+impl Permission {
+    // The name used by the Permissions API, and so the webdriver extension.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Permission::Notifications => "notifications",
+            Permission::Geolocation => "geolocation",
+            Permission::ClipboardRead => "clipboard-read",
+            Permission::ClipboardWrite => "clipboard-write",
+            Permission::Camera => "camera",
+            Permission::Microphone => "microphone",
+        }
+    }
+
+    // The name used by the DevTools `Browser.PermissionType`.
+    pub(crate) fn devtools_name(self) -> &'static str {
+        match self {
+            Permission::Notifications => "notifications",
+            Permission::Geolocation => "geolocation",
+            Permission::ClipboardRead => "clipboardReadWrite",
+            Permission::ClipboardWrite => "clipboardSanitizedWrite",
+            Permission::Camera => "videoCapture",
+            Permission::Microphone => "audioCapture",
+        }
+    }
+}
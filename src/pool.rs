@@ -0,0 +1,183 @@
+//! A pool of ready-started browser sessions, so that tests running in
+//! parallel do not each pay the cost of starting a browser.
+//!
+//! ```no_run
+//! # type Start = fn() -> Result<sulfur::DriverHolder, failure::Error>;
+//! # fn example(start_browser: Start) -> Result<(), failure::Error> {
+//! use sulfur::pool::SessionPool;
+//!
+//! // eg: `|| chrome::start(chrome::Config::default().headless(true))`
+//! let pool = SessionPool::new(4, start_browser)?;
+//! let session = pool.checkout()?;
+//! session.visit("https://example.com/")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::borrow::{Borrow, BorrowMut};
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+use failure::Error;
+
+use crate::client::Client;
+use crate::driver::DriverHolder;
+
+type StartFn = dyn Fn() -> Result<DriverHolder, Error> + Send + Sync;
+
+/// A fixed size pool of driver and browser pairs.
+///
+/// Sessions are health checked and reset (cookies cleared, and navigated to
+/// `about:blank`) when they are returned; sessions that fail this are
+/// replaced with a freshly started one. Note that command hooks and logs
+/// persist between uses of a session.
+#[derive(Clone)]
+pub struct SessionPool {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    start: Arc<StartFn>,
+    state: Mutex<State>,
+    returned: Condvar,
+}
+
+struct State {
+    idle: Vec<DriverHolder>,
+    // Sessions that are either idle or checked out.
+    live: usize,
+}
+
+/// A session checked out from a [`SessionPool`]. Dereferences to the
+/// [`Client`], and is returned to the pool when dropped.
+pub struct PooledSession {
+    pool: Arc<Inner>,
+    holder: Option<DriverHolder>,
+}
+
+impl SessionPool {
+    /// Start `size` sessions in parallel with `start`, eg:
+    /// `|| chrome::start(&config)`. `start` is also used to replace crashed
+    /// sessions.
+    pub fn new<F>(size: usize, start: F) -> Result<Self, Error>
+    where
+        F: Fn() -> Result<DriverHolder, Error> + Send + Sync + 'static,
+    {
+        let start: Arc<StartFn> = Arc::new(start);
+        let starting = (0..size)
+            .map(|i| {
+                let start = start.clone();
+                thread::Builder::new()
+                    .name(format!("SessionPool start {}", i))
+                    .spawn(move || start())
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut idle = Vec::with_capacity(size);
+        for handle in starting {
+            match handle.join() {
+                Ok(holder) => idle.push(holder?),
+                Err(_) => bail!("Starting a pooled session panicked"),
+            }
+        }
+
+        Ok(SessionPool {
+            inner: Arc::new(Inner {
+                start,
+                state: Mutex::new(State {
+                    live: idle.len(),
+                    idle,
+                }),
+                returned: Condvar::new(),
+            }),
+        })
+    }
+
+    /// Take a session from the pool, blocking until one is available. Fails
+    /// if every session has crashed and could not be replaced.
+    pub fn checkout(&self) -> Result<PooledSession, Error> {
+        let mut state = self.inner.state.lock().expect("unpoison");
+        loop {
+            if let Some(holder) = state.idle.pop() {
+                return Ok(PooledSession {
+                    pool: self.inner.clone(),
+                    holder: Some(holder),
+                });
+            }
+            if state.live == 0 {
+                bail!("No sessions left in the pool");
+            }
+            state = self.inner.returned.wait(state).expect("unpoison");
+        }
+    }
+
+    /// The number of sessions waiting to be checked out.
+    pub fn idle(&self) -> usize {
+        self.inner.state.lock().expect("unpoison").idle.len()
+    }
+}
+
+impl Inner {
+    fn checkin(&self, holder: DriverHolder) {
+        let holder = match reset(&holder) {
+            Ok(()) => Some(holder),
+            Err(e) => {
+                warn!("Replacing unhealthy pooled session: {}", e);
+                if let Err(e) = holder.close() {
+                    debug!("Closing unhealthy session: {}", e);
+                }
+                (self.start)()
+                    .map_err(|e| error!("Could not replace pooled session: {}", e))
+                    .ok()
+            }
+        };
+
+        let mut state = self.state.lock().expect("unpoison");
+        match holder {
+            Some(holder) => state.idle.push(holder),
+            None => state.live -= 1,
+        }
+        self.returned.notify_one();
+    }
+}
+
+// Clears up after the previous user, and checks that the session still works.
+fn reset(client: &Client) -> Result<(), Error> {
+    client.delete_all_cookies()?;
+    client.visit("about:blank")?;
+    Ok(())
+}
+
+impl Deref for PooledSession {
+    type Target = Client;
+    fn deref(&self) -> &Self::Target {
+        self.holder.as_ref().expect("session present until drop")
+    }
+}
+
+impl DerefMut for PooledSession {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.holder.as_mut().expect("session present until drop")
+    }
+}
+
+impl Borrow<Client> for PooledSession {
+    fn borrow(&self) -> &Client {
+        self
+    }
+}
+
+impl BorrowMut<Client> for PooledSession {
+    fn borrow_mut(&mut self) -> &mut Client {
+        self
+    }
+}
+
+impl Drop for PooledSession {
+    fn drop(&mut self) {
+        if let Some(holder) = self.holder.take() {
+            self.pool.checkin(holder);
+        }
+    }
+}
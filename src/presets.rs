@@ -0,0 +1,142 @@
+use std::collections::BTreeMap;
+
+use crate::client::Capabilities;
+
+/// A registry of named [`Capabilities`], eg: for running a suite against
+/// each browser on a remote grid. [`CapabilityPresets::builtin`] includes
+/// presets for common desktop and mobile configurations. Unlike a
+/// `profile::Profile`, these only hold capabilities, so are not tied to
+/// starting a local driver.
+///
+/// ```no_run
+/// # fn example() -> Result<(), failure::Error> {
+/// use sulfur::{Capabilities, Client, CapabilityPresets};
+///
+/// let mut presets = CapabilityPresets::builtin();
+/// presets.register(
+///     "iphone-12",
+///     Capabilities::new().browser_name("chrome").capability(
+///         "goog:chromeOptions",
+///         serde_json::json!({"mobileEmulation": {"deviceName": "iPhone 12 Pro"}}),
+///     ),
+/// );
+/// for (name, caps) in presets.iter() {
+///     let client = Client::builder()
+///         .url("http://localhost:4444/")
+///         .capabilities(caps.clone())
+///         .build()?;
+///     println!("{}: {}", name, client.title()?);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CapabilityPresets {
+    presets: BTreeMap<String, Capabilities>,
+}
+
+impl CapabilityPresets {
+    /// An empty registry.
+    pub fn new() -> Self {
+        CapabilityPresets::default()
+    }
+
+    /// A registry holding the built-in presets:
+    ///
+    /// * `desktop-chrome`: Chrome with a 1920x1080 window.
+    /// * `headless-chrome`: Chrome, without the UI.
+    /// * `desktop-firefox`: Firefox with a 1920x1080 window.
+    /// * `headless-firefox`: Firefox, without the UI.
+    /// * `iphone-se` and `pixel-5`: Chrome emulating the named phone.
+    pub fn builtin() -> Self {
+        let chrome = |args: &[&str]| {
+            Capabilities::new()
+                .browser_name("chrome")
+                .capability("goog:chromeOptions", json!({ "args": args }))
+        };
+        let firefox = |args: &[&str]| {
+            Capabilities::new()
+                .browser_name("firefox")
+                .capability("moz:firefoxOptions", json!({ "args": args }))
+        };
+        let phone = |device: &str| {
+            Capabilities::new().browser_name("chrome").capability(
+                "goog:chromeOptions",
+                json!({ "mobileEmulation": { "deviceName": device } }),
+            )
+        };
+
+        let mut presets = CapabilityPresets::new();
+        presets
+            .register("desktop-chrome", chrome(&["--window-size=1920,1080"]))
+            .register("headless-chrome", chrome(&["--headless"]))
+            .register(
+                "desktop-firefox",
+                firefox(&["--width=1920", "--height=1080"]),
+            )
+            .register("headless-firefox", firefox(&["--headless"]))
+            .register("iphone-se", phone("iPhone SE"))
+            .register("pixel-5", phone("Pixel 5"));
+        presets
+    }
+
+    /// Register `capabilities` as `name`, replacing any existing preset
+    /// with that name.
+    pub fn register<S: Into<String>>(&mut self, name: S, capabilities: Capabilities) -> &mut Self {
+        self.presets.insert(name.into(), capabilities);
+        self
+    }
+
+    /// The capabilities registered as `name`, if any.
+    pub fn get(&self, name: &str) -> Option<&Capabilities> {
+        self.presets.get(name)
+    }
+
+    /// The registered names, in order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.presets.keys().map(String::as_str)
+    }
+
+    /// The registered presets, ordered by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Capabilities)> {
+        self.presets
+            .iter()
+            .map(|(name, caps)| (name.as_str(), caps))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_can_be_replaced_and_extended() {
+        let mut presets = CapabilityPresets::builtin();
+        assert_eq!(
+            presets.get("iphone-se").map(|caps| &caps.always_match),
+            Some(&json!({
+                "browserName": "chrome",
+                "goog:chromeOptions": { "mobileEmulation": { "deviceName": "iPhone SE" } },
+            }))
+        );
+        assert_eq!(presets.get("netscape"), None);
+
+        let caps = Capabilities::new().browser_name("safari");
+        presets
+            .register("desktop-safari", caps.clone())
+            .register("iphone-se", caps.clone());
+        assert_eq!(presets.get("iphone-se"), Some(&caps));
+        assert_eq!(
+            presets.names().collect::<Vec<_>>(),
+            vec![
+                "desktop-chrome",
+                "desktop-firefox",
+                "desktop-safari",
+                "headless-chrome",
+                "headless-firefox",
+                "iphone-se",
+                "pixel-5",
+            ]
+        );
+    }
+}
@@ -0,0 +1,315 @@
+use std::io;
+use std::process::{Child, Command};
+#[cfg(unix)]
+use std::sync::atomic::{AtomicI32, Ordering};
+#[cfg(unix)]
+use std::sync::Once;
+use std::time;
+
+use failure::Error;
+
+use crate::wait::wait_until;
+
+// How long drivers are given to exit after being asked to terminate, before
+// they are killed.
+pub(crate) const DEFAULT_GRACE_PERIOD: time::Duration = time::Duration::from_secs(5);
+
+// The most drivers whose process groups are signalled if we are interrupted.
+#[cfg(unix)]
+const MAX_GROUPS: usize = 64;
+// The process groups of running drivers, so that they can be signalled if we
+// are interrupted; zero marks a free slot. As this is read by a signal
+// handler, it has a fixed size.
+#[cfg(unix)]
+static GROUPS: [AtomicI32; MAX_GROUPS] = [const { AtomicI32::new(0) }; MAX_GROUPS];
+
+// Spawns a driver. On Unix, it is started in its own process group, so that
+// the browsers it starts can be cleaned up along with it, even if the driver
+// itself exits uncleanly. This also means that the driver does not receive
+// signals sent to our own process group, so `SIGINT` (eg: from Ctrl-C) and
+// `SIGTERM` are forwarded to it, unless the application handles them itself.
+// On Windows, nothing cleans up after a driver that is killed, as that would
+// need a job object, which is not implemented.
+pub(crate) fn spawn(cmd: &mut Command) -> io::Result<Child> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        cmd.process_group(0);
+    }
+    let child = cmd.spawn()?;
+    #[cfg(unix)]
+    track_group(&child);
+    Ok(child)
+}
+
+// Whether `child` has exited. On Unix, this does not reap it, so that its
+// process group can still be cleaned up by `terminate`.
+pub(crate) fn has_exited(child: &mut Child) -> Result<bool, Error> {
+    Ok(state(child)? != State::Running)
+}
+
+// Stops `child`, and on Unix, its process group. The group is sent
+// `SIGTERM`, so that the driver can shut down the browsers it started, and
+// is only killed if the driver is still running after `grace`. Elsewhere,
+// the driver is killed immediately. The group is only signalled while the
+// driver is unreaped, as until then, its id cannot be re-used.
+pub(crate) fn terminate(child: &mut Child, grace: time::Duration) -> Result<(), Error> {
+    match state(child)? {
+        State::Reaped => {
+            debug!("Child {} already reaped", child.id());
+            forget_group(child);
+            return Ok(());
+        }
+        State::Exited => {
+            debug!("Child {} already exited", child.id());
+            // Clean up anything the driver left behind.
+            signal_group(child, Signal::Kill)?;
+        }
+        State::Running => {
+            if signal_group(child, Signal::Term)? {
+                let exited = wait_until(grace, || has_exited(child))?;
+                if exited {
+                    debug!("Child {} exited after SIGTERM", child.id());
+                } else {
+                    warn!(
+                        "Child {} still running {:?} after SIGTERM; killing it",
+                        child.id(),
+                        grace
+                    );
+                }
+                signal_group(child, Signal::Kill)?;
+            } else {
+                child.kill()?;
+            }
+        }
+    }
+    forget_group(child);
+    let status = child.wait()?;
+    debug!("Child {} exited with {}", child.id(), status);
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Running,
+    // Exited, but not yet reaped.
+    Exited,
+    Reaped,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Signal {
+    Term,
+    Kill,
+}
+
+#[cfg(unix)]
+fn state(child: &mut Child) -> Result<State, Error> {
+    // Safety: `siginfo_t` is plain data, for which zeroes are valid.
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    // Safety: `info` is valid for writes. With `WNOWAIT`, the child is left
+    // to be reaped by `Child::wait`.
+    let res = unsafe {
+        libc::waitid(
+            libc::P_PID,
+            child.id() as libc::id_t,
+            &mut info,
+            libc::WEXITED | libc::WNOHANG | libc::WNOWAIT,
+        )
+    };
+    if res == 0 {
+        // `info` is left zeroed while the child is running.
+        return Ok(if info.si_signo == 0 {
+            State::Running
+        } else {
+            State::Exited
+        });
+    }
+    let err = io::Error::last_os_error();
+    if err.raw_os_error() == Some(libc::ECHILD) {
+        return Ok(State::Reaped);
+    }
+    Err(err.into())
+}
+
+#[cfg(not(unix))]
+fn state(child: &mut Child) -> Result<State, Error> {
+    match child.try_wait()? {
+        Some(_) => Ok(State::Reaped),
+        None => Ok(State::Running),
+    }
+}
+
+// Sends `signal` to the child's process group, returning whether signals
+// are supported on this platform.
+#[cfg(unix)]
+fn signal_group(child: &Child, signal: Signal) -> Result<bool, Error> {
+    let signal = match signal {
+        Signal::Term => libc::SIGTERM,
+        Signal::Kill => libc::SIGKILL,
+    };
+    // Safety: `kill` has no memory safety requirements. The child leads its
+    // process group, and callers ensure it has not been reaped, so the id
+    // cannot have been re-used.
+    let res = unsafe { libc::kill(-(child.id() as libc::pid_t), signal) };
+    if res != 0 {
+        let err = io::Error::last_os_error();
+        // The group has already gone away.
+        if err.raw_os_error() != Some(libc::ESRCH) {
+            return Err(err.into());
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(not(unix))]
+fn signal_group(_: &Child, _: Signal) -> Result<bool, Error> {
+    Ok(false)
+}
+
+// Records the child's process group, so that signals we receive are
+// forwarded to it.
+#[cfg(unix)]
+fn track_group(child: &Child) {
+    static FORWARDING: Once = Once::new();
+    FORWARDING.call_once(|| {
+        forward_signal(libc::SIGINT);
+        forward_signal(libc::SIGTERM);
+    });
+    let pgid = child.id() as i32;
+    let tracked = GROUPS.iter().any(|slot| {
+        slot.compare_exchange(0, pgid, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+    });
+    if !tracked {
+        debug!(
+            "Too many drivers to forward signals to process group {}",
+            pgid
+        );
+    }
+}
+
+// Stops forwarding signals to the child's process group. This must happen
+// before the child is reaped.
+#[cfg(unix)]
+fn forget_group(child: &Child) {
+    let pgid = child.id() as i32;
+    for slot in GROUPS.iter() {
+        let _ = slot.compare_exchange(pgid, 0, Ordering::SeqCst, Ordering::SeqCst);
+    }
+}
+
+#[cfg(not(unix))]
+fn forget_group(_: &Child) {}
+
+// Handles `signal` with `forward_to_groups`, unless the application already
+// handles (or ignores) it.
+#[cfg(unix)]
+fn forward_signal(signal: libc::c_int) {
+    let handler = forward_to_groups as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    // Safety: the handler only makes async-signal-safe calls.
+    unsafe {
+        let previous = libc::signal(signal, handler);
+        if previous != libc::SIG_DFL {
+            libc::signal(signal, previous);
+        }
+    }
+}
+
+#[cfg(unix)]
+extern "C" fn forward_to_groups(signal: libc::c_int) {
+    for slot in GROUPS.iter() {
+        let pgid = slot.load(Ordering::SeqCst);
+        if pgid != 0 {
+            // Safety: as for `signal_group`.
+            unsafe { libc::kill(-pgid, signal) };
+        }
+    }
+    // Then go down with the signal, as we would have without the handler.
+    // Safety: both are async-signal-safe.
+    unsafe {
+        libc::signal(signal, libc::SIG_DFL);
+        libc::raise(signal);
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::process::{Command, Stdio};
+
+    use super::*;
+
+    #[test]
+    fn terminates_gracefully() {
+        let mut child = spawn(Command::new("sleep").arg("10")).expect("spawn");
+        terminate(&mut child, time::Duration::from_secs(5)).expect("terminate");
+        let status = child.try_wait().expect("try_wait").expect("exited");
+        use std::os::unix::process::ExitStatusExt;
+        assert_eq!(status.signal(), Some(libc::SIGTERM));
+    }
+
+    #[test]
+    fn kills_children_that_ignore_sigterm() {
+        // The shell's `sleep` outlives it, so must not hold on to our output.
+        let mut child = spawn(
+            Command::new("sh")
+                .args(["-c", "trap '' TERM; sleep 10"])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null()),
+        )
+        .expect("spawn");
+        // Give the shell a chance to install the trap.
+        std::thread::sleep(time::Duration::from_millis(200));
+        terminate(&mut child, time::Duration::from_millis(200)).expect("terminate");
+        let status = child.try_wait().expect("try_wait").expect("exited");
+        use std::os::unix::process::ExitStatusExt;
+        assert_eq!(status.signal(), Some(libc::SIGKILL));
+    }
+
+    #[test]
+    fn cleans_up_the_process_group() {
+        // The shell exits straight away, leaving its `sleep` behind.
+        let mut child = spawn(
+            Command::new("sh")
+                .args(["-c", "sleep 10 >/dev/null & echo $!"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null()),
+        )
+        .expect("spawn");
+        let mut out = String::new();
+        std::io::Read::read_to_string(child.stdout.as_mut().expect("stdout"), &mut out)
+            .expect("read");
+        let orphan: libc::pid_t = out.trim().parse().expect("pid");
+        let exited = wait_until(time::Duration::from_secs(5), || has_exited(&mut child));
+        assert!(exited.expect("wait"), "shell should have exited");
+
+        terminate(&mut child, time::Duration::from_secs(1)).expect("terminate");
+        // The orphan is reparented (to init or a subreaper), so we cannot
+        // reap it ourselves; wait for it to go away.
+        let alive = || unsafe { libc::kill(orphan, 0) } == 0;
+        let gone = wait_until(time::Duration::from_secs(5), || Ok(!alive())).expect("wait");
+        assert!(gone, "orphaned process {} should have been killed", orphan);
+    }
+
+    #[test]
+    fn leaves_groups_of_reaped_children_alone() {
+        let mut child = spawn(
+            Command::new("sh")
+                .args(["-c", "sleep 10 >/dev/null & echo $!"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null()),
+        )
+        .expect("spawn");
+        let mut out = String::new();
+        std::io::Read::read_to_string(child.stdout.as_mut().expect("stdout"), &mut out)
+            .expect("read");
+        let orphan: libc::pid_t = out.trim().parse().expect("pid");
+        child.wait().expect("wait");
+
+        // The group's id could have been re-used by now.
+        terminate(&mut child, time::Duration::from_secs(1)).expect("terminate");
+        let alive = unsafe { libc::kill(orphan, 0) } == 0;
+        unsafe { libc::kill(orphan, libc::SIGKILL) };
+        assert!(alive, "process {} should have been left alone", orphan);
+    }
+}
@@ -0,0 +1,341 @@
+//! Named browser profiles, loaded from a config file, so that a team can
+//! share and version the browsers their suites run against.
+//!
+//! Profiles are read from `sulfur.toml` (with the `toml` feature) or
+//! `sulfur.json` in the current directory, or the file named by
+//! `$SULFUR_CONFIG`:
+//!
+//! ```toml
+//! [profiles.desktop-firefox]
+//! browser = "firefox"
+//!
+//! [profiles.mobile-chrome]
+//! browser = "chrome"
+//! headless = true
+//! args = ["--window-size=375,812"]
+//! remote_url = "http://selenium:4444/wd/hub"
+//!
+//! [profiles.mobile-chrome.capabilities."goog:chromeOptions".mobileEmulation]
+//! deviceName = "iPhone X"
+//! ```
+//!
+//! ```no_run
+//! # fn example() -> Result<(), failure::Error> {
+//! let session = sulfur::start_profile("mobile-chrome")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use failure::{Error, ResultExt};
+use serde::{Deserialize, Deserializer};
+
+#[cfg(feature = "chrome")]
+use crate::chrome;
+use crate::driver::DriverHolder;
+use crate::environment::Browser;
+#[cfg(feature = "gecko")]
+use crate::gecko;
+
+/// The environment variable naming the config file to read profiles from.
+pub const CONFIG_ENV: &str = "SULFUR_CONFIG";
+// The files searched for in the current directory, in order.
+const CONFIG_FILES: &[&str] = &["sulfur.toml", "sulfur.json"];
+
+/// How to start a browser session: which browser, where, and with what
+/// arguments and capabilities.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    browser: Browser,
+    headless: bool,
+    args: Vec<String>,
+    capabilities: serde_json::Map<String, serde_json::Value>,
+    remote_url: Option<String>,
+    driver_path: Option<PathBuf>,
+}
+
+/// A set of named [`Profile`]s, as read from a config file.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileFile {
+    #[serde(default)]
+    profiles: BTreeMap<String, Profile>,
+}
+
+/// Start a session with the profile called `name`, from the config file
+/// found as described in the [module documentation](crate::profile).
+pub fn start_profile(name: &str) -> Result<DriverHolder, Error> {
+    let file = ProfileFile::find()?;
+    info!("Starting session with profile {:?}", name);
+    file.profile(name)?.start()
+}
+
+impl Profile {
+    /// A profile for a headless `browser`, started locally.
+    pub fn new(browser: Browser) -> Self {
+        Profile {
+            browser,
+            ..Profile::default()
+        }
+    }
+
+    /// The browser this profile starts.
+    pub fn browser(&self) -> Browser {
+        self.browser
+    }
+
+    /// Whether to hide the browser UI. Defaults to `true`.
+    pub fn headless(&mut self, headless: bool) -> &mut Self {
+        self.headless = headless;
+        self
+    }
+
+    /// Pass `arg` on the browser command line.
+    pub fn arg<S: Into<String>>(&mut self, arg: S) -> &mut Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Request the capability `name`; see eg: [`chrome::Config::capability`].
+    pub fn capability<K: Into<String>, V: Into<serde_json::Value>>(
+        &mut self,
+        name: K,
+        value: V,
+    ) -> &mut Self {
+        self.capabilities.insert(name.into(), value.into());
+        self
+    }
+
+    /// Attach to the driver or grid at `url`, rather than starting a driver.
+    pub fn remote_url<S: Into<String>>(&mut self, url: S) -> &mut Self {
+        self.remote_url = Some(url.into());
+        self
+    }
+
+    /// Run the driver binary at `path`, rather than finding it on `$PATH`.
+    pub fn driver_path<P: Into<PathBuf>>(&mut self, path: P) -> &mut Self {
+        self.driver_path = Some(path.into());
+        self
+    }
+
+    /// Start a session as described by this profile. The session is
+    /// restarted with the same profile by [`DriverHolder::restart`].
+    pub fn start(&self) -> Result<DriverHolder, Error> {
+        let profile = self.clone();
+        let holder = match self.browser {
+            #[cfg(feature = "chrome")]
+            Browser::Chrome => {
+                let driver = match &self.remote_url {
+                    Some(url) => chrome::Driver::connect(url)?,
+                    None => {
+                        let mut config = chrome::DriverConfig::default();
+                        if let Some(path) = &self.driver_path {
+                            config.path(path);
+                        }
+                        chrome::Driver::driver_config(&config)?
+                    }
+                };
+                let mut config = chrome::Config::default();
+                config.headless(self.headless);
+                for arg in &self.args {
+                    config.arg(arg.as_str());
+                }
+                for (name, value) in &self.capabilities {
+                    config.capability(name.as_str(), value.clone());
+                }
+                let client = driver.new_session_config(&config)?;
+                DriverHolder::new(Box::new(driver), client)
+            }
+            #[cfg(feature = "gecko")]
+            Browser::Firefox => {
+                let driver = match &self.remote_url {
+                    Some(url) => gecko::Driver::connect(url)?,
+                    None => {
+                        let mut config = gecko::DriverConfig::default();
+                        if let Some(path) = &self.driver_path {
+                            config.path(path);
+                        }
+                        gecko::Driver::driver_config(&config)?
+                    }
+                };
+                let mut config = gecko::Config::default();
+                config.headless(self.headless);
+                for arg in &self.args {
+                    config.arg(arg.as_str());
+                }
+                for (name, value) in &self.capabilities {
+                    config.capability(name.as_str(), value.clone());
+                }
+                let client = driver.new_session_config(&config)?;
+                DriverHolder::new(Box::new(driver), client)
+            }
+            #[allow(unreachable_patterns)]
+            other => bail!("Support for {} is not enabled in this build", other),
+        };
+        Ok(holder.restart_with(move || profile.start()))
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Profile {
+            browser: Browser::default(),
+            headless: true,
+            args: Vec::new(),
+            capabilities: serde_json::Map::new(),
+            remote_url: None,
+            driver_path: None,
+        }
+    }
+}
+
+impl ProfileFile {
+    /// Read the config file named by `$SULFUR_CONFIG`, or else the first of
+    /// `sulfur.toml` and `sulfur.json` in the current directory.
+    pub fn find() -> Result<Self, Error> {
+        if let Some(path) = env::var_os(CONFIG_ENV) {
+            return Self::load(path);
+        }
+        match CONFIG_FILES.iter().map(Path::new).find(|p| p.exists()) {
+            Some(path) => Self::load(path),
+            None => bail!(
+                "No profile config found; set ${} or create one of {:?}",
+                CONFIG_ENV,
+                CONFIG_FILES
+            ),
+        }
+    }
+
+    /// Read profiles from `path`, as TOML or JSON according to its
+    /// extension.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .with_context(|_| format!("Reading profiles from {:?}", path))?;
+        let file = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Self::from_toml(&content),
+            Some("json") => Self::from_json(&content),
+            _ => bail!("Unknown profile config format: {:?}", path),
+        };
+        Ok(file.with_context(|_| format!("Parsing profiles from {:?}", path))?)
+    }
+
+    /// Parse profiles from TOML.
+    #[cfg(feature = "toml")]
+    pub fn from_toml(content: &str) -> Result<Self, Error> {
+        Ok(toml::from_str(content)?)
+    }
+
+    /// Parse profiles from TOML. This needs the `toml` feature.
+    #[cfg(not(feature = "toml"))]
+    pub fn from_toml(_content: &str) -> Result<Self, Error> {
+        bail!("Reading TOML profiles needs the `toml` feature")
+    }
+
+    /// Parse profiles from JSON.
+    pub fn from_json(content: &str) -> Result<Self, Error> {
+        Ok(serde_json::from_str(content)?)
+    }
+
+    /// The profile called `name`.
+    pub fn profile(&self, name: &str) -> Result<&Profile, Error> {
+        match self.profiles.get(name) {
+            Some(profile) => Ok(profile),
+            None => bail!(
+                "No profile {:?}; expected one of {:?}",
+                name,
+                self.profiles.keys().collect::<Vec<_>>()
+            ),
+        }
+    }
+
+    /// The names of the profiles defined.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.profiles.keys().map(String::as_str)
+    }
+}
+
+impl<'de> Deserialize<'de> for Browser {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_json_profiles() {
+        let file = ProfileFile::from_json(
+            r#"{"profiles": {
+                "desktop-firefox": {"browser": "firefox", "headless": false},
+                "mobile-chrome": {
+                    "browser": "chrome",
+                    "args": ["--window-size=375,812"],
+                    "capabilities": {
+                        "goog:chromeOptions": {"mobileEmulation": {"deviceName": "iPhone X"}}
+                    },
+                    "remote_url": "http://selenium:4444/wd/hub"
+                }
+            }}"#,
+        )
+        .expect("parse");
+
+        assert_eq!(
+            file.names().collect::<Vec<_>>(),
+            vec!["desktop-firefox", "mobile-chrome"]
+        );
+        let mut firefox = Profile::new(Browser::Firefox);
+        firefox.headless(false);
+        assert_eq!(file.profile("desktop-firefox").expect("firefox"), &firefox);
+
+        let mut mobile = Profile::new(Browser::Chrome);
+        mobile
+            .arg("--window-size=375,812")
+            .capability(
+                "goog:chromeOptions",
+                json!({"mobileEmulation": {"deviceName": "iPhone X"}}),
+            )
+            .remote_url("http://selenium:4444/wd/hub");
+        assert_eq!(file.profile("mobile-chrome").expect("mobile"), &mobile);
+
+        assert!(file.profile("netscape").is_err());
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn reads_toml_profiles() {
+        let file = ProfileFile::from_toml(
+            r#"
+            [profiles.mobile-chrome]
+            browser = "chrome"
+            args = ["--window-size=375,812"]
+
+            [profiles.mobile-chrome.capabilities."goog:chromeOptions".mobileEmulation]
+            deviceName = "iPhone X"
+            "#,
+        )
+        .expect("parse");
+
+        let mut mobile = Profile::new(Browser::Chrome);
+        mobile.arg("--window-size=375,812").capability(
+            "goog:chromeOptions",
+            json!({"mobileEmulation": {"deviceName": "iPhone X"}}),
+        );
+        assert_eq!(file.profile("mobile-chrome").expect("mobile"), &mobile);
+    }
+
+    #[test]
+    fn rejects_unknown_settings() {
+        let err = ProfileFile::from_json(r#"{"profiles": {"x": {"broswer": "chrome"}}}"#)
+            .expect_err("typo");
+        assert!(err.to_string().contains("broswer"), "{}", err);
+    }
+}
@@ -0,0 +1,712 @@
+//! A local recording HTTP proxy, for asserting on the requests that a page
+//! makes, eg: which API calls the frontend made.
+//!
+//! Plain HTTP requests are recorded in full. Responses are relayed as they
+//! arrive, eg: for server-sent events, and recorded once complete, or once
+//! the server has sent nothing for a minute. HTTPS traffic is tunnelled via
+//! `CONNECT` without being decrypted, so only the host and port are
+//! recorded for those.
+//!
+//! ```no_run
+//! # #[cfg(feature = "chrome")]
+//! # fn example() -> Result<(), failure::Error> {
+//! use sulfur::chrome;
+//! use sulfur::proxy::RecordingProxy;
+//!
+//! let proxy = RecordingProxy::start()?;
+//! let driver = chrome::start(chrome::Config::default().proxy(proxy.address()))?;
+//! driver.visit("http://localhost:8000/")?;
+//! assert!(proxy.exchanges().iter().any(|ex| ex.url.ends_with("/api/items")));
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use failure::{Error, ResultExt};
+
+use crate::har;
+use crate::http1;
+
+// Headers that only apply to a single connection, so are not forwarded.
+// Bodies are re-framed where needed; see `write_headers`.
+const HOP_BY_HOP: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-authorization",
+    "proxy-connection",
+    "te",
+    "transfer-encoding",
+    "upgrade",
+];
+// How long to wait for the browser or server to send anything, eg: before
+// cutting off an idle event stream.
+const READ_TIMEOUT: Duration = Duration::from_secs(60);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A proxy listening on the loopback interface, which records each request
+/// made through it. Stops listening when dropped.
+#[derive(Debug)]
+pub struct RecordingProxy {
+    addr: SocketAddr,
+    exchanges: Arc<Mutex<Vec<Exchange>>>,
+    stopping: Arc<AtomicBool>,
+    acceptor: Option<thread::JoinHandle<()>>,
+}
+
+/// A request made through a [`RecordingProxy`], and its response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exchange {
+    /// The request method, eg: `GET`, or `CONNECT` for a tunnel.
+    pub method: String,
+    /// The requested URL, or for `CONNECT` requests, the `host:port`.
+    pub url: String,
+    /// The request headers, in the order they were sent.
+    pub request_headers: Vec<(String, String)>,
+    /// The request body.
+    pub request_body: Vec<u8>,
+    /// The response status, eg: 502 if the server could not be reached.
+    pub status: u16,
+    /// The response headers, in the order they were received.
+    pub response_headers: Vec<(String, String)>,
+    /// The response body, with any transfer encoding removed.
+    pub response_body: Vec<u8>,
+    /// When the request was received.
+    pub started: SystemTime,
+    /// How long the response took to arrive in full.
+    pub duration: Duration,
+}
+
+impl RecordingProxy {
+    /// Start listening on an ephemeral port.
+    pub fn start() -> Result<Self, Error> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let exchanges = Arc::new(Mutex::new(Vec::new()));
+        let stopping = Arc::new(AtomicBool::new(false));
+
+        let acceptor = {
+            let exchanges = exchanges.clone();
+            let stopping = stopping.clone();
+            thread::Builder::new()
+                .name(format!("RecordingProxy {}", addr))
+                .spawn(move || accept(listener, exchanges, stopping))?
+        };
+        debug!("Recording proxy listening on {}", addr);
+
+        Ok(RecordingProxy {
+            addr,
+            exchanges,
+            stopping,
+            acceptor: Some(acceptor),
+        })
+    }
+
+    /// The `host:port` that the proxy listens on, eg: for
+    /// [`chrome::Config::proxy`](crate::chrome::Config::proxy).
+    pub fn address(&self) -> String {
+        self.addr.to_string()
+    }
+
+    /// A W3C `proxy` capability that directs both HTTP and HTTPS traffic
+    /// through this proxy, eg: for use with
+    /// [`Capabilities::capability`](crate::Capabilities::capability).
+    pub fn capability(&self) -> serde_json::Value {
+        json!({
+            "proxyType": "manual",
+            "httpProxy": self.address(),
+            "sslProxy": self.address(),
+        })
+    }
+
+    /// The exchanges recorded so far, oldest first.
+    pub fn exchanges(&self) -> Vec<Exchange> {
+        self.exchanges.lock().expect("unpoison").clone()
+    }
+
+    /// A HAR 1.2 document of the exchanges recorded so far, eg: for opening
+    /// in the browser's devtools. `CONNECT` tunnels are left out, as nothing
+    /// is known about the requests made through them.
+    pub fn har(&self) -> serde_json::Value {
+        let entries = self
+            .exchanges()
+            .iter()
+            .filter(|ex| ex.method != "CONNECT")
+            .map(Exchange::to_har_entry)
+            .collect::<Vec<_>>();
+        har::document(&entries)
+    }
+
+    /// Forget the exchanges recorded so far.
+    pub fn clear(&self) {
+        self.exchanges.lock().expect("unpoison").clear()
+    }
+}
+
+impl Drop for RecordingProxy {
+    fn drop(&mut self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        // Wake the acceptor up, so it notices that we are stopping.
+        let _ = TcpStream::connect(self.addr);
+        if let Some(acceptor) = self.acceptor.take() {
+            let _ = acceptor.join();
+        }
+    }
+}
+
+impl Exchange {
+    /// The value of the first response header called `name`, ignoring
+    /// case.
+    pub fn response_header(&self, name: &str) -> Option<&str> {
+        self.response_headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn to_har_entry(&self) -> har::Entry {
+        let body = |body: &[u8]| String::from_utf8_lossy(body).into_owned();
+        har::Entry {
+            started: Some(self.started),
+            duration: self.duration,
+            method: self.method.clone(),
+            url: self.url.clone(),
+            http_version: "HTTP/1.1".into(),
+            request_headers: self.request_headers.clone(),
+            request_body: Some(&self.request_body)
+                .filter(|b| !b.is_empty())
+                .map(|b| body(b)),
+            status: self.status,
+            status_text: String::new(),
+            response_headers: self.response_headers.clone(),
+            mime_type: self
+                .response_header("content-type")
+                .unwrap_or_default()
+                .to_string(),
+            body_size: self.response_body.len() as i64,
+            text: Some(body(&self.response_body)),
+        }
+    }
+}
+
+fn accept(listener: TcpListener, exchanges: Arc<Mutex<Vec<Exchange>>>, stopping: Arc<AtomicBool>) {
+    for conn in listener.incoming() {
+        if stopping.load(Ordering::SeqCst) {
+            return;
+        }
+        let conn = match conn {
+            Ok(conn) => conn,
+            Err(e) => {
+                warn!("Accepting proxy connection: {}", e);
+                continue;
+            }
+        };
+        let exchanges = exchanges.clone();
+        thread::spawn(move || {
+            if let Err(e) = handle(conn, &exchanges) {
+                warn!("Proxying request: {}", e);
+            }
+        });
+    }
+}
+
+// Handles a single request, and then closes the connection.
+fn handle(mut conn: TcpStream, exchanges: &Mutex<Vec<Exchange>>) -> Result<(), Error> {
+    let started = SystemTime::now();
+    let timer = Instant::now();
+    conn.set_read_timeout(Some(READ_TIMEOUT))?;
+    let (head, body) = read_head(&mut conn)?;
+    let head = http1::parse_head(&head)?;
+    let mut parts = head.start_line.split(' ');
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method.to_string(), target.to_string()),
+        _ => bail!("Bad request line: {:?}", head.start_line),
+    };
+
+    let mut exchange = Exchange {
+        method,
+        url: target,
+        request_headers: head.headers.clone(),
+        request_body: Vec::new(),
+        status: 0,
+        response_headers: Vec::new(),
+        response_body: Vec::new(),
+        started,
+        duration: Duration::default(),
+    };
+
+    if exchange.method == "CONNECT" {
+        return tunnel(conn, body, exchange, exchanges);
+    }
+
+    exchange.request_body = read_body(&mut conn, &head, body)?;
+
+    let (mut upstream, resp_head, early) = match forward(&exchange) {
+        Ok(response) => response,
+        Err(e) => {
+            warn!("Forwarding {} {}: {}", exchange.method, exchange.url, e);
+            let message = e.to_string();
+            exchange.status = 502;
+            exchange.duration = timer.elapsed();
+            exchanges.lock().expect("unpoison").push(exchange);
+            write!(
+                conn,
+                "HTTP/1.1 502 Bad Gateway\r\ncontent-type: text/plain\r\ncontent-length: {}\r\n\r\n{}",
+                message.len(),
+                message
+            )?;
+            conn.shutdown(Shutdown::Both)?;
+            return Ok(());
+        }
+    };
+    exchange.status = resp_head
+        .start_line
+        .split(' ')
+        .nth(1)
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| format_err!("Bad HTTP status line: {:?}", resp_head.start_line))?;
+    exchange.response_headers = resp_head.headers.clone();
+    let framing = if exchange.method == "HEAD" || [204, 304].contains(&exchange.status) {
+        Framing::Empty
+    } else if resp_head.is_chunked() {
+        Framing::Chunked
+    } else {
+        match resp_head.content_length()? {
+            Some(len) => Framing::Length(len),
+            None => Framing::Close,
+        }
+    };
+
+    let mut out = format!("{}\r\n", resp_head.start_line).into_bytes();
+    let chunked = Some(("Transfer-Encoding", "chunked".to_string()));
+    write_headers(
+        &mut out,
+        &resp_head.headers,
+        chunked.filter(|_| framing == Framing::Chunked),
+    );
+    conn.write_all(&out)?;
+
+    // Relay the body as it arrives, eg: for server-sent events, until it is
+    // complete. The exchange is recorded before the browser sees the end of
+    // the response, so that it is visible as soon as eg: a page load
+    // completes.
+    let mut raw = early;
+    let mut written = 0;
+    let mut buf = [0; 8192];
+    let relayed = loop {
+        if let Some(len) = framing.body_len(&raw)? {
+            raw.truncate(len);
+            break Ok(());
+        }
+        conn.write_all(&raw[written..])?;
+        written = raw.len();
+        match upstream.read(&mut buf) {
+            Ok(0) if framing == Framing::Close => break Ok(()),
+            Ok(0) => break Err(format_err!("Truncated response from {}", exchange.url)),
+            Ok(n) => raw.extend_from_slice(&buf[..n]),
+            Err(e) => break Err(Error::from(e)),
+        }
+    };
+    exchange.response_body = match framing {
+        Framing::Chunked if relayed.is_ok() => http1::dechunk(&raw)?,
+        _ => raw.clone(),
+    };
+    exchange.duration = timer.elapsed();
+    exchanges.lock().expect("unpoison").push(exchange);
+
+    conn.write_all(&raw[written..])?;
+    conn.shutdown(Shutdown::Both)?;
+    relayed
+}
+
+// How the end of a response body is marked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Framing {
+    Empty,
+    Length(usize),
+    Chunked,
+    // The body ends when the server closes the connection.
+    Close,
+}
+
+impl Framing {
+    // The length of the body at the start of `raw`, if it is complete.
+    fn body_len(self, raw: &[u8]) -> Result<Option<usize>, Error> {
+        match self {
+            Framing::Empty => Ok(Some(0)),
+            Framing::Length(len) => Ok(Some(len).filter(|&len| raw.len() >= len)),
+            Framing::Chunked => http1::chunked_len(raw),
+            Framing::Close => Ok(None),
+        }
+    }
+}
+
+// Reads the rest of the request body, decoding it if it is chunked.
+fn read_body(
+    conn: &mut TcpStream,
+    head: &http1::Head,
+    mut body: Vec<u8>,
+) -> Result<Vec<u8>, Error> {
+    let complete = |body: &[u8]| -> Result<Option<usize>, Error> {
+        if head.is_chunked() {
+            return http1::chunked_len(body);
+        }
+        Ok(match head.content_length()? {
+            Some(len) => Some(len).filter(|&len| body.len() >= len),
+            None => Some(body.len()),
+        })
+    };
+    let len = loop {
+        if let Some(len) = complete(&body)? {
+            break len;
+        }
+        let mut buf = [0; 8192];
+        let n = conn.read(&mut buf)?;
+        if n == 0 {
+            bail!("Truncated request body");
+        }
+        body.extend_from_slice(&buf[..n]);
+    };
+    body.truncate(len);
+    if head.is_chunked() {
+        body = http1::dechunk(&body)?;
+    }
+    Ok(body)
+}
+
+// Sends the request on to the origin server, and reads the head of the
+// response, returning the connection, the head, and any of the body read so
+// far.
+fn forward(exchange: &Exchange) -> Result<(TcpStream, http1::Head, Vec<u8>), Error> {
+    let url = url::Url::parse(&exchange.url)
+        .with_context(|_| format!("Parsing proxied URL {:?}", exchange.url))?;
+    if url.scheme() != "http" {
+        bail!("Can only proxy http URLs, not {:?}", url.scheme());
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| format_err!("No host in {:?}", exchange.url))?;
+    let port = url.port_or_known_default().unwrap_or(80);
+    let mut upstream = connect(host, port)?;
+    upstream.set_read_timeout(Some(READ_TIMEOUT))?;
+    upstream.set_write_timeout(Some(READ_TIMEOUT))?;
+
+    let mut target = url.path().to_string();
+    if let Some(query) = url.query() {
+        target.push('?');
+        target.push_str(query);
+    }
+    let has_body = exchange.request_headers.iter().any(|(name, _)| {
+        name.eq_ignore_ascii_case("content-length")
+            || name.eq_ignore_ascii_case("transfer-encoding")
+    });
+    let length = Some(("Content-Length", exchange.request_body.len().to_string()));
+    let mut out = format!("{} {} HTTP/1.1\r\n", exchange.method, target).into_bytes();
+    write_headers(
+        &mut out,
+        &exchange.request_headers,
+        length.filter(|_| has_body),
+    );
+    out.extend_from_slice(&exchange.request_body);
+    upstream.write_all(&out)?;
+
+    let (head, early) = read_head(&mut upstream)?;
+    let head = http1::parse_head(&head)?;
+    Ok((upstream, head, early))
+}
+
+fn connect(host: &str, port: u16) -> Result<TcpStream, Error> {
+    let mut last_error = None;
+    for addr in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    let e = last_error.unwrap_or_else(|| io::ErrorKind::NotFound.into());
+    Err(Error::from(e)
+        .context(format!("Connecting to {}:{}", host, port))
+        .into())
+}
+
+// Relays bytes between the browser and the server, without inspecting them.
+fn tunnel(
+    mut conn: TcpStream,
+    early: Vec<u8>,
+    mut exchange: Exchange,
+    exchanges: &Mutex<Vec<Exchange>>,
+) -> Result<(), Error> {
+    // Tunnels may sit idle for a long time, eg: for a websocket.
+    conn.set_read_timeout(None)?;
+    let mut upstream = match TcpStream::connect(&exchange.url) {
+        Ok(upstream) => upstream,
+        Err(e) => {
+            exchange.status = 502;
+            exchanges.lock().expect("unpoison").push(exchange);
+            conn.write_all(b"HTTP/1.1 502 Bad Gateway\r\ncontent-length: 0\r\n\r\n")?;
+            return Err(e.into());
+        }
+    };
+    exchange.status = 200;
+    exchanges.lock().expect("unpoison").push(exchange);
+    conn.write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")?;
+    upstream.write_all(&early)?;
+
+    let mut from_browser = conn.try_clone()?;
+    let mut to_server = upstream.try_clone()?;
+    let outbound = thread::spawn(move || {
+        let _ = io::copy(&mut from_browser, &mut to_server);
+        let _ = to_server.shutdown(Shutdown::Write);
+    });
+    let _ = io::copy(&mut upstream, &mut conn);
+    let _ = conn.shutdown(Shutdown::Write);
+    let _ = outbound.join();
+    Ok(())
+}
+
+// Reads up to the end of the head, returning it, and any body read so far.
+fn read_head(conn: &mut TcpStream) -> Result<(Vec<u8>, Vec<u8>), Error> {
+    let mut raw = Vec::new();
+    let mut buf = [0; 8192];
+    loop {
+        if let Some((head, rest)) = http1::split_head(&raw) {
+            return Ok((head.to_vec(), rest.to_vec()));
+        }
+        let n = conn.read(&mut buf)?;
+        if n == 0 {
+            bail!("Connection closed before the end of the request head");
+        }
+        raw.extend_from_slice(&buf[..n]);
+    }
+}
+
+// Writes `headers`, minus hop-by-hop headers, then `framing` (the header
+// that delimits the body, replacing any of the same name), and asks for the
+// connection to be closed after this exchange.
+fn write_headers(out: &mut Vec<u8>, headers: &[(String, String)], framing: Option<(&str, String)>) {
+    for (name, value) in headers {
+        let replaced = framing
+            .as_ref()
+            .is_some_and(|(framed, _)| name.eq_ignore_ascii_case(framed));
+        if replaced || HOP_BY_HOP.iter().any(|h| name.eq_ignore_ascii_case(h)) {
+            continue;
+        }
+        out.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+    }
+    if let Some((name, value)) = framing {
+        out.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+    }
+    out.extend_from_slice(b"Connection: close\r\n\r\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A server that answers a single request with `response`, and returns
+    // the request it received.
+    fn origin(response: &'static [u8]) -> (SocketAddr, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let addr = listener.local_addr().expect("local_addr");
+        let server = thread::spawn(move || {
+            let (mut conn, _) = listener.accept().expect("accept");
+            let (head, _) = read_head(&mut conn).expect("read_head");
+            conn.write_all(response).expect("write");
+            String::from_utf8(head).expect("utf8")
+        });
+        (addr, server)
+    }
+
+    #[test]
+    fn records_proxied_requests() {
+        let (addr, server) = origin(
+            b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+              Transfer-Encoding: chunked\r\n\r\n2\r\n[]\r\n0\r\n\r\n",
+        );
+        let proxy = RecordingProxy::start().expect("start");
+        let client = reqwest::Client::builder()
+            .proxy(reqwest::Proxy::http(&format!("http://{}", proxy.address())).expect("proxy"))
+            .build()
+            .expect("client");
+
+        let url = format!("http://{}/api/items?q=1", addr);
+        let mut resp = client.get(&url).send().expect("get");
+        assert_eq!(resp.status(), reqwest::StatusCode::OK);
+        assert_eq!(resp.text().expect("text"), "[]");
+
+        let request = server.join().expect("join");
+        assert!(
+            request.starts_with("GET /api/items?q=1 HTTP/1.1\r\n"),
+            "{}",
+            request
+        );
+        let exchanges = proxy.exchanges();
+        assert_eq!(exchanges.len(), 1);
+        assert_eq!(exchanges[0].method, "GET");
+        assert_eq!(exchanges[0].url, url);
+        assert_eq!(exchanges[0].status, 200);
+        assert_eq!(
+            exchanges[0].response_header("content-type"),
+            Some("application/json")
+        );
+        assert_eq!(exchanges[0].response_body, b"[]");
+
+        let har = proxy.har();
+        assert_eq!(har["log"]["entries"][0]["request"]["url"], url);
+        assert_eq!(
+            har["log"]["entries"][0]["response"]["content"]["text"],
+            "[]"
+        );
+
+        proxy.clear();
+        assert!(proxy.exchanges().is_empty());
+    }
+
+    // Sends a request via `proxy` on a fresh connection.
+    fn send_via(proxy: &RecordingProxy, request: &str) -> TcpStream {
+        let mut conn = TcpStream::connect(proxy.address()).expect("connect");
+        conn.write_all(request.as_bytes()).expect("write");
+        conn
+    }
+
+    #[test]
+    fn streams_responses_as_they_arrive() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let addr = listener.local_addr().expect("local_addr");
+        let (next, wait_for_next) = std::sync::mpsc::channel();
+        let server = thread::spawn(move || {
+            let (mut conn, _) = listener.accept().expect("accept");
+            read_head(&mut conn).expect("read_head");
+            conn.write_all(
+                b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\r\ndata: 1\n\n",
+            )
+            .expect("write");
+            wait_for_next.recv().expect("recv");
+            conn.write_all(b"data: 2\n\n").expect("write");
+        });
+        let proxy = RecordingProxy::start().expect("start");
+
+        let mut conn = send_via(
+            &proxy,
+            &format!(
+                "GET http://{}/events HTTP/1.1\r\nHost: {}\r\n\r\n",
+                addr, addr
+            ),
+        );
+        let mut received = Vec::new();
+        while http1::find(&received, b"data: 1").is_none() {
+            let mut buf = [0; 1024];
+            let n = conn.read(&mut buf).expect("read");
+            assert_ne!(n, 0, "{:?}", String::from_utf8_lossy(&received));
+            received.extend_from_slice(&buf[..n]);
+        }
+        assert!(proxy.exchanges().is_empty());
+
+        next.send(()).expect("send");
+        conn.read_to_end(&mut received).expect("read");
+        server.join().expect("join");
+        assert!(received.ends_with(b"data: 1\n\ndata: 2\n\n"));
+        assert_eq!(
+            proxy.exchanges()[0].response_body,
+            b"data: 1\n\ndata: 2\n\n"
+        );
+    }
+
+    #[test]
+    fn decodes_chunked_request_bodies() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let addr = listener.local_addr().expect("local_addr");
+        let server = thread::spawn(move || {
+            let (mut conn, _) = listener.accept().expect("accept");
+            let (head, mut body) = read_head(&mut conn).expect("read_head");
+            while body.len() < 4 {
+                let mut buf = [0; 1024];
+                let n = conn.read(&mut buf).expect("read");
+                body.extend_from_slice(&buf[..n]);
+            }
+            conn.write_all(b"HTTP/1.1 204 No Content\r\n\r\n")
+                .expect("write");
+            (String::from_utf8(head).expect("utf8"), body)
+        });
+        let proxy = RecordingProxy::start().expect("start");
+
+        let mut conn = send_via(
+            &proxy,
+            &format!(
+                "POST http://{}/api HTTP/1.1\r\nHost: {}\r\nTransfer-Encoding: chunked\r\n\
+                 TE: trailers\r\nProxy-Authorization: Basic c2VjcmV0\r\n\r\n\
+                 2\r\npi\r\n2\r\nng\r\n0\r\n\r\n",
+                addr, addr
+            ),
+        );
+        let mut reply = Vec::new();
+        conn.read_to_end(&mut reply).expect("read");
+        assert!(reply.starts_with(b"HTTP/1.1 204 No Content\r\n"));
+
+        let (head, body) = server.join().expect("join");
+        assert_eq!(body, b"ping");
+        let head = http1::parse_head(head.as_bytes()).expect("parse");
+        assert_eq!(head.header("content-length"), Some("4"));
+        for hop_by_hop in &["transfer-encoding", "te", "proxy-authorization"] {
+            assert_eq!(head.header(hop_by_hop), None, "{}", hop_by_hop);
+        }
+        assert_eq!(proxy.exchanges()[0].request_body, b"ping");
+    }
+
+    #[test]
+    fn reports_unreachable_servers() {
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .and_then(|l| l.local_addr())
+            .expect("addr");
+        let proxy = RecordingProxy::start().expect("start");
+        let client = reqwest::Client::builder()
+            .proxy(reqwest::Proxy::http(&format!("http://{}", proxy.address())).expect("proxy"))
+            .build()
+            .expect("client");
+
+        let resp = client
+            .get(&format!("http://{}/", addr))
+            .send()
+            .expect("get");
+        assert_eq!(resp.status(), reqwest::StatusCode::BAD_GATEWAY);
+        assert_eq!(proxy.exchanges()[0].status, 502);
+    }
+
+    #[test]
+    fn tunnels_connect_requests() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let addr = listener.local_addr().expect("local_addr");
+        let echo = thread::spawn(move || {
+            let (mut conn, _) = listener.accept().expect("accept");
+            let mut buf = [0; 4];
+            conn.read_exact(&mut buf).expect("read");
+            conn.write_all(&buf).expect("write");
+        });
+        let proxy = RecordingProxy::start().expect("start");
+
+        let mut conn = TcpStream::connect(proxy.address()).expect("connect");
+        write!(
+            conn,
+            "CONNECT {} HTTP/1.1\r\nHost: {}\r\n\r\nping",
+            addr, addr
+        )
+        .expect("write");
+        let mut reply = Vec::new();
+        conn.read_to_end(&mut reply).expect("read");
+        assert_eq!(
+            String::from_utf8(reply).expect("utf8"),
+            "HTTP/1.1 200 Connection Established\r\n\r\nping"
+        );
+        echo.join().expect("join");
+
+        let exchanges = proxy.exchanges();
+        assert_eq!(exchanges[0].method, "CONNECT");
+        assert_eq!(exchanges[0].url, addr.to_string());
+        assert_eq!(exchanges[0].status, 200);
+    }
+}
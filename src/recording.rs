@@ -0,0 +1,271 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time;
+
+use failure::Error;
+use failure::ResultExt;
+
+use crate::client::{Client, DomSnapshot};
+use crate::command::{Command, CommandOutcome};
+
+// Commands that must not trigger a frame, as taking one would recurse.
+const SCREENSHOT_COMMANDS: &[&str] = &["screenshot", "element_screenshot"];
+// Commands after which there is no session to record a frame or snapshot
+// from.
+const SESSION_ENDING_COMMANDS: &[&str] = &["close"];
+// Only the most recent DOM snapshots are kept, as each holds a whole page.
+const MAX_SNAPSHOTS: usize = 100;
+
+/// Records the viewport after each command as a directory of numbered PNG
+/// frames (`00000.png`, `00001.png`, ...), for replaying what the browser did
+/// during a failed test.
+///
+/// ```no_run
+/// # fn example(client: &mut sulfur::Client) -> Result<(), failure::Error> {
+/// use std::time::Duration;
+///
+/// sulfur::ScreenRecorder::new("target/frames")
+///     .min_interval(Duration::from_millis(250))
+///     .attach(client)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ScreenRecorder {
+    dir: PathBuf,
+    min_interval: time::Duration,
+    state: Arc<Mutex<State>>,
+}
+
+/// Records the document after each command (see [`Client::snapshot_dom`]),
+/// giving a step by step view of how the page changed during a failed test.
+///
+/// Snapshots of the most recent commands are kept in memory, and only saved
+/// along with the client's other failure artifacts (see
+/// [`Client::set_artifact_sink`] and [`Client::capture_artifacts`]), into a
+/// `dom` directory, as numbered HTML files (`00000-visit.html`,
+/// `00001-click.html`, ...) with a `timeline.jsonl` index.
+///
+/// ```no_run
+/// # fn example(client: &mut sulfur::Client) {
+/// client.set_artifact_sink(&sulfur::ArtifactSink::from_env());
+/// sulfur::DomRecorder::new().attach(client);
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DomRecorder {
+    timeline: Arc<Mutex<Timeline>>,
+}
+
+#[derive(Debug, Default)]
+struct Timeline {
+    next: usize,
+    // The step number, and command, of each snapshot, oldest first.
+    snapshots: VecDeque<(usize, &'static str, DomSnapshot)>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    next_frame: usize,
+    last_frame_at: Option<time::Instant>,
+}
+
+impl ScreenRecorder {
+    /// A recorder that writes frames into `dir`.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        ScreenRecorder {
+            dir: dir.into(),
+            min_interval: time::Duration::default(),
+            state: Arc::default(),
+        }
+    }
+
+    /// Skip frames for commands that complete within `interval` of the
+    /// previous frame. By default, every command produces a frame.
+    pub fn min_interval(&mut self, interval: time::Duration) -> &mut Self {
+        self.min_interval = interval;
+        self
+    }
+
+    /// The directory frames are written to.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Start recording commands sent via `client`. Creates the output
+    /// directory if needed.
+    pub fn attach(&self, client: &mut Client) -> Result<(), Error> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|_| format!("Creating frame directory {:?}", self.dir))?;
+        let recorder = self.clone();
+        client.on_command(
+            |_: &Client, _: &Command| (),
+            move |client: &Client, cmd: &Command, outcome: &CommandOutcome| {
+                if outcome.result.is_err()
+                    || SCREENSHOT_COMMANDS.contains(&cmd.name)
+                    || SESSION_ENDING_COMMANDS.contains(&cmd.name)
+                {
+                    return;
+                }
+                if let Err(e) = recorder.capture(client) {
+                    warn!("Failed to record frame after {}: {}", cmd.name, e);
+                }
+            },
+        );
+        Ok(())
+    }
+
+    /// The frames recorded so far, in order.
+    pub fn frames(&self) -> Result<Vec<PathBuf>, Error> {
+        let mut frames = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().map(|ext| ext == "png").unwrap_or(false) {
+                frames.push(path);
+            }
+        }
+        frames.sort();
+        Ok(frames)
+    }
+
+    fn capture(&self, client: &Client) -> Result<(), Error> {
+        let mut state = self.state.lock().expect("unpoison");
+        let now = time::Instant::now();
+        if let Some(last) = state.last_frame_at {
+            if now.duration_since(last) < self.min_interval {
+                return Ok(());
+            }
+        }
+
+        let path = self.dir.join(format!("{:05}.png", state.next_frame));
+        client.screenshot_to(&path)?;
+        state.next_frame += 1;
+        state.last_frame_at = Some(now);
+        Ok(())
+    }
+}
+
+impl DomRecorder {
+    /// A recorder with no snapshots.
+    pub fn new() -> Self {
+        DomRecorder::default()
+    }
+
+    /// Start recording commands sent via `client`. The snapshots are taken
+    /// directly, so are not seen by hooks, or the command log.
+    pub fn attach(&self, client: &mut Client) {
+        let recorder = self.clone();
+        client.on_command(
+            |_: &Client, _: &Command| (),
+            move |client: &Client, cmd: &Command, outcome: &CommandOutcome| {
+                if outcome.result.is_err() || SESSION_ENDING_COMMANDS.contains(&cmd.name) {
+                    return;
+                }
+                match client.snapshot_dom_internal() {
+                    Ok(snapshot) => recorder.push(cmd.name, snapshot),
+                    Err(e) => warn!("Failed to snapshot DOM after {}: {}", cmd.name, e),
+                }
+            },
+        );
+        let recorder = self.clone();
+        client.on_capture_artifacts("dom", move |_, dir| recorder.save(dir.join("dom")));
+    }
+
+    /// The snapshots held, oldest first, along with the command each was
+    /// taken after.
+    pub fn snapshots(&self) -> Vec<(&'static str, DomSnapshot)> {
+        let timeline = self.timeline.lock().expect("unpoison");
+        timeline
+            .snapshots
+            .iter()
+            .map(|(_, command, snapshot)| (*command, snapshot.clone()))
+            .collect()
+    }
+
+    /// Writes the snapshots held into `dir`, which is created if needed.
+    pub fn save<P: AsRef<Path>>(&self, dir: P) -> Result<(), Error> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)
+            .with_context(|_| format!("Creating snapshot directory {:?}", dir))?;
+        let mut timeline = fs::File::create(dir.join("timeline.jsonl"))?;
+        for (step, command, snapshot) in self.timeline.lock().expect("unpoison").snapshots.iter() {
+            let name = format!("{:05}-{}.html", step, command);
+            fs::write(dir.join(&name), &snapshot.html)?;
+            let entry = json!({
+                "snapshot": name,
+                "command": command,
+                "url": snapshot.url,
+                "title": snapshot.title,
+            });
+            writeln!(timeline, "{}", entry)?;
+        }
+        Ok(())
+    }
+
+    fn push(&self, command: &'static str, snapshot: DomSnapshot) {
+        let mut timeline = self.timeline.lock().expect("unpoison");
+        if timeline.snapshots.len() >= MAX_SNAPSHOTS {
+            timeline.snapshots.pop_front();
+        }
+        let step = timeline.next;
+        timeline.snapshots.push_back((step, command, snapshot));
+        timeline.next += 1;
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::*;
+    use crate::testing::MockWebDriver;
+
+    #[test]
+    fn dom_snapshots_are_only_saved_with_failure_artifacts() {
+        let mock = MockWebDriver::start().expect("start");
+        mock.set_title("Home");
+        let mut client = mock.client().expect("client");
+        let recorder = DomRecorder::new();
+        recorder.attach(&mut client);
+
+        client.title().expect("title");
+        // The mock cannot run scripts, so no snapshot is taken.
+        assert!(recorder.snapshots().is_empty());
+        assert_eq!(
+            mock.requests()
+                .iter()
+                .filter(|r| r.path == "execute/sync")
+                .count(),
+            1
+        );
+        let commands = client
+            .command_log()
+            .entries()
+            .iter()
+            .map(|e| e.command.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(commands, vec!["title"]);
+
+        recorder.push(
+            "visit",
+            DomSnapshot {
+                url: "http://example.com/".into(),
+                title: "Home".into(),
+                html: "<html></html>".into(),
+            },
+        );
+        let dir = tempfile::tempdir().expect("tempdir");
+        client
+            .capture_artifacts(dir.path())
+            .expect_err("mock lacks screenshots");
+        let dom = dir.path().join("dom");
+        assert_eq!(
+            fs::read_to_string(dom.join("00000-visit.html")).expect("snapshot"),
+            "<html></html>"
+        );
+        assert!(fs::read_to_string(dom.join("timeline.jsonl"))
+            .expect("timeline")
+            .contains("\"command\":\"visit\""));
+    }
+}
@@ -0,0 +1,96 @@
+/// A policy for replacing a session that has gone away, eg: because the
+/// browser crashed, or a remote grid reaped it. See
+/// [`Client::set_session_recovery`](crate::Client::set_session_recovery).
+///
+/// ```no_run
+/// # fn example(mut client: sulfur::Client) {
+/// use sulfur::SessionRecovery;
+///
+/// client.set_session_recovery(Some(SessionRecovery::new().restore_url(true)));
+/// client.on_session_recovered(|_, event| {
+///     eprintln!("Session {} replaced", event.previous_session_id)
+/// });
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SessionRecovery {
+    restore_url: bool,
+}
+
+/// Describes a session that was replaced, as passed to
+/// [`Client::on_session_recovered`](crate::Client::on_session_recovered)
+/// hooks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionRecovered {
+    /// The session that went away.
+    pub previous_session_id: String,
+    /// The replacement session.
+    pub session_id: String,
+    /// The URL that the replacement session was navigated to, if any.
+    pub restored_url: Option<String>,
+}
+
+impl SessionRecovery {
+    /// A policy that starts a fresh session, with the capabilities the
+    /// original was started with.
+    pub fn new() -> Self {
+        SessionRecovery::default()
+    }
+
+    /// Navigate the replacement session to the last URL visited with
+    /// [`Client::visit`](crate::Client::visit). Cookies and other browser
+    /// state are not restored.
+    pub fn restore_url(mut self, restore_url: bool) -> Self {
+        self.restore_url = restore_url;
+        self
+    }
+
+    pub(crate) fn restores_url(&self) -> bool {
+        self.restore_url
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::client::{ErrorKind, WdError};
+    use crate::testing::MockWebDriver;
+
+    #[test]
+    fn replaces_sessions_that_go_away() {
+        let mock = MockWebDriver::start().expect("start");
+        mock.set_title("Home");
+        let mut client = mock.client().expect("client");
+        client.visit("http://example.com/").expect("visit");
+
+        mock.crash();
+        let err = client.title().expect_err("session has gone away");
+        assert_eq!(
+            err.downcast_ref::<WdError>().map(WdError::kind),
+            Some(ErrorKind::InvalidSessionId)
+        );
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let seen = events.clone();
+        client.set_session_recovery(Some(SessionRecovery::new().restore_url(true)));
+        client.on_session_recovered(move |_, event| {
+            seen.lock().expect("unpoison").push(event.clone())
+        });
+
+        assert_eq!(client.title().expect("title"), "Home");
+        assert_eq!(
+            *events.lock().expect("unpoison"),
+            vec![SessionRecovered {
+                previous_session_id: "mock-session".into(),
+                session_id: "mock-session-2".into(),
+                restored_url: Some("http://example.com/".into()),
+            }]
+        );
+        assert_eq!(
+            client.current_url().expect("current_url"),
+            "http://example.com/"
+        );
+    }
+}
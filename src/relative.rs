@@ -0,0 +1,75 @@
+use failure::Error;
+
+use crate::client::{Client, Element};
+
+// How close elements must be to count as "near", by default. This matches
+// Selenium's relative locators.
+pub(crate) const DEFAULT_NEAR_DISTANCE: u32 = 50;
+
+// A constraint on an element's position relative to an anchor element.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub(crate) enum Relation {
+    Above { element: Element },
+    Below { element: Element },
+    LeftOf { element: Element },
+    RightOf { element: Element },
+    Near { element: Element, distance: u32 },
+}
+
+// Defines `filterByRelations(candidates, relations)`, which filters
+// candidates by the layout boxes of the elements, sorted by how far they are
+// from the first anchor.
+pub(crate) const FILTER_BY_RELATIONS: &str = r#"
+function filterByRelations(candidates, relations) {
+    if (relations.length === 0) {
+        return candidates;
+    }
+    var found = candidates.filter(function (elt) {
+        return relations.every(function (rel) { return matches(elt, rel); });
+    });
+    var anchor = relations[0].element.getBoundingClientRect();
+    found.sort(function (x, y) {
+        return gap(x.getBoundingClientRect(), anchor) - gap(y.getBoundingClientRect(), anchor);
+    });
+    return found;
+}
+function gap(r, a) {
+    var dx = Math.max(a.left - r.right, r.left - a.right, 0);
+    var dy = Math.max(a.top - r.bottom, r.top - a.bottom, 0);
+    return Math.sqrt(dx * dx + dy * dy);
+}
+function matches(elt, rel) {
+    if (elt === rel.element) {
+        return false;
+    }
+    var r = elt.getBoundingClientRect(), a = rel.element.getBoundingClientRect();
+    switch (rel.kind) {
+        case "above": return r.bottom <= a.top;
+        case "below": return r.top >= a.bottom;
+        case "leftOf": return r.right <= a.left;
+        case "rightOf": return r.left >= a.right;
+        case "near": return gap(r, a) <= rel.distance;
+    }
+    throw new Error("Unknown relation: " + rel.kind);
+}
+"#;
+
+pub(crate) fn filter(
+    client: &Client,
+    candidates: Vec<Element>,
+    relations: &[Relation],
+) -> Result<Vec<Element>, Error> {
+    if relations.is_empty() || candidates.is_empty() {
+        return Ok(candidates);
+    }
+    let args = [
+        serde_json::to_value(&candidates)?,
+        serde_json::to_value(relations)?,
+    ];
+    let script = format!(
+        "{}return filterByRelations(arguments[0], arguments[1]);",
+        FILTER_BY_RELATIONS
+    );
+    client.execute_script(&script, &args)
+}
@@ -0,0 +1,247 @@
+//! Helpers for running sessions on hosted browser farms, such as Sauce Labs,
+//! BrowserStack or LambdaTest.
+//!
+//! ```no_run
+//! # fn example() -> Result<(), failure::Error> {
+//! use sulfur::remote::{CloudOptions, Provider};
+//! use sulfur::{Capabilities, Client};
+//!
+//! let options = CloudOptions::from_env(Provider::SauceLabs)?.build("nightly-42");
+//! let caps = options.apply(Capabilities::new().browser_name("chrome"));
+//! let client = Client::builder()
+//!     .url(Provider::SauceLabs.hub_url())
+//!     .capabilities(caps)
+//!     .build()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::env;
+use std::fmt;
+
+use failure::Error;
+
+use crate::client::Capabilities;
+
+/// A hosted browser provider.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Provider {
+    /// Sauce Labs, configured via `sauce:options`.
+    SauceLabs,
+    /// BrowserStack, configured via `bstack:options`.
+    BrowserStack,
+    /// LambdaTest, configured via `LT:Options`.
+    LambdaTest,
+}
+
+/// Vendor specific options for a [`Provider`], eg: credentials and the name
+/// of the build to report results under.
+#[derive(Clone, PartialEq, Eq)]
+pub struct CloudOptions {
+    provider: Provider,
+    username: Option<String>,
+    access_key: Option<String>,
+    build: Option<String>,
+    name: Option<String>,
+    tunnel: Option<String>,
+}
+
+impl Provider {
+    /// The capability the provider reads its options from.
+    pub fn capability(self) -> &'static str {
+        match self {
+            Provider::SauceLabs => "sauce:options",
+            Provider::BrowserStack => "bstack:options",
+            Provider::LambdaTest => "LT:Options",
+        }
+    }
+
+    /// The provider's default webdriver endpoint.
+    pub fn hub_url(self) -> &'static str {
+        match self {
+            Provider::SauceLabs => "https://ondemand.us-west-1.saucelabs.com/wd/hub/",
+            Provider::BrowserStack => "https://hub-cloud.browserstack.com/wd/hub/",
+            Provider::LambdaTest => "https://hub.lambdatest.com/wd/hub/",
+        }
+    }
+
+    // The environment variables conventionally holding the credentials.
+    fn credential_vars(self) -> (&'static str, &'static str) {
+        match self {
+            Provider::SauceLabs => ("SAUCE_USERNAME", "SAUCE_ACCESS_KEY"),
+            Provider::BrowserStack => ("BROWSERSTACK_USERNAME", "BROWSERSTACK_ACCESS_KEY"),
+            Provider::LambdaTest => ("LT_USERNAME", "LT_ACCESS_KEY"),
+        }
+    }
+}
+
+impl CloudOptions {
+    /// Options for `provider`, without credentials.
+    pub fn new(provider: Provider) -> Self {
+        CloudOptions {
+            provider,
+            username: None,
+            access_key: None,
+            build: None,
+            name: None,
+            tunnel: None,
+        }
+    }
+
+    /// Options for `provider`, with credentials from the provider's usual
+    /// environment variables, eg: `SAUCE_USERNAME` and `SAUCE_ACCESS_KEY`.
+    pub fn from_env(provider: Provider) -> Result<Self, Error> {
+        let (user_var, key_var) = provider.credential_vars();
+        let var = |name: &str| {
+            env::var(name).map_err(|e| format_err!("Reading ${} for {:?}: {}", name, provider, e))
+        };
+        Ok(CloudOptions::new(provider).credentials(var(user_var)?, var(key_var)?))
+    }
+
+    /// Set the credentials to authenticate with.
+    pub fn credentials<U: Into<String>, K: Into<String>>(
+        mut self,
+        username: U,
+        access_key: K,
+    ) -> Self {
+        self.username = Some(username.into());
+        self.access_key = Some(access_key.into());
+        self
+    }
+
+    /// Set the build that sessions are grouped under in the provider's UI.
+    pub fn build<S: Into<String>>(mut self, build: S) -> Self {
+        self.build = Some(build.into());
+        self
+    }
+
+    /// Set the name of the session, eg: the name of the test.
+    pub fn name<S: Into<String>>(mut self, name: S) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Route traffic through the named tunnel, eg: to reach a server on the
+    /// local network.
+    pub fn tunnel<S: Into<String>>(mut self, tunnel: S) -> Self {
+        self.tunnel = Some(tunnel.into());
+        self
+    }
+
+    /// The provider specific options, as JSON.
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut opts = json!({});
+        let mut set = |key: &str, value: &Option<String>| {
+            if let Some(value) = value {
+                opts[key] = json!(value);
+            }
+        };
+        match self.provider {
+            Provider::SauceLabs => {
+                set("username", &self.username);
+                set("accessKey", &self.access_key);
+                set("build", &self.build);
+                set("name", &self.name);
+                set("tunnelIdentifier", &self.tunnel);
+            }
+            Provider::BrowserStack => {
+                set("userName", &self.username);
+                set("accessKey", &self.access_key);
+                set("buildName", &self.build);
+                set("sessionName", &self.name);
+                set("localIdentifier", &self.tunnel);
+                if self.tunnel.is_some() {
+                    opts["local"] = json!(true);
+                }
+            }
+            Provider::LambdaTest => {
+                set("username", &self.username);
+                set("accessKey", &self.access_key);
+                set("build", &self.build);
+                set("name", &self.name);
+                set("tunnelName", &self.tunnel);
+                if self.tunnel.is_some() {
+                    opts["tunnel"] = json!(true);
+                }
+            }
+        }
+        opts
+    }
+
+    /// Adds these options to `capabilities`.
+    pub fn apply(&self, capabilities: Capabilities) -> Capabilities {
+        capabilities.capability(self.provider.capability(), self.to_json())
+    }
+}
+
+impl fmt::Debug for CloudOptions {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("CloudOptions")
+            .field("provider", &self.provider)
+            .field("username", &self.username)
+            .field(
+                "access_key",
+                &self.access_key.as_ref().map(|_| "<redacted>"),
+            )
+            .field("build", &self.build)
+            .field("name", &self.name)
+            .field("tunnel", &self.tunnel)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(provider: Provider) -> CloudOptions {
+        CloudOptions::new(provider)
+            .credentials("alice", "s3cret")
+            .build("nightly")
+            .name("logs in")
+            .tunnel("ci-tunnel")
+    }
+
+    #[test]
+    fn builds_vendor_options() {
+        assert_eq!(
+            options(Provider::SauceLabs).to_json(),
+            json!({
+                "username": "alice",
+                "accessKey": "s3cret",
+                "build": "nightly",
+                "name": "logs in",
+                "tunnelIdentifier": "ci-tunnel",
+            })
+        );
+        assert_eq!(
+            options(Provider::BrowserStack).to_json(),
+            json!({
+                "userName": "alice",
+                "accessKey": "s3cret",
+                "buildName": "nightly",
+                "sessionName": "logs in",
+                "local": true,
+                "localIdentifier": "ci-tunnel",
+            })
+        );
+        assert_eq!(
+            CloudOptions::new(Provider::LambdaTest).build("b").to_json(),
+            json!({ "build": "b" })
+        );
+    }
+
+    #[test]
+    fn applies_under_the_vendor_capability() {
+        let caps = options(Provider::LambdaTest).apply(Capabilities::new().browser_name("chrome"));
+        let json = serde_json::to_value(&caps).expect("serialize");
+        assert_eq!(json["alwaysMatch"]["browserName"], "chrome");
+        assert_eq!(json["alwaysMatch"]["LT:Options"]["tunnelName"], "ci-tunnel");
+    }
+
+    #[test]
+    fn redacts_access_keys() {
+        let debug = format!("{:?}", options(Provider::SauceLabs));
+        assert!(!debug.contains("s3cret"), "{}", debug);
+    }
+}
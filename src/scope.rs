@@ -0,0 +1,91 @@
+use failure::Error;
+
+use crate::client::{By, Client, Element, ShadowRoot};
+use crate::element_ref::ElementRef;
+
+/// Where a [`Scope`] searches for elements.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Root {
+    Document,
+    Element(Element),
+    ShadowRoot(ShadowRoot),
+}
+
+/// A search context, which may be the whole document, the descendants of
+/// an element, or the contents of a shadow root. This allows helpers to
+/// accept any of these, rather than duplicating the `_from` variants of
+/// [`Client`]'s methods.
+///
+/// ```no_run
+/// # fn example(client: &sulfur::Client) -> Result<(), failure::Error> {
+/// use sulfur::{By, Scope};
+///
+/// fn row_count(scope: &Scope) -> Result<usize, failure::Error> {
+///     Ok(scope.find_all(&By::css("tr"))?.len())
+/// }
+///
+/// let table = client.find(&By::id("results"))?;
+/// println!("{} of {}", row_count(&table.scope())?, row_count(&client.document())?);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Scope<'c> {
+    client: &'c Client,
+    root: Root,
+}
+
+impl<'c> Scope<'c> {
+    pub(crate) fn new(client: &'c Client, root: Root) -> Self {
+        Scope { client, root }
+    }
+
+    /// The client this scope belongs to.
+    pub fn client(&self) -> &'c Client {
+        self.client
+    }
+
+    /// Find the first element in this scope matching `by`.
+    pub fn find(&self, by: &By) -> Result<ElementRef<'c>, Error> {
+        let elt = match self.root {
+            Root::Document => self.client.find_element(by)?,
+            Root::Element(ref root) => self.client.find_element_from(root, by)?,
+            Root::ShadowRoot(ref root) => self.client.find_element_in_shadow(root, by)?,
+        };
+        Ok(ElementRef::new(self.client, elt))
+    }
+
+    /// Find all elements in this scope matching `by`.
+    pub fn find_all(&self, by: &By) -> Result<Vec<ElementRef<'c>>, Error> {
+        let elts = match self.root {
+            Root::Document => self.client.find_elements(by)?,
+            Root::Element(ref root) => self.client.find_elements_from(root, by)?,
+            Root::ShadowRoot(ref root) => self.client.find_elements_in_shadow(root, by)?,
+        };
+        Ok(elts
+            .into_iter()
+            .map(|elt| ElementRef::new(self.client, elt))
+            .collect())
+    }
+
+    /// Narrow the search to the descendants of the first element in this
+    /// scope matching `by`.
+    pub fn scope(&self, by: &By) -> Result<Scope<'c>, Error> {
+        Ok(self.find(by)?.scope())
+    }
+
+    /// The rendered text of this scope: for the document that of its
+    /// `<body>`, and for a shadow root that of its top level elements, one
+    /// per line.
+    pub fn text(&self) -> Result<String, Error> {
+        match self.root {
+            Root::Document => self.find(&By::tag_name("body"))?.text(),
+            Root::Element(ref root) => self.client.text(root),
+            Root::ShadowRoot(ref root) => self.client.execute_script(
+                "return Array.prototype.map.call(arguments[0].children, \
+                 function (elt) { return elt.innerText; }).join('\\n');",
+                &[root.into()],
+            ),
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use std::fs;
+use std::path::Path;
+
+use failure::Error;
+use failure::ResultExt;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// A PNG encoded screenshot, as returned by the browser.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Screenshot {
+    png: Vec<u8>,
+}
+
+impl Screenshot {
+    /// Wrap PNG encoded image data.
+    pub fn from_png(png: Vec<u8>) -> Self {
+        Screenshot { png }
+    }
+
+    /// The raw PNG data.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.png
+    }
+
+    /// Unwrap the raw PNG data.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.png
+    }
+
+    /// The `(width, height)` of the image in pixels, read from the PNG
+    /// header. Returns `None` if the data does not look like a PNG.
+    pub fn dimensions(&self) -> Option<(u32, u32)> {
+        // The signature is followed by the IHDR chunk: a four byte length,
+        // the chunk type, then the width and height as big-endian u32s.
+        if self.png.len() < 24
+            || !self.png.starts_with(PNG_SIGNATURE)
+            || &self.png[12..16] != b"IHDR"
+        {
+            return None;
+        }
+        let be_u32 = |offset: usize| {
+            let mut buf = [0u8; 4];
+            buf.copy_from_slice(&self.png[offset..offset + 4]);
+            u32::from_be_bytes(buf)
+        };
+        Some((be_u32(16), be_u32(20)))
+    }
+
+    /// Decode the image, eg: for making assertions about pixels.
+    #[cfg(feature = "image")]
+    pub fn to_image(&self) -> Result<image::DynamicImage, Error> {
+        let img = image::load_from_memory_with_format(&self.png, image::ImageFormat::Png)
+            .context("Decoding screenshot")?;
+        Ok(img)
+    }
+
+    /// Write the image to `path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        fs::write(path, &self.png).with_context(|_| format!("Writing screenshot to {:?}", path))?;
+        Ok(())
+    }
+}
+
+impl AsRef<[u8]> for Screenshot {
+    fn as_ref(&self) -> &[u8] {
+        &self.png
+    }
+}
+
+impl From<Screenshot> for Vec<u8> {
+    fn from(ss: Screenshot) -> Self {
+        ss.png
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_dimensions_from_header() {
+        let mut png = PNG_SIGNATURE.to_vec();
+        png.extend_from_slice(&[0, 0, 0, 13]);
+        png.extend_from_slice(b"IHDR");
+        png.extend_from_slice(&640u32.to_be_bytes());
+        png.extend_from_slice(&480u32.to_be_bytes());
+        png.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+        assert_eq!(Screenshot::from_png(png).dimensions(), Some((640, 480)));
+        assert_eq!(Screenshot::from_png(b"GIF89a".to_vec()).dimensions(), None);
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn decodes_to_image() {
+        use image::GenericImageView;
+
+        let mut png = Vec::new();
+        image::DynamicImage::new_rgba8(3, 2)
+            .write_to(&mut png, image::ImageOutputFormat::Png)
+            .expect("encode");
+        let ss = Screenshot::from_png(png);
+
+        assert_eq!(ss.dimensions(), Some((3, 2)));
+        assert_eq!(ss.to_image().expect("decode").dimensions(), (3, 2));
+    }
+}
@@ -0,0 +1,111 @@
+use failure::Error;
+
+use crate::client::{By, Client, Element, ErrorKind, WdError};
+use crate::element_ref::ElementRef;
+
+/// A helper for interacting with `<select>` dropdowns and list boxes.
+#[derive(Debug, Clone)]
+pub struct Select<'c> {
+    element: ElementRef<'c>,
+    multiple: bool,
+}
+
+impl<'c> Select<'c> {
+    /// Wrap the given `<select>` element. Fails if it is some other kind of
+    /// element.
+    pub fn new(client: &'c Client, element: Element) -> Result<Self, Error> {
+        let element = ElementRef::new(client, element);
+        let tag = element.name()?;
+        if !tag.eq_ignore_ascii_case("select") {
+            bail!("Expected a <select> element, got <{}>", tag);
+        }
+        let multiple = element.property("multiple")?.as_bool().unwrap_or(false);
+        Ok(Select { element, multiple })
+    }
+
+    /// The underlying `<select>` element.
+    pub fn element(&self) -> &ElementRef<'c> {
+        &self.element
+    }
+
+    /// Whether more than one option may be selected at once.
+    pub fn is_multiple(&self) -> bool {
+        self.multiple
+    }
+
+    /// All of the `<option>`s in this list.
+    pub fn options(&self) -> Result<Vec<ElementRef<'c>>, Error> {
+        self.element.find_all(&By::tag_name("option"))
+    }
+
+    /// The currently selected `<option>`s.
+    pub fn selected_options(&self) -> Result<Vec<ElementRef<'c>>, Error> {
+        let mut selected = Vec::new();
+        for option in self.options()? {
+            if option.is_selected()? {
+                selected.push(option);
+            }
+        }
+        Ok(selected)
+    }
+
+    /// Select the option(s) whose displayed text is `text`.
+    pub fn select_by_visible_text(&self, text: &str) -> Result<(), Error> {
+        self.select_matching(&format!("with text {:?}", text), |option| {
+            Ok(option.text()?.trim() == text.trim())
+        })
+    }
+
+    /// Select the option(s) whose `value` is `value`.
+    pub fn select_by_value(&self, value: &str) -> Result<(), Error> {
+        self.select_matching(&format!("with value {:?}", value), |option| {
+            Ok(option.property("value")?.as_str() == Some(value))
+        })
+    }
+
+    /// Select the option at position `index`, counting from zero.
+    pub fn select_by_index(&self, index: usize) -> Result<(), Error> {
+        self.select_matching(&format!("at index {}", index), |option| {
+            Ok(option.property("index")?.as_u64() == Some(index as u64))
+        })
+    }
+
+    /// Deselect all options. Only valid for multi-selects.
+    pub fn deselect_all(&self) -> Result<(), Error> {
+        if !self.multiple {
+            bail!("Can only deselect options of a multi-select");
+        }
+        for option in self.selected_options()? {
+            option.click()?;
+        }
+        Ok(())
+    }
+
+    fn select_matching<F>(&self, description: &str, mut pred: F) -> Result<(), Error>
+    where
+        F: FnMut(&ElementRef<'c>) -> Result<bool, Error>,
+    {
+        let mut found = false;
+        for option in self.options()? {
+            if !pred(&option)? {
+                continue;
+            }
+            found = true;
+            if !option.is_selected()? {
+                option.click()?;
+            }
+            if !self.multiple {
+                break;
+            }
+        }
+
+        if !found {
+            return Err(WdError::new(
+                ErrorKind::NoSuchElement,
+                format!("No option {} found", description),
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
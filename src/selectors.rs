@@ -0,0 +1,377 @@
+//! Helpers for safely building selectors from arbitrary strings, eg:
+//! generated ids containing `:`, or text containing quotes.
+//!
+//! ```
+//! use sulfur::{selectors, By};
+//!
+//! let by = By::css(format!("#{} > input", selectors::css_escape("form:1")));
+//! let by = By::xpath(format!("//label[. = {}]", selectors::xpath_literal("Don't")));
+//! ```
+//!
+//! Or XPath expressions may be built with [`XPath`]:
+//!
+//! ```
+//! use sulfur::{selectors::XPath, By};
+//!
+//! let by = By::from(XPath::tag("div").with_class("card").descendant("a").containing_text("More"));
+//! ```
+
+use std::fmt::{self, Write};
+
+use crate::client::By;
+
+/// A builder for XPath expressions, which quotes strings as needed.
+/// Expressions are relative to the context node, so may be used with eg:
+/// [`Client::find_element_from`](crate::Client::find_element_from).
+///
+/// Tag and attribute names that are not simple XML names (eg: containing
+/// `]`) are compared with `name()`, so any name may be given safely; `*`
+/// matches any tag.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct XPath {
+    expr: String,
+}
+
+impl XPath {
+    /// Matches `tag` elements beneath the context node.
+    pub fn tag(tag: &str) -> Self {
+        XPath {
+            expr: format!(".//{}", tag_test(tag)),
+        }
+    }
+
+    /// Matches any element beneath the context node.
+    pub fn any() -> Self {
+        XPath::tag("*")
+    }
+
+    /// Restricts matches to `tag` elements that are children of the
+    /// current matches.
+    pub fn child(mut self, tag: &str) -> Self {
+        write!(self.expr, "/{}", tag_test(tag)).expect("write to string");
+        self
+    }
+
+    /// Restricts matches to `tag` elements that are descendants of the
+    /// current matches.
+    pub fn descendant(mut self, tag: &str) -> Self {
+        write!(self.expr, "//{}", tag_test(tag)).expect("write to string");
+        self
+    }
+
+    /// Matches the parents of the current matches.
+    pub fn parent(mut self) -> Self {
+        self.expr.push_str("/..");
+        self
+    }
+
+    /// Restricts matches to those with the class `class`.
+    pub fn with_class(self, class: &str) -> Self {
+        let class = xpath_literal(&format!(" {} ", class.trim()));
+        self.predicate(format!(
+            "contains(concat(' ', normalize-space(@class), ' '), {})",
+            class
+        ))
+    }
+
+    /// Restricts matches to those with the id `id`.
+    pub fn with_id(self, id: &str) -> Self {
+        self.with_attribute("id", id)
+    }
+
+    /// Restricts matches to those with `attribute` set to `value`.
+    pub fn with_attribute(self, attribute: &str, value: &str) -> Self {
+        let test = attribute_test(attribute);
+        self.predicate(format!("{}={}", test, xpath_literal(value)))
+    }
+
+    /// Restricts matches to those with `attribute` set, to any value.
+    pub fn has_attribute(self, attribute: &str) -> Self {
+        self.predicate(attribute_test(attribute))
+    }
+
+    /// Restricts matches to those whose text is `text`, ignoring
+    /// surrounding whitespace, and treating each run of whitespace as a
+    /// single space.
+    pub fn with_text(self, text: &str) -> Self {
+        self.predicate(format!(
+            "normalize-space(.)={}",
+            xpath_literal(&normalize_space(text))
+        ))
+    }
+
+    /// Restricts matches to those whose text contains `text`.
+    pub fn containing_text(self, text: &str) -> Self {
+        self.predicate(format!("contains(., {})", xpath_literal(text)))
+    }
+
+    /// Restricts matches to the `n`th (counting from one) of its siblings
+    /// that match so far.
+    pub fn nth(self, n: usize) -> Self {
+        self.predicate(n.to_string())
+    }
+
+    /// The expression built so far.
+    pub fn as_str(&self) -> &str {
+        &self.expr
+    }
+
+    fn predicate(mut self, predicate: String) -> Self {
+        write!(self.expr, "[{}]", predicate).expect("write to string");
+        self
+    }
+}
+
+impl fmt::Display for XPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.expr)
+    }
+}
+
+impl From<XPath> for By {
+    fn from(xpath: XPath) -> Self {
+        By::xpath(xpath.expr)
+    }
+}
+
+// A node test for elements named `tag`.
+fn tag_test(tag: &str) -> String {
+    if tag == "*" || is_name(tag) {
+        tag.to_string()
+    } else {
+        format!("*[name()={}]", xpath_literal(tag))
+    }
+}
+
+// A node test for the attribute `name`.
+fn attribute_test(name: &str) -> String {
+    if is_name(name) {
+        format!("@{}", name)
+    } else {
+        format!("@*[name()={}]", xpath_literal(name))
+    }
+}
+
+// Whether `name` is a (simplified) XML name, so may be used unquoted.
+fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.')
+        }
+        _ => false,
+    }
+}
+
+/// Escapes `ident` for use as a CSS identifier, eg: an id or class name, as
+/// per CSSOM's "serialize an identifier".
+pub fn css_escape(ident: &str) -> String {
+    let mut out = String::with_capacity(ident.len());
+    let chars = ident.chars().collect::<Vec<_>>();
+    for (i, &c) in chars.iter().enumerate() {
+        let starts_with_digit = c.is_ascii_digit() && (i == 0 || (i == 1 && chars[0] == '-'));
+        match c {
+            '\0' => out.push('\u{FFFD}'),
+            '\u{1}'..='\u{1f}' | '\u{7f}' => escape_code_point(&mut out, c),
+            _ if starts_with_digit => escape_code_point(&mut out, c),
+            '-' if chars.len() == 1 => out.push_str("\\-"),
+            '-' | '_' => out.push(c),
+            _ if c.is_ascii_alphanumeric() || c >= '\u{80}' => out.push(c),
+            _ => {
+                out.push('\\');
+                out.push(c);
+            }
+        }
+    }
+    out
+}
+
+// Quotes `s` as a CSS string, as per CSSOM's "serialize a string".
+pub(crate) fn css_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '\0' => out.push('\u{FFFD}'),
+            '\u{1}'..='\u{1f}' | '\u{7f}' => escape_code_point(&mut out, c),
+            '"' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Quotes `s` as an XPath 1.0 string literal. XPath has no escape syntax,
+/// so strings containing both kinds of quote are built up with `concat()`.
+pub fn xpath_literal(s: &str) -> String {
+    if !s.contains('\'') {
+        return format!("'{}'", s);
+    }
+    if !s.contains('"') {
+        return format!("\"{}\"", s);
+    }
+    let parts = s
+        .split('\'')
+        .map(|part| format!("'{}'", part))
+        .collect::<Vec<_>>()
+        .join(", \"'\", ");
+    format!("concat({})", parts)
+}
+
+// Collapses whitespace as XPath's `normalize-space` does, so that `text`
+// can be compared with its result.
+pub(crate) fn normalize_space(text: &str) -> String {
+    text.split([' ', '\t', '\r', '\n'])
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Builds an XPath matching elements with the ARIA `role`, either explicitly
+// or implicitly from their tag, and optionally an accessible name of `name`.
+// This approximates the accessible name computation: any of the sources of
+// a name may match, regardless of precedence.
+pub(crate) fn role_xpath(role: &str, name: Option<&str>) -> String {
+    let role = role.trim().to_ascii_lowercase();
+    let mut expr = format!("@role={}", xpath_literal(&role));
+    if let Some(implicit) = implicit_role(&role) {
+        write!(expr, " or (not(@role) and ({}))", implicit).expect("write to string");
+    }
+    match name {
+        None => format!(".//*[{}]", expr),
+        Some(name) => {
+            let name = xpath_literal(&normalize_space(name));
+            format!(
+                ".//*[({})][normalize-space(@aria-label)={name} \
+                 or @aria-labelledby=//*[normalize-space(.)={name}]/@id \
+                 or @id=//label[normalize-space(.)={name}]/@for \
+                 or ancestor::label[normalize-space(.)={name}] \
+                 or (not(@aria-label) and normalize-space(.)={name}) \
+                 or self::input[@type='submit' or @type='button' or @type='reset'][normalize-space(@value)={name}] \
+                 or normalize-space(@alt)={name} or normalize-space(@title)={name}]",
+                expr,
+                name = name
+            )
+        }
+    }
+}
+
+// Predicates for the elements that have `role` without an explicit `role`
+// attribute, for commonly used roles.
+fn implicit_role(role: &str) -> Option<&'static str> {
+    let predicate = match role {
+        "button" => {
+            "self::button or self::summary \
+             or self::input[@type='button' or @type='submit' or @type='reset' or @type='image']"
+        }
+        "link" => "self::a[@href] or self::area[@href]",
+        "heading" => "self::h1 or self::h2 or self::h3 or self::h4 or self::h5 or self::h6",
+        "checkbox" => "self::input[@type='checkbox']",
+        "radio" => "self::input[@type='radio']",
+        "textbox" => {
+            "self::textarea \
+             or self::input[not(@type) or @type='text' or @type='email' or @type='tel' or @type='url']"
+        }
+        "searchbox" => "self::input[@type='search']",
+        "spinbutton" => "self::input[@type='number']",
+        "slider" => "self::input[@type='range']",
+        "combobox" => "self::select[not(@multiple) and not(@size > 1)]",
+        "listbox" => "self::select[@multiple or @size > 1] or self::datalist",
+        "option" => "self::option",
+        "list" => "self::ul or self::ol or self::menu",
+        "listitem" => "self::li",
+        "img" => "self::img[not(@alt='')]",
+        "navigation" => "self::nav",
+        "main" => "self::main",
+        "article" => "self::article",
+        "dialog" => "self::dialog",
+        "form" => "self::form",
+        "table" => "self::table",
+        "row" => "self::tr",
+        "cell" => "self::td",
+        "columnheader" => "self::th",
+        "progressbar" => "self::progress",
+        _ => return None,
+    };
+    Some(predicate)
+}
+
+fn escape_code_point(out: &mut String, c: char) {
+    write!(out, "\\{:x} ", c as u32).expect("write to string");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_css_identifiers() {
+        assert_eq!(css_escape("user-menu"), "user-menu");
+        assert_eq!(css_escape("a:b.c"), "a\\:b\\.c");
+        assert_eq!(css_escape("1st"), "\\31 st");
+        assert_eq!(css_escape("-2"), "-\\32 ");
+        assert_eq!(css_escape("-"), "\\-");
+        assert_eq!(css_escape("caf\u{e9}"), "caf\u{e9}");
+    }
+
+    #[test]
+    fn quotes_xpath_literals() {
+        assert_eq!(xpath_literal("Save"), "'Save'");
+        assert_eq!(xpath_literal("Don't"), "\"Don't\"");
+        assert_eq!(
+            xpath_literal("Say \"don't\""),
+            "concat('Say \"don', \"'\", 't\"')"
+        );
+    }
+
+    #[test]
+    fn builds_xpath_expressions() {
+        assert_eq!(
+            XPath::tag("div")
+                .with_class("card")
+                .descendant("a")
+                .containing_text("Don't")
+                .to_string(),
+            ".//div[contains(concat(' ', normalize-space(@class), ' '), ' card ')]\
+             //a[contains(., \"Don't\")]"
+        );
+        assert_eq!(
+            XPath::any()
+                .with_attribute("data-test-id", "x")
+                .child("li")
+                .nth(2)
+                .parent()
+                .as_str(),
+            ".//*[@data-test-id='x']/li[2]/.."
+        );
+        assert_eq!(
+            XPath::tag("label").with_text(" Name ").to_string(),
+            ".//label[normalize-space(.)='Name']"
+        );
+    }
+
+    #[test]
+    fn quotes_unusual_names() {
+        assert_eq!(
+            XPath::tag("div").has_attribute("a]|//b").as_str(),
+            ".//div[@*[name()='a]|//b']]"
+        );
+        assert_eq!(
+            XPath::tag("my:widget")
+                .with_attribute("@click", "go")
+                .as_str(),
+            ".//*[name()='my:widget'][@*[name()='@click']='go']"
+        );
+    }
+
+    #[test]
+    fn quotes_css_strings() {
+        assert_eq!(css_string("q"), "\"q\"");
+        assert_eq!(css_string("say \"hi\"\\"), "\"say \\\"hi\\\"\\\\\"");
+        assert_eq!(css_string("a\nb"), "\"a\\a b\"");
+    }
+}
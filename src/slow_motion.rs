@@ -0,0 +1,114 @@
+use std::time;
+
+// Outlines the element `arguments[0]` for `arguments[1]` milliseconds.
+pub(crate) const HIGHLIGHT: &str = r#"
+var elt = arguments[0], outline = elt.style.outline;
+elt.style.outline = "3px solid magenta";
+setTimeout(function () { elt.style.outline = outline; }, arguments[1]);
+"#;
+
+/// Slows a session down so that a person can follow what it does, eg: when
+/// watching a headed browser to understand a flaky interaction. See
+/// [`Client::set_slow_motion`](crate::Client::set_slow_motion).
+///
+/// ```no_run
+/// # fn example(mut client: sulfur::Client) {
+/// use std::time::Duration;
+/// use sulfur::SlowMotion;
+///
+/// client.set_slow_motion(Some(SlowMotion::new(Duration::from_millis(500)).highlight(true)));
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlowMotion {
+    delay: time::Duration,
+    highlight: bool,
+}
+
+impl SlowMotion {
+    /// Pause for `delay` before each command.
+    pub fn new(delay: time::Duration) -> Self {
+        SlowMotion {
+            delay,
+            highlight: false,
+        }
+    }
+
+    /// Outline the element a command acts on (eg: the one being clicked)
+    /// during the pause before it.
+    pub fn highlight(mut self, highlight: bool) -> Self {
+        self.highlight = highlight;
+        self
+    }
+
+    pub(crate) fn delay(&self) -> time::Duration {
+        self.delay
+    }
+
+    pub(crate) fn highlights(&self) -> bool {
+        self.highlight
+    }
+}
+
+// The id of the element `path` (eg: `session/1/element/2/click`) acts on, if
+// any.
+pub(crate) fn target_element(path: &str) -> Option<&str> {
+    match path.split('/').collect::<Vec<_>>()[..] {
+        ["session", _, "element", id, _, ..] => Some(id),
+        _ => None,
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::*;
+    use crate::client::By;
+    use crate::testing::{MockElement, MockWebDriver};
+
+    #[test]
+    fn finds_the_target_element() {
+        assert_eq!(target_element("session/1/element/e2/click"), Some("e2"));
+        assert_eq!(
+            target_element("session/1/element/e2/attribute/href"),
+            Some("e2")
+        );
+        assert_eq!(target_element("session/1/element"), None);
+        assert_eq!(target_element("session/1/element/active"), None);
+        assert_eq!(target_element("session/1/url"), None);
+    }
+
+    #[test]
+    fn pauses_and_highlights_before_commands() {
+        let mock = MockWebDriver::start().expect("start");
+        mock.on_find(
+            &By::css("button"),
+            vec![MockElement::new("button").text("Go")],
+        );
+        let mut client = mock.client().expect("client");
+        let delay = time::Duration::from_millis(50);
+        client.set_slow_motion(Some(SlowMotion::new(delay).highlight(true)));
+
+        let started = time::Instant::now();
+        let button = client.find_element(&By::css("button")).expect("find");
+        assert_eq!(client.text(&button).expect("text"), "Go");
+        assert!(started.elapsed() >= delay * 2);
+
+        let paths = mock
+            .requests()
+            .into_iter()
+            .map(|r| r.path)
+            .collect::<Vec<_>>();
+        assert_eq!(paths.len(), 3, "{:?}", paths);
+        assert_eq!(paths[0], "element");
+        assert_eq!(paths[1], "execute/sync");
+        assert!(paths[2].ends_with("/text"), "{:?}", paths);
+        assert!(
+            client
+                .command_log()
+                .entries()
+                .iter()
+                .all(|e| e.command != "execute_script"),
+            "Highlights should not be logged"
+        );
+    }
+}
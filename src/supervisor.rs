@@ -0,0 +1,264 @@
+//! Support for long-running automation jobs (eg: scraping) that should
+//! survive browser or driver crashes.
+//!
+//! A [`Supervisor`] works through a queue of URLs, periodically persisting its
+//! [`Progress`] to disk. When handling a page fails, the session is
+//! re-created, and the saved cookies and location are restored before
+//! carrying on where it left off. Re-running a job with the same state file
+//! resumes a previously crashed run.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time;
+
+use failure::Error;
+use failure::{Fail, ResultExt};
+
+use crate::client::{Client, Cookie};
+use crate::driver::DriverHolder;
+
+const DEFAULT_HEARTBEAT: time::Duration = time::Duration::from_secs(30);
+const DEFAULT_MAX_RESTARTS: usize = 3;
+
+/// The persisted state of a supervised job.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Progress {
+    /// The browser's location as of the last heartbeat.
+    pub current_url: Option<String>,
+    /// The cookies visible as of the last heartbeat.
+    pub cookies: Vec<Cookie>,
+    /// URLs that remain to be processed.
+    pub queue: VecDeque<String>,
+}
+
+/// Runs a job over a queue of URLs, restarting the session when it fails.
+pub struct Supervisor<F> {
+    start: F,
+    state_path: PathBuf,
+    heartbeat: time::Duration,
+    max_restarts: usize,
+    progress: Progress,
+}
+
+impl Progress {
+    /// Load previously saved progress, if there is any.
+    pub fn load(path: &Path) -> Result<Option<Self>, Error> {
+        match fs::read(path) {
+            Ok(content) => {
+                let progress = serde_json::from_slice(&content)
+                    .with_context(|_| format!("Parsing progress from {:?}", path))?;
+                Ok(Some(progress))
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e
+                .context(format!("Reading progress from {:?}", path))
+                .into()),
+        }
+    }
+
+    /// Save the progress to `path`. We write to a temporary file first so
+    /// that a crash mid-write cannot leave a truncated state file.
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec(self)?)
+            .with_context(|_| format!("Writing progress to {:?}", tmp))?;
+        fs::rename(&tmp, path).with_context(|_| format!("Renaming progress to {:?}", path))?;
+        Ok(())
+    }
+}
+
+impl<F> Supervisor<F>
+where
+    F: FnMut() -> Result<DriverHolder, Error>,
+{
+    /// Create a supervisor that persists progress to `state_path`, and uses
+    /// `start` to create a new session whenever one is needed. If the state
+    /// file already exists, the job resumes from there.
+    pub fn new<P: Into<PathBuf>>(state_path: P, start: F) -> Result<Self, Error> {
+        let state_path = state_path.into();
+        let progress = Progress::load(&state_path)?.unwrap_or_default();
+        if !progress.queue.is_empty() {
+            info!(
+                "Resuming from {:?} with {} queued items",
+                state_path,
+                progress.queue.len()
+            );
+        }
+        Ok(Supervisor {
+            start,
+            state_path,
+            heartbeat: DEFAULT_HEARTBEAT,
+            max_restarts: DEFAULT_MAX_RESTARTS,
+            progress,
+        })
+    }
+
+    /// How often progress should be persisted.
+    pub fn heartbeat(&mut self, interval: time::Duration) -> &mut Self {
+        self.heartbeat = interval;
+        self
+    }
+
+    /// How many times in a row we will restart the session for the same
+    /// item before giving up.
+    pub fn max_restarts(&mut self, max_restarts: usize) -> &mut Self {
+        self.max_restarts = max_restarts;
+        self
+    }
+
+    /// Add a URL to the end of the work queue.
+    pub fn enqueue<S: Into<String>>(&mut self, url: S) -> &mut Self {
+        self.progress.queue.push_back(url.into());
+        self
+    }
+
+    /// The current state of the job.
+    pub fn progress(&self) -> &Progress {
+        &self.progress
+    }
+
+    /// Process the queue until it is empty. `handler` is called for each URL,
+    /// and may return further URLs to be queued. If `handler` fails, the
+    /// session is restarted, and the same URL retried.
+    pub fn run<H>(&mut self, mut handler: H) -> Result<(), Error>
+    where
+        H: FnMut(&Client, &str) -> Result<Vec<String>, Error>,
+    {
+        let mut session = self.restart()?;
+        let mut last_heartbeat = time::Instant::now();
+        let mut failures = 0;
+
+        while let Some(url) = self.progress.queue.front().cloned() {
+            // Checked before each item, so that progress is still saved
+            // while items are failing.
+            let res = self
+                .heartbeat_if_due(&session, &mut last_heartbeat)
+                .and_then(|()| handler(&session, &url))
+                .map(|more| {
+                    self.progress.queue.pop_front();
+                    self.progress.queue.extend(more);
+                });
+
+            match res {
+                Ok(()) => failures = 0,
+                Err(e) => {
+                    failures += 1;
+                    warn!("Processing {:?} failed ({} times): {:?}", url, failures, e);
+                    if failures > self.max_restarts {
+                        self.progress.save(&self.state_path)?;
+                        return Err(e.context(format!("Giving up on {:?}", url)).into());
+                    }
+                    self.progress.save(&self.state_path)?;
+                    drop(session);
+                    session = self.restart()?;
+                }
+            }
+        }
+
+        self.checkpoint(&session)?;
+        session.close()?;
+        Ok(())
+    }
+
+    fn heartbeat_if_due(
+        &mut self,
+        client: &Client,
+        last_heartbeat: &mut time::Instant,
+    ) -> Result<(), Error> {
+        if last_heartbeat.elapsed() >= self.heartbeat {
+            self.checkpoint(client)?;
+            *last_heartbeat = time::Instant::now();
+        }
+        Ok(())
+    }
+
+    fn checkpoint(&mut self, client: &Client) -> Result<(), Error> {
+        self.progress.current_url = Some(client.current_url_internal()?);
+        self.progress.cookies = client.cookies_internal()?;
+        debug!("Heartbeat: saving progress to {:?}", self.state_path);
+        self.progress.save(&self.state_path)
+    }
+
+    fn restart(&mut self) -> Result<DriverHolder, Error> {
+        let session = (self.start)().context("Starting session")?;
+        if let Some(url) = self.progress.current_url.as_ref() {
+            info!("Restoring session at {:?}", url);
+            // Cookies can only be set for the current document's domain.
+            session.visit(url)?;
+            for cookie in self.progress.cookies.iter() {
+                if let Err(e) = session.add_cookie(cookie) {
+                    warn!("Could not restore cookie {:?}: {:?}", cookie.name, e);
+                }
+            }
+            session.refresh()?;
+        }
+        Ok(session)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_round_trips_through_state_file() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("state.json");
+
+        assert_eq!(Progress::load(&path).expect("load missing"), None);
+
+        let progress = Progress {
+            current_url: Some("http://example.com/".into()),
+            cookies: vec![Cookie {
+                name: "session".into(),
+                value: "abc".into(),
+                ..Cookie::default()
+            }],
+            queue: vec!["http://example.com/next".to_string()].into(),
+        };
+        progress.save(&path).expect("save");
+
+        assert_eq!(Progress::load(&path).expect("load"), Some(progress));
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn heartbeats_while_items_fail() {
+        use crate::driver::Driver;
+        use crate::testing::MockWebDriver;
+
+        struct NoDriver;
+        impl Driver for NoDriver {
+            fn close(&mut self) -> Result<(), Error> {
+                Ok(())
+            }
+        }
+
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mock = MockWebDriver::start().expect("start");
+        let mut supervisor = Supervisor::new(dir.path().join("state.json"), || {
+            Ok(DriverHolder::new(Box::new(NoDriver), mock.client()?))
+        })
+        .expect("supervisor");
+        supervisor
+            .heartbeat(time::Duration::from_secs(0))
+            .max_restarts(2)
+            .enqueue("http://example.com/");
+
+        supervisor
+            .run(|client, url| -> Result<Vec<String>, Error> {
+                client.visit(url)?;
+                bail!("Always fails")
+            })
+            .expect_err("gives up");
+        let heartbeats = mock
+            .requests()
+            .iter()
+            .filter(|r| r.method == "GET" && r.path == "cookie")
+            .count();
+        assert_eq!(heartbeats, 3);
+    }
+}
@@ -0,0 +1,710 @@
+//! Helpers for writing browser tests, such as serving fixture pages for the
+//! browser to load, starting sessions for tests (see also the
+//! `browser_test` attribute, enabled by the `macros` feature), and a mock
+//! driver for testing without a browser.
+//!
+//! ```no_run
+//! # fn example(client: &sulfur::Client) -> Result<(), failure::Error> {
+//! use sulfur::testing::StaticServer;
+//!
+//! let server = StaticServer::with_pages(vec![("/", "<h1>Hello</h1>")])?;
+//! client.visit(&server.url())?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::net::{SocketAddr, TcpListener};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use failure::Error;
+use futures::channel::oneshot;
+use futures::future::select;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, StatusCode};
+use tokio::runtime;
+
+#[cfg(any(feature = "chrome", feature = "gecko"))]
+use crate::artifacts::ArtifactSink;
+use crate::client::{By, Capabilities, Client, Timeouts, ELEMENT_KEY};
+#[cfg(any(feature = "chrome", feature = "gecko"))]
+use crate::driver::DriverHolder;
+
+/// The environment variable previously used to pick the driver for
+/// [`start_session`], either `chromedriver` or `geckodriver`. It is still
+/// read when [`SULFUR_BROWSER`](crate::environment::BROWSER_ENV) is unset.
+pub const DRIVER_ENV: &str = "DRIVER";
+pub use crate::artifacts::ARTIFACTS_ENV;
+
+/// An HTTP server on an ephemeral local port, serving either a directory or
+/// a fixed set of pages. The server is shut down when this is dropped.
+#[derive(Debug)]
+pub struct StaticServer {
+    shutdown: Option<oneshot::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+    addr: SocketAddr,
+}
+
+enum Content {
+    Directory(hyper_staticfile::Static),
+    Pages(BTreeMap<String, String>),
+    WebDriver(Arc<Mutex<MockState>>),
+}
+
+/// A fake webdriver server, which speaks just enough of the protocol to
+/// unit test code built on sulfur without a browser. Elements are found via
+/// canned responses for each selector; navigation just records the URL.
+///
+/// ```no_run
+/// # fn example() -> Result<(), failure::Error> {
+/// use sulfur::testing::{MockElement, MockWebDriver};
+/// use sulfur::By;
+///
+/// let mock = MockWebDriver::start()?;
+/// mock.on_find(&By::css("button"), vec![MockElement::new("button").text("Go")]);
+/// let client = mock.client()?;
+/// let button = client.find_element(&By::css("button"))?;
+/// client.click(&button)?;
+/// assert_eq!(mock.requests().last().map(|r| r.path.as_str()), Some("element/mock-1/click"));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct MockWebDriver {
+    server: StaticServer,
+    state: Arc<Mutex<MockState>>,
+}
+
+/// An element returned by a [`MockWebDriver`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MockElement {
+    tag_name: String,
+    text: String,
+    attributes: BTreeMap<String, String>,
+    displayed: bool,
+    enabled: bool,
+}
+
+/// A command received by a [`MockWebDriver`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockRequest {
+    /// The HTTP method, eg: `POST`.
+    pub method: String,
+    /// The endpoint path, relative to the session, eg: `url`.
+    pub path: String,
+    /// The request body, if any.
+    pub body: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    url: String,
+    title: String,
+    finds: BTreeMap<String, Vec<String>>,
+    elements: BTreeMap<String, MockElement>,
+    requests: Vec<MockRequest>,
+    sessions: BTreeSet<String>,
+    started: usize,
+    timeouts: Timeouts,
+    console: Vec<serde_json::Value>,
+    unsupported: BTreeSet<String>,
+    script_result: Option<serde_json::Value>,
+}
+
+const MOCK_SESSION_ID: &str = "mock-session";
+
+impl StaticServer {
+    /// Serve the files in the directory `root`.
+    pub fn serve_dir<P: AsRef<Path>>(root: P) -> Result<Self, Error> {
+        let content = Content::Directory(hyper_staticfile::Static::new(root.as_ref()));
+        StaticServer::start(content)
+    }
+
+    /// Serve each of the given `(path, html)` pairs, eg: `("/", "<p>Hi</p>")`.
+    /// Any other path gets a 404 response.
+    pub fn with_pages<I, P, H>(pages: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = (P, H)>,
+        P: Into<String>,
+        H: Into<String>,
+    {
+        let pages = pages
+            .into_iter()
+            .map(|(path, html)| (path.into(), html.into()))
+            .collect();
+        StaticServer::start(Content::Pages(pages))
+    }
+
+    /// The base URL of the server, eg: `http://127.0.0.1:34567/`.
+    pub fn url(&self) -> String {
+        format!("http://{}:{}/", self.addr.ip(), self.addr.port())
+    }
+
+    /// The address the server is listening on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    fn start(content: Content) -> Result<Self, Error> {
+        let (tx, rx) = oneshot::channel::<()>();
+        let sock = TcpListener::bind("127.0.0.1:0")?;
+        let addr = sock.local_addr()?;
+        let mut rt = runtime::Builder::new()
+            .basic_scheduler()
+            .enable_all()
+            .build()?;
+
+        let content = Arc::new(content);
+        let make_service = make_service_fn(move |_| {
+            let content = content.clone();
+            futures::future::ok::<_, hyper::Error>(service_fn(move |req| {
+                let content = content.clone();
+                async move { content.respond(req).await }
+            }))
+        });
+
+        let thread = thread::Builder::new()
+            .name(format!("StaticServer {}", addr))
+            .spawn(move || {
+                rt.block_on(async {
+                    let srv = match hyper::Server::from_tcp(sock) {
+                        Ok(builder) => builder.serve(make_service),
+                        Err(e) => {
+                            error!("Listening on {}: {}", addr, e);
+                            return;
+                        }
+                    };
+                    debug!("Serving on {}", addr);
+                    let _ = select(srv, rx).await;
+                })
+            })?;
+
+        Ok(StaticServer {
+            shutdown: Some(tx),
+            thread: Some(thread),
+            addr,
+        })
+    }
+}
+
+impl MockWebDriver {
+    /// Starts a mock driver on an ephemeral local port.
+    pub fn start() -> Result<Self, Error> {
+        let state = Arc::new(Mutex::new(MockState::default()));
+        let server = StaticServer::start(Content::WebDriver(state.clone()))?;
+        Ok(MockWebDriver { server, state })
+    }
+
+    /// The base URL of the driver, eg: for [`Client::new`].
+    pub fn url(&self) -> String {
+        self.server.url()
+    }
+
+    /// Starts a session on this driver.
+    pub fn client(&self) -> Result<Client, Error> {
+        Client::new(&self.url(), Capabilities::new())
+    }
+
+    /// Answers finds for `by` with `elements`, replacing any previous answer.
+    /// Selectors without an answer find nothing. Relative selectors are
+    /// resolved by a script, which the mock cannot run, so cannot be
+    /// answered.
+    pub fn on_find(&self, by: &By, elements: Vec<MockElement>) {
+        assert!(
+            !by.is_relative(),
+            "Relative selectors cannot be mocked: {:?}",
+            by
+        );
+        let mut state = self.state.lock().expect("unpoison");
+        let ids = elements
+            .into_iter()
+            .map(|elt| {
+                let id = format!("mock-{}", state.elements.len() + 1);
+                state.elements.insert(id.clone(), elt);
+                id
+            })
+            .collect();
+        state.finds.insert(selector_key(by), ids);
+    }
+
+    /// Sets the title of the "current page".
+    pub fn set_title<S: Into<String>>(&self, title: S) {
+        self.state.lock().expect("unpoison").title = title.into();
+    }
+
+    /// Adds a message to the browser's console log, as read by
+    /// [`Client::logs`].
+    pub fn console_log<S: Into<String>>(&self, level: &str, message: S) {
+        let entry = json!({ "level": level, "message": message.into(), "timestamp": 0 });
+        self.state.lock().expect("unpoison").console.push(entry);
+    }
+
+    /// Makes commands whose endpoint ends in `endpoint` (eg: `displayed`)
+    /// fail with `unknown command`, as with drivers that do not implement
+    /// them.
+    pub fn unsupported<S: Into<String>>(&self, endpoint: S) {
+        let mut state = self.state.lock().expect("unpoison");
+        state.unsupported.insert(endpoint.into());
+    }
+
+    /// Answers script executions with `result`. Without one, scripts fail
+    /// with `unknown command`, as the mock cannot run them.
+    pub fn on_script<V: Into<serde_json::Value>>(&self, result: V) {
+        self.state.lock().expect("unpoison").script_result = Some(result.into());
+    }
+
+    /// Simulates the browser crashing: commands for existing sessions fail
+    /// with `invalid session id`. New sessions may still be started.
+    pub fn crash(&self) {
+        self.state.lock().expect("unpoison").sessions.clear();
+    }
+
+    /// The commands received so far for the session, oldest first.
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.state.lock().expect("unpoison").requests.clone()
+    }
+}
+
+impl MockElement {
+    /// A visible, enabled element with the given tag name, eg: `button`.
+    pub fn new<S: Into<String>>(tag_name: S) -> Self {
+        MockElement {
+            tag_name: tag_name.into(),
+            displayed: true,
+            enabled: true,
+            ..MockElement::default()
+        }
+    }
+
+    /// Sets the element's text.
+    pub fn text<S: Into<String>>(mut self, text: S) -> Self {
+        self.text = text.into();
+        self
+    }
+
+    /// Sets an attribute, eg: `href`.
+    pub fn attribute<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.attributes.insert(name.into(), value.into());
+        self
+    }
+
+    /// Sets whether the element is displayed.
+    pub fn displayed(mut self, displayed: bool) -> Self {
+        self.displayed = displayed;
+        self
+    }
+
+    /// Sets whether the element is enabled.
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+}
+
+fn selector_key(by: &By) -> String {
+    serde_json::to_string(by).expect("serialize selector")
+}
+
+impl MockState {
+    fn handle(
+        &mut self,
+        method: &hyper::Method,
+        path: &str,
+        body: Option<serde_json::Value>,
+    ) -> Result<serde_json::Value, (StatusCode, &'static str, String)> {
+        let segments = path
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>();
+        match (method, &segments[..]) {
+            (&hyper::Method::POST, ["session"]) => {
+                self.started += 1;
+                let id = match self.started {
+                    1 => MOCK_SESSION_ID.to_string(),
+                    n => format!("{}-{}", MOCK_SESSION_ID, n),
+                };
+                self.sessions.insert(id.clone());
+                return Ok(json!({ "sessionId": id, "capabilities": {} }));
+            }
+            (&hyper::Method::GET, ["status"]) => {
+                return Ok(json!({ "ready": true, "message": "mock ready" }));
+            }
+            (_, ["session", id, ..]) if !self.sessions.contains(*id) => {
+                return Err(wd_error("invalid session id", path));
+            }
+            _ => {}
+        }
+        let rest = match &segments[..] {
+            ["session", _, rest @ ..] => rest,
+            _ => return Err(wd_error("unknown command", path)),
+        };
+        self.requests.push(MockRequest {
+            method: method.to_string(),
+            path: rest.join("/"),
+            body: body.clone(),
+        });
+        let body = body.unwrap_or(serde_json::Value::Null);
+        if matches!(rest.last(), Some(last) if self.unsupported.contains(*last)) {
+            return Err(wd_error("unknown command", path));
+        }
+
+        let value = match (method, rest) {
+            (&hyper::Method::DELETE, []) => json!(null),
+            (&hyper::Method::POST, ["url"]) => {
+                self.url = body["url"].as_str().unwrap_or_default().to_string();
+                json!(null)
+            }
+            (&hyper::Method::GET, ["url"]) => json!(self.url),
+            (&hyper::Method::POST, ["refresh"]) => json!(null),
+            (&hyper::Method::GET, ["title"]) => json!(self.title),
+            (&hyper::Method::GET, ["cookie"]) => json!([]),
+            (&hyper::Method::POST, ["execute", "sync"]) => match &self.script_result {
+                Some(result) => result.clone(),
+                None => return Err(wd_error("unknown command", path)),
+            },
+            (&hyper::Method::POST, ["se", "log"]) => json!(std::mem::take(&mut self.console)),
+            (&hyper::Method::GET, ["timeouts"]) => json!(self.timeouts),
+            (&hyper::Method::POST, ["timeouts"]) => {
+                let update: Timeouts = serde_json::from_value(body)
+                    .map_err(|e| wd_error("invalid argument", &e.to_string()))?;
+                self.timeouts.implicit = update.implicit.or(self.timeouts.implicit);
+                self.timeouts.page_load = update.page_load.or(self.timeouts.page_load);
+                self.timeouts.script = update.script.or(self.timeouts.script);
+                json!(null)
+            }
+            (&hyper::Method::POST, [.., "element"]) => {
+                let found = self.find(&body);
+                match found.first() {
+                    Some(elt) => elt.clone(),
+                    None => return Err(wd_error("no such element", &body.to_string())),
+                }
+            }
+            (&hyper::Method::POST, [.., "elements"]) => json!(self.find(&body)),
+            (_, ["element", id, action @ ..]) => {
+                let elt = self
+                    .elements
+                    .get_mut(*id)
+                    .ok_or_else(|| wd_error("stale element reference", id))?;
+                match (method, action) {
+                    (&hyper::Method::GET, ["text"]) => json!(elt.text),
+                    (&hyper::Method::GET, ["name"]) => json!(elt.tag_name),
+                    (&hyper::Method::GET, ["attribute", name]) => json!(elt.attributes.get(*name)),
+                    (&hyper::Method::GET, ["displayed"]) => json!(elt.displayed),
+                    (&hyper::Method::GET, ["enabled"]) => json!(elt.enabled),
+                    (&hyper::Method::POST, ["click"]) => json!(null),
+                    (&hyper::Method::POST, ["clear"]) => {
+                        elt.attributes.remove("value");
+                        json!(null)
+                    }
+                    (&hyper::Method::POST, ["value"]) => {
+                        let typed = body["text"].as_str().unwrap_or_default();
+                        elt.attributes
+                            .entry("value".into())
+                            .or_default()
+                            .push_str(typed);
+                        json!(null)
+                    }
+                    _ => return Err(wd_error("unknown command", path)),
+                }
+            }
+            _ => return Err(wd_error("unknown command", path)),
+        };
+        Ok(value)
+    }
+
+    fn find(&self, body: &serde_json::Value) -> Vec<serde_json::Value> {
+        let key = serde_json::from_value::<By>(body.clone())
+            .map(|by| selector_key(&by))
+            .unwrap_or_default();
+        self.finds
+            .get(&key)
+            .map(|ids| ids.iter().map(|id| json!({ ELEMENT_KEY: id })).collect())
+            .unwrap_or_default()
+    }
+}
+
+// An error response, with the HTTP status the spec gives for `error`.
+fn wd_error(error: &'static str, what: &str) -> (StatusCode, &'static str, String) {
+    let status = match error {
+        "invalid argument" => StatusCode::BAD_REQUEST,
+        "unknown command"
+        | "no such element"
+        | "invalid session id"
+        | "stale element reference" => StatusCode::NOT_FOUND,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, error, format!("{}: {}", error, what))
+}
+
+impl Content {
+    async fn respond(&self, req: Request<Body>) -> Result<Response<Body>, io::Error> {
+        match self {
+            Content::Directory(files) => files.clone().serve(req).await,
+            Content::WebDriver(state) => {
+                let (parts, body) = req.into_parts();
+                let body = hyper::body::to_bytes(body)
+                    .await
+                    .map_err(io::Error::other)?;
+                let body = serde_json::from_slice(&body).ok();
+                let result =
+                    state
+                        .lock()
+                        .expect("unpoison")
+                        .handle(&parts.method, parts.uri.path(), body);
+                let (status, value) = match result {
+                    Ok(value) => (StatusCode::OK, value),
+                    Err((status, error, message)) => {
+                        (status, json!({ "error": error, "message": message }))
+                    }
+                };
+                let resp = Response::builder()
+                    .status(status)
+                    .header("Content-Type", "application/json; charset=utf-8")
+                    .body(Body::from(json!({ "value": value }).to_string()));
+                Ok(resp.expect("valid response"))
+            }
+            Content::Pages(pages) => {
+                let resp = match pages.get(req.uri().path()) {
+                    Some(html) => Response::builder()
+                        .header("Content-Type", "text/html; charset=utf-8")
+                        .body(Body::from(html.clone())),
+                    None => Response::builder()
+                        .status(StatusCode::NOT_FOUND)
+                        .body(Body::empty()),
+                };
+                Ok(resp.expect("valid response"))
+            }
+        }
+    }
+}
+
+impl Drop for StaticServer {
+    fn drop(&mut self) {
+        if let Some(tx) = self.shutdown.take() {
+            let _ = tx.send(());
+        }
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                error!("StaticServer thread for {} panicked", self.addr);
+            }
+        }
+    }
+}
+
+/// Start a browser session as configured by the environment; see
+/// [`from_env`](crate::from_env). Sessions are headless by default.
+#[cfg(any(feature = "chrome", feature = "gecko"))]
+pub fn start_session() -> Result<DriverHolder, Error> {
+    crate::environment::from_env()
+}
+
+/// Runs `test` against a freshly started session, as per [`start_session`].
+/// This also initialises `env_logger`, and if the test panics, saves
+/// artifacts (see [`Client::capture_artifacts`](../struct.Client.html#method.capture_artifacts))
+/// under a timestamped directory named for the test, in
+/// [`ArtifactSink::from_env`](crate::ArtifactSink::from_env).
+///
+/// This is what the `browser_test` attribute expands to.
+#[cfg(any(feature = "chrome", feature = "gecko"))]
+pub fn run_browser_test<R, F: FnOnce(&DriverHolder) -> R>(name: &str, test: F) -> R {
+    env_logger::try_init().unwrap_or_default();
+    let session = start_session().expect("start browser session");
+    let _guard = session.artifacts_on_panic(ArtifactSink::from_env().session_dir(name));
+    test(&session)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn serves_pages_until_dropped() {
+        let server = StaticServer::with_pages(vec![("/", "<p>Hi</p>")]).expect("start");
+        let url = server.url();
+
+        let mut resp = reqwest::get(&url).expect("get");
+        assert!(resp.status().is_success());
+        let mut body = String::new();
+        resp.read_to_string(&mut body).expect("read");
+        assert_eq!(body, "<p>Hi</p>");
+
+        let missing = reqwest::get(&format!("{}missing", url)).expect("get");
+        assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+
+        drop(server);
+        assert!(reqwest::get(&url).is_err());
+    }
+
+    #[test]
+    fn serves_directories() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        std::fs::write(dir.path().join("index.html"), "<p>Index</p>").expect("write");
+        let server = StaticServer::serve_dir(dir.path()).expect("start");
+
+        let mut body = String::new();
+        reqwest::get(&server.url())
+            .expect("get")
+            .read_to_string(&mut body)
+            .expect("read");
+        assert_eq!(body, "<p>Index</p>");
+    }
+
+    #[test]
+    fn setting_one_timeout_leaves_the_others() {
+        let mock = MockWebDriver::start().expect("start");
+        let client = mock.client().expect("client");
+
+        client
+            .set_timeouts(&Timeouts::new().implicit(Duration::from_secs(5)))
+            .expect("set_timeouts");
+        client
+            .set_script_timeout(Duration::from_secs(30))
+            .expect("set_script_timeout");
+        assert_eq!(
+            client.timeouts().expect("timeouts"),
+            Timeouts::new()
+                .implicit(Duration::from_secs(5))
+                .script(Duration::from_secs(30))
+        );
+        let sent = mock
+            .requests()
+            .into_iter()
+            .filter(|req| req.method == "POST" && req.path == "timeouts")
+            .map(|req| req.body)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sent,
+            vec![
+                Some(json!({ "implicit": 5000 })),
+                Some(json!({ "script": 30000 }))
+            ]
+        );
+    }
+
+    #[test]
+    fn mock_webdriver_answers_canned_finds() {
+        let mock = MockWebDriver::start().expect("start");
+        mock.set_title("Mocked");
+        mock.on_find(
+            &By::css("input"),
+            vec![MockElement::new("input").attribute("name", "q")],
+        );
+        let client = mock.client().expect("client");
+
+        client.visit("http://example.com/").expect("visit");
+        assert_eq!(client.current_url().expect("url"), "http://example.com/");
+        assert_eq!(client.title().expect("title"), "Mocked");
+
+        let input = client.find_element(&By::css("input")).expect("find");
+        assert_eq!(client.name(&input).expect("name"), "input");
+        client.send_keys(&input, "hello").expect("send_keys");
+        assert_eq!(
+            client.attribute(&input, "value").expect("value"),
+            Some("hello".to_string())
+        );
+        client.click(&input).expect("click");
+        assert!(mock
+            .requests()
+            .iter()
+            .any(|r| r.method == "POST" && r.path == "element/mock-1/click"));
+
+        assert!(client.find_element(&By::css("button")).is_err());
+        assert!(client
+            .find_elements(&By::css("button"))
+            .expect("find_elements")
+            .is_empty());
+    }
+
+    #[test]
+    fn mock_webdriver_rejects_unknown_paths() {
+        let mock = MockWebDriver::start().expect("start");
+        let client = mock.client().expect("client");
+        let url = reqwest::Url::parse(&mock.url()).expect("url");
+        let http = reqwest::Client::new();
+        for (method, path) in &[
+            (reqwest::Method::GET, "sessions"),
+            (reqwest::Method::GET, ""),
+            (reqwest::Method::DELETE, "session"),
+        ] {
+            let resp = http
+                .request(method.clone(), url.join(path).expect("join"))
+                .send()
+                .expect("send");
+            assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND, "{}", path);
+        }
+        let resp = http
+            .post(url.join("session/mock-session/timeouts").expect("join"))
+            .json(&json!({ "implicit": "soon" }))
+            .send()
+            .expect("send");
+        assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST);
+
+        client.title().expect("still served");
+    }
+
+    #[test]
+    fn clones_delete_the_session_once() {
+        let mock = MockWebDriver::start().expect("start");
+        let deletes = || {
+            mock.requests()
+                .iter()
+                .filter(|r| r.method == "DELETE")
+                .count()
+        };
+        let client = mock.client().expect("client");
+        let clone = client.clone();
+
+        drop(client);
+        assert_eq!(deletes(), 0);
+        clone.title().expect("title");
+        drop(clone);
+        assert_eq!(deletes(), 1);
+
+        let mut client = mock.client().expect("client");
+        let clone = client.clone();
+        client.close().expect("close");
+        assert!(clone.title().is_err(), "Closing should affect clones");
+        drop(clone);
+        drop(client);
+        assert_eq!(deletes(), 2);
+
+        let client = mock.client().expect("client");
+        let closers = (0..4)
+            .map(|_| {
+                let mut clone = client.clone();
+                std::thread::spawn(move || clone.close().expect("close"))
+            })
+            .collect::<Vec<_>>();
+        for closer in closers {
+            closer.join().expect("join");
+        }
+        drop(client);
+        assert_eq!(deletes(), 3);
+    }
+
+    #[cfg(feature = "chrome")]
+    #[test]
+    fn attaches_to_a_running_driver() {
+        use crate::driver::Driver;
+
+        let mock = MockWebDriver::start().expect("start");
+        mock.set_title("Home");
+        let url = mock.url();
+        let mut driver =
+            crate::chrome::Driver::connect(url.trim_end_matches('/')).expect("connect");
+        let client = driver.new_session().expect("session");
+        assert_eq!(client.title().expect("title"), "Home");
+        drop(client);
+
+        driver.check_health().expect("healthy");
+        Driver::close(&mut driver).expect("close");
+        drop(driver);
+        mock.client().expect("driver left running");
+    }
+}
@@ -0,0 +1,144 @@
+//! A minimal HTTP/1.1 client over Unix domain sockets, for drivers (or
+//! proxies in front of them) that listen on a socket path rather than a TCP
+//! port. Each request uses a fresh connection.
+
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::time;
+
+use failure::{Error, ResultExt};
+
+use crate::http1;
+
+// How long to wait for the driver to accept a request or send a response,
+// as for reqwest's default timeout over TCP.
+const IO_TIMEOUT: time::Duration = time::Duration::from_secs(30);
+
+// A response, read in full.
+#[derive(Debug)]
+pub(crate) struct Response {
+    pub(crate) status: u16,
+    pub(crate) content_type: Option<String>,
+    pub(crate) body: Vec<u8>,
+}
+
+// Sends a request for `url` (of which only the path and query are used) to
+// the server listening on `socket`.
+pub(crate) fn request(
+    socket: &Path,
+    method: &http::Method,
+    url: &url::Url,
+    body: Option<&serde_json::Value>,
+) -> Result<Response, Error> {
+    let body = match body {
+        Some(body) => serde_json::to_vec(body)?,
+        None => Vec::new(),
+    };
+    let mut target = url.path().to_string();
+    if let Some(query) = url.query() {
+        target.push('?');
+        target.push_str(query);
+    }
+
+    // Connecting does not wait for the server to accept, and fails at once
+    // if nothing is listening, so only reads and writes need a timeout.
+    let mut stream =
+        UnixStream::connect(socket).with_context(|_| format!("Connecting to {:?}", socket))?;
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    let mut req = format!(
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nAccept: application/json\r\n",
+        method, target
+    );
+    if !body.is_empty() {
+        req.push_str("Content-Type: application/json; charset=utf-8\r\n");
+    }
+    req.push_str(&format!("Content-Length: {}\r\n\r\n", body.len()));
+    stream.write_all(req.as_bytes())?;
+    stream.write_all(&body)?;
+
+    let mut raw = Vec::new();
+    stream
+        .read_to_end(&mut raw)
+        .with_context(|_| format!("Reading response from {:?}", socket))?;
+    parse_response(&raw)
+}
+
+fn parse_response(raw: &[u8]) -> Result<Response, Error> {
+    let (head, body) =
+        http1::split_head(raw).ok_or_else(|| format_err!("Truncated HTTP response"))?;
+    let head = http1::parse_head(head)?;
+    let status = head
+        .start_line
+        .split(' ')
+        .nth(1)
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| format_err!("Bad HTTP status line: {:?}", head.start_line))?;
+
+    let mut body = body.to_vec();
+    if let Some(len) = head.content_length()? {
+        body.truncate(len);
+    }
+    if head.is_chunked() {
+        body = http1::dechunk(&body)?;
+    }
+
+    Ok(Response {
+        status,
+        content_type: head.header("content-type").map(str::to_string),
+        body,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+    use std::thread;
+
+    #[test]
+    fn sends_requests_over_the_socket() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("driver.sock");
+        let listener = UnixListener::bind(&path).expect("bind");
+        let server = thread::spawn(move || {
+            let (mut conn, _) = listener.accept().expect("accept");
+            let mut req = Vec::new();
+            let mut buf = [0; 1024];
+            while http1::find(&req, b"{\"a\":1}").is_none() {
+                let n = conn.read(&mut buf).expect("read");
+                req.extend_from_slice(&buf[..n]);
+            }
+            conn.write_all(
+                b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                  Transfer-Encoding: chunked\r\n\r\n\
+                  5\r\n{\"val\r\n9\r\nue\":null}\r\n0\r\n\r\n",
+            )
+            .expect("write");
+            String::from_utf8(req).expect("utf8")
+        });
+
+        let url = url::Url::parse("http://localhost/session?x=y").expect("url");
+        let resp =
+            request(&path, &http::Method::POST, &url, Some(&json!({"a": 1}))).expect("request");
+        assert_eq!(resp.status, 200);
+        assert_eq!(resp.content_type.as_deref(), Some("application/json"));
+        assert_eq!(resp.body, b"{\"value\":null}");
+
+        let req = server.join().expect("join");
+        assert!(req.starts_with("POST /session?x=y HTTP/1.1\r\n"), "{}", req);
+        assert!(req.contains("Content-Length: 7\r\n"), "{}", req);
+    }
+
+    #[test]
+    fn reads_sized_error_responses() {
+        let resp = parse_response(
+            b"HTTP/1.1 404 Not Found\r\ncontent-type: text/plain\r\ncontent-length: 4\r\n\r\nnope",
+        )
+        .expect("parse");
+        assert_eq!(resp.status, 404);
+        assert_eq!(resp.content_type.as_deref(), Some("text/plain"));
+        assert_eq!(resp.body, b"nope");
+    }
+}
@@ -0,0 +1,69 @@
+use std::fs;
+use std::io::{self, Write};
+use std::net::IpAddr;
+use std::path::Path;
+
+use failure::Error;
+use failure::ResultExt;
+use zip::write::{FileOptions, ZipWriter};
+
+// Packages the file at `path` as a zip archive containing just that file, as
+// expected by the remote `/se/file` upload endpoint.
+pub(crate) fn zip_file(path: &Path) -> Result<Vec<u8>, Error> {
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| format_err!("Path has no usable file name: {:?}", path))?;
+    let content = fs::read(path).with_context(|_| format!("Reading {:?}", path))?;
+
+    let mut zip = ZipWriter::new(io::Cursor::new(Vec::new()));
+    zip.start_file(name, FileOptions::default())?;
+    zip.write_all(&content)?;
+    let out = zip.finish()?;
+
+    Ok(out.into_inner())
+}
+
+// Whether the driver lives on this machine, and so can read local files.
+pub(crate) fn is_loopback(url: &url::Url) -> bool {
+    match url.host_str() {
+        None | Some("localhost") => true,
+        Some(host) => host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+            .map(|addr| addr.is_loopback())
+            .unwrap_or(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zips_single_file() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("upload.txt");
+        fs::write(&path, b"hello").expect("write");
+
+        let content = zip_file(&path).expect("zip");
+
+        let mut archive = zip::ZipArchive::new(io::Cursor::new(content)).expect("archive");
+        assert_eq!(archive.len(), 1);
+        let mut file = archive.by_index(0).expect("entry");
+        assert_eq!(file.name(), "upload.txt");
+        let mut read = Vec::new();
+        io::Read::read_to_end(&mut file, &mut read).expect("read");
+        assert_eq!(read, b"hello");
+    }
+
+    #[test]
+    fn detects_local_drivers() {
+        let local = |u: &str| is_loopback(&url::Url::parse(u).expect("url"));
+        assert!(local("http://127.0.0.1:4444/"));
+        assert!(local("http://localhost:4444/"));
+        assert!(local("http://[::1]:4444/"));
+        assert!(!local("http://grid.example.com:4444/wd/hub/"));
+    }
+}
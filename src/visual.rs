@@ -0,0 +1,292 @@
+//! Basic visual regression testing, by comparing screenshots pixel by pixel.
+//!
+//! ```no_run
+//! # fn example(client: &sulfur::Client) -> Result<(), failure::Error> {
+//! use std::path::Path;
+//!
+//! let screenshot = client.screenshot()?;
+//! sulfur::visual::check_golden(&screenshot, Path::new("tests/golden/home.png"), 8)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+use std::env;
+use std::path::{Path, PathBuf};
+
+use failure::Error;
+use failure::ResultExt;
+use image::{Rgba, RgbaImage};
+
+use crate::screenshot::Screenshot;
+
+/// When set in the environment, [`check_golden`] overwrites golden files
+/// with the current screenshot, rather than comparing against them.
+pub const UPDATE_GOLDEN_ENV: &str = "SULFUR_UPDATE_GOLDEN";
+
+const DIFF_HIGHLIGHT: Rgba<u8> = Rgba([255, 0, 0, 255]);
+
+/// A rectangular region of an image, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    /// Left edge.
+    pub x: u32,
+    /// Top edge.
+    pub y: u32,
+    /// Width.
+    pub width: u32,
+    /// Height.
+    pub height: u32,
+}
+
+/// The outcome of comparing two screenshots; see [`diff`].
+#[derive(Debug, Clone)]
+pub struct DiffReport {
+    /// The number of pixels that differ by more than the tolerance.
+    pub differing_pixels: u64,
+    /// The number of pixels in each image.
+    pub total_pixels: u64,
+    /// The bounding box of each contiguous group of differing pixels.
+    pub regions: Vec<Region>,
+    /// A copy of the second image, faded, with differing pixels
+    /// highlighted. Only present if anything differs.
+    pub diff_image: Option<RgbaImage>,
+}
+
+impl DiffReport {
+    /// Whether the images matched, within tolerance.
+    pub fn is_match(&self) -> bool {
+        self.differing_pixels == 0
+    }
+}
+
+/// Compare two screenshots of the same size. Pixels are considered equal
+/// if no channel differs by more than `tolerance`.
+pub fn diff(
+    expected: &Screenshot,
+    actual: &Screenshot,
+    tolerance: u8,
+) -> Result<DiffReport, Error> {
+    let expected = expected.to_image()?.to_rgba8();
+    let actual = actual.to_image()?.to_rgba8();
+    if expected.dimensions() != actual.dimensions() {
+        bail!(
+            "Screenshots differ in size: expected {:?}, got {:?}",
+            expected.dimensions(),
+            actual.dimensions()
+        );
+    }
+
+    let (width, height) = actual.dimensions();
+    let mut differs = vec![false; width as usize * height as usize];
+    let mut differing_pixels = 0;
+    for (x, y, pixel) in actual.enumerate_pixels() {
+        let other = expected.get_pixel(x, y);
+        let distance = pixel
+            .0
+            .iter()
+            .zip(other.0.iter())
+            .map(|(a, b)| (i16::from(*a) - i16::from(*b)).abs())
+            .max()
+            .unwrap_or(0);
+        if distance > i16::from(tolerance) {
+            differs[(y * width + x) as usize] = true;
+            differing_pixels += 1;
+        }
+    }
+
+    let diff_image = if differing_pixels > 0 {
+        Some(highlight(&actual, &differs))
+    } else {
+        None
+    };
+
+    Ok(DiffReport {
+        differing_pixels,
+        total_pixels: u64::from(width) * u64::from(height),
+        regions: regions(width, height, &differs),
+        diff_image,
+    })
+}
+
+/// Compare `actual` to the golden image stored at `path`, failing if they
+/// differ. On failure, the actual and diff images are written alongside the
+/// golden file, as `<name>.actual.png` and `<name>.diff.png`.
+///
+/// If the golden file does not exist, or [`UPDATE_GOLDEN_ENV`] is set, then
+/// `actual` is saved as the new golden image instead.
+pub fn check_golden(actual: &Screenshot, path: &Path, tolerance: u8) -> Result<(), Error> {
+    if !path.exists() || env::var_os(UPDATE_GOLDEN_ENV).is_some() {
+        info!("Saving golden image to {:?}", path);
+        return actual.save(path);
+    }
+
+    let expected = Screenshot::from_png(
+        std::fs::read(path).with_context(|_| format!("Reading golden image {:?}", path))?,
+    );
+    let report = diff(&expected, actual, tolerance)?;
+    if report.is_match() {
+        return Ok(());
+    }
+
+    let actual_path = sibling(path, "actual");
+    actual.save(&actual_path)?;
+    let diff_path = sibling(path, "diff");
+    if let Some(img) = &report.diff_image {
+        img.save(&diff_path)
+            .with_context(|_| format!("Writing diff image to {:?}", diff_path))?;
+    }
+
+    bail!(
+        "Screenshot differs from {:?} in {} of {} pixels, in {:?}; see {:?} and {:?}",
+        path,
+        report.differing_pixels,
+        report.total_pixels,
+        report.regions,
+        actual_path,
+        diff_path
+    )
+}
+
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!("{}.{}.png", stem, suffix))
+}
+
+fn highlight(img: &RgbaImage, differs: &[bool]) -> RgbaImage {
+    let width = img.width();
+    let mut out = img.clone();
+    for (x, y, pixel) in out.enumerate_pixels_mut() {
+        if differs[(y * width + x) as usize] {
+            *pixel = DIFF_HIGHLIGHT;
+        } else {
+            for c in pixel.0.iter_mut().take(3) {
+                *c = 192 + *c / 4;
+            }
+        }
+    }
+    out
+}
+
+// Finds the bounding boxes of the 8-connected groups of differing pixels.
+fn regions(width: u32, height: u32, differs: &[bool]) -> Vec<Region> {
+    let mut seen = vec![false; differs.len()];
+    let mut regions = Vec::new();
+    let mut queue = VecDeque::new();
+
+    for start in 0..differs.len() {
+        if !differs[start] || seen[start] {
+            continue;
+        }
+        seen[start] = true;
+        queue.push_back(start);
+        let (mut min_x, mut min_y) = (width, height);
+        let (mut max_x, mut max_y) = (0, 0);
+
+        while let Some(idx) = queue.pop_front() {
+            let (x, y) = (idx as u32 % width, idx as u32 / width);
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+
+            for ny in y.saturating_sub(1)..=(y + 1).min(height - 1) {
+                for nx in x.saturating_sub(1)..=(x + 1).min(width - 1) {
+                    let n = (ny * width + nx) as usize;
+                    if differs[n] && !seen[n] {
+                        seen[n] = true;
+                        queue.push_back(n);
+                    }
+                }
+            }
+        }
+
+        regions.push(Region {
+            x: min_x,
+            y: min_y,
+            width: max_x - min_x + 1,
+            height: max_y - min_y + 1,
+        });
+    }
+
+    regions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screenshot(img: &RgbaImage) -> Screenshot {
+        let mut png = Vec::new();
+        image::DynamicImage::ImageRgba8(img.clone())
+            .write_to(&mut png, image::ImageOutputFormat::Png)
+            .expect("encode");
+        Screenshot::from_png(png)
+    }
+
+    #[test]
+    fn finds_differing_regions() {
+        let white = RgbaImage::from_pixel(10, 10, Rgba([255, 255, 255, 255]));
+        let mut changed = white.clone();
+        // A slight change, within tolerance.
+        changed.put_pixel(0, 0, Rgba([250, 250, 250, 255]));
+        // Two separate blobs.
+        changed.put_pixel(1, 1, Rgba([0, 0, 0, 255]));
+        changed.put_pixel(2, 2, Rgba([0, 0, 0, 255]));
+        changed.put_pixel(7, 8, Rgba([0, 0, 0, 255]));
+
+        let same = diff(&screenshot(&white), &screenshot(&white), 0).expect("diff");
+        assert!(same.is_match());
+        assert!(same.diff_image.is_none());
+
+        let report = diff(&screenshot(&white), &screenshot(&changed), 8).expect("diff");
+        assert_eq!(report.differing_pixels, 3);
+        assert_eq!(report.total_pixels, 100);
+        assert_eq!(
+            report.regions,
+            vec![
+                Region {
+                    x: 1,
+                    y: 1,
+                    width: 2,
+                    height: 2
+                },
+                Region {
+                    x: 7,
+                    y: 8,
+                    width: 1,
+                    height: 1
+                },
+            ]
+        );
+        let diff_image = report.diff_image.expect("diff image");
+        assert_eq!(*diff_image.get_pixel(7, 8), DIFF_HIGHLIGHT);
+    }
+
+    #[test]
+    fn rejects_mismatched_sizes() {
+        let small = RgbaImage::new(2, 2);
+        let large = RgbaImage::new(3, 2);
+        assert!(diff(&screenshot(&small), &screenshot(&large), 0).is_err());
+    }
+
+    #[test]
+    fn golden_files() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let golden = dir.path().join("page.png");
+        let white = RgbaImage::from_pixel(4, 4, Rgba([255, 255, 255, 255]));
+        let mut changed = white.clone();
+        changed.put_pixel(3, 3, Rgba([0, 0, 0, 255]));
+
+        check_golden(&screenshot(&white), &golden, 0).expect("first run saves");
+        assert!(golden.exists());
+        check_golden(&screenshot(&white), &golden, 0).expect("matches");
+
+        assert!(check_golden(&screenshot(&changed), &golden, 0).is_err());
+        assert!(dir.path().join("page.actual.png").exists());
+        assert!(dir.path().join("page.diff.png").exists());
+    }
+}
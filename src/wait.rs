@@ -0,0 +1,375 @@
+//! Explicit waits, for polling the browser until some condition holds.
+//!
+//! ```no_run
+//! # fn example(client: &sulfur::Client) -> Result<(), failure::Error> {
+//! use std::time::Duration;
+//! use sulfur::{wait::conditions, By};
+//!
+//! let elt = client
+//!     .wait()
+//!     .at_most(Duration::from_secs(5))
+//!     .every(Duration::from_millis(50))
+//!     .until(conditions::element_present(&By::css(".x")))?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::{thread, time};
+
+use failure::{Error, ResultExt};
+
+use crate::client::{Client, ErrorKind, WdError};
+
+const DEFAULT_TIMEOUT: time::Duration = time::Duration::from_secs(10);
+const DEFAULT_INTERVAL: time::Duration = time::Duration::from_millis(100);
+// The longest pause between checks in `wait_until`.
+const MAX_BACKOFF: time::Duration = time::Duration::from_millis(500);
+
+/// Something we can wait for. A condition is checked repeatedly, until it
+/// returns `Some` value.
+pub trait Condition<T> {
+    /// Check whether the condition holds, and if so, return the result.
+    fn check(&mut self, client: &Client) -> Result<Option<T>, Error>;
+}
+
+impl<T, F> Condition<T> for F
+where
+    F: FnMut(&Client) -> Result<Option<T>, Error>,
+{
+    fn check(&mut self, client: &Client) -> Result<Option<T>, Error> {
+        self(client)
+    }
+}
+
+/// Polls a [`Condition`] until it holds, or the timeout elapses.
+#[derive(Debug, Clone)]
+pub struct Wait<'a> {
+    client: &'a Client,
+    timeout: time::Duration,
+    interval: time::Duration,
+}
+
+impl<'a> Wait<'a> {
+    pub(crate) fn new(client: &'a Client) -> Self {
+        Wait {
+            client,
+            timeout: DEFAULT_TIMEOUT,
+            interval: DEFAULT_INTERVAL,
+        }
+    }
+
+    /// How long to wait before giving up.
+    pub fn at_most(mut self, timeout: time::Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// How long to pause between checks.
+    pub fn every(mut self, interval: time::Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Check `condition` until it holds, and return the result. Fails with a
+    /// `timeout` [`WdError`] if it does not hold before the deadline.
+    pub fn until<T, C: Condition<T>>(&self, mut condition: C) -> Result<T, Error> {
+        let started_at = time::Instant::now();
+        loop {
+            if let Some(result) = condition.check(self.client)? {
+                return Ok(result);
+            }
+            let elapsed = started_at.elapsed();
+            if elapsed >= self.timeout {
+                return Err(WdError::new(
+                    ErrorKind::Timeout,
+                    format!("Condition did not hold after {:?}", self.timeout),
+                )
+                .into());
+            }
+            let pause = self.interval.min(self.timeout - elapsed);
+            debug!("Pausing for {:?}", pause);
+            thread::sleep(pause);
+        }
+    }
+}
+
+/// Repeatedly call `check` with exponential backoff (up to half a second
+/// between checks) until it returns `true` or `deadline` elapses. Returns the
+/// result of the final check, which is made once the deadline has passed.
+pub fn wait_until<F: FnMut() -> Result<bool, Error>>(
+    deadline: time::Duration,
+    mut check: F,
+) -> Result<bool, Error> {
+    let mut pause_time = time::Duration::from_millis(1);
+    let started_at = time::Instant::now();
+    loop {
+        if check()? {
+            return Ok(true);
+        }
+        let elapsed = started_at.elapsed();
+        if elapsed >= deadline {
+            return Ok(false);
+        }
+        let pause = pause_time.min(deadline - elapsed);
+        debug!("Pausing for {:?}", pause);
+        thread::sleep(pause);
+        pause_time = (pause_time * 2).min(MAX_BACKOFF);
+    }
+}
+
+// Suffixes used by browsers for downloads that are still in progress.
+const PARTIAL_DOWNLOAD_SUFFIXES: &[&str] = &[".part", ".crdownload", ".tmp"];
+
+/// Watches a directory for a new download, eg: one configured via
+/// `chrome::Config::download_dir` or `gecko::Config::download_dir`. Create
+/// the watcher before the action that starts the download, so that files
+/// which arrive quickly are not mistaken for ones that were already there.
+///
+/// ```no_run
+/// # fn example(client: &sulfur::Client, link: &sulfur::Element) -> Result<(), failure::Error> {
+/// use std::time::Duration;
+///
+/// let download = sulfur::wait::before_download("target/downloads")?;
+/// client.click(link)?;
+/// let path = download.wait(Duration::from_secs(10))?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct DownloadWatcher {
+    dir: PathBuf,
+    known: Vec<PathBuf>,
+}
+
+/// Note the files already in `dir`, so that a later
+/// [`DownloadWatcher::wait`] can find the one that is downloaded next.
+pub fn before_download<P: AsRef<Path>>(dir: P) -> Result<DownloadWatcher, Error> {
+    let dir = dir.as_ref().to_path_buf();
+    let known = list_files(&dir)?;
+    Ok(DownloadWatcher { dir, known })
+}
+
+impl DownloadWatcher {
+    /// Wait for a download to complete, and return the path of the file. A
+    /// download is complete once there are no partial downloads in the
+    /// directory, and it contains a file that was not present when the
+    /// watcher was created.
+    pub fn wait(&self, timeout: time::Duration) -> Result<PathBuf, Error> {
+        let mut downloaded = None;
+        wait_until(timeout, || {
+            downloaded = self.completed()?;
+            Ok(downloaded.is_some())
+        })?;
+        downloaded.ok_or_else(|| {
+            WdError::new(
+                ErrorKind::Timeout,
+                format!(
+                    "No download completed in {:?} after {:?}",
+                    self.dir, timeout
+                ),
+            )
+            .into()
+        })
+    }
+
+    fn completed(&self) -> Result<Option<PathBuf>, Error> {
+        let files = list_files(&self.dir)?;
+        let partial = files.iter().any(|f| {
+            let name = f.to_string_lossy();
+            PARTIAL_DOWNLOAD_SUFFIXES.iter().any(|s| name.ends_with(s))
+        });
+        if partial {
+            return Ok(None);
+        }
+        Ok(files.into_iter().find(|f| !self.known.contains(f)))
+    }
+}
+
+fn list_files(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|_| format!("Listing {:?}", dir))? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            files.push(entry.path());
+        }
+    }
+    Ok(files)
+}
+
+/// Standard conditions for use with [`Wait::until`].
+pub mod conditions {
+    use failure::Error;
+
+    use crate::client::{By, Client, Element, ErrorKind, WdError};
+
+    /// Holds once an element matching `by` is present in the document.
+    pub fn element_present(by: &By) -> impl FnMut(&Client) -> Result<Option<Element>, Error> {
+        let by = by.clone();
+        move |client| Ok(client.find_elements(&by)?.into_iter().next())
+    }
+
+    /// Holds once an element matching `by` is present and displayed.
+    pub fn element_visible(by: &By) -> impl FnMut(&Client) -> Result<Option<Element>, Error> {
+        let by = by.clone();
+        move |client| {
+            for elt in client.find_elements(&by)? {
+                if ignore_stale(client.is_displayed(&elt))? == Some(true) {
+                    return Ok(Some(elt));
+                }
+            }
+            Ok(None)
+        }
+    }
+
+    /// Holds once an element matching `by` is displayed and enabled, so
+    /// can be clicked on.
+    pub fn element_clickable(by: &By) -> impl FnMut(&Client) -> Result<Option<Element>, Error> {
+        let by = by.clone();
+        move |client| {
+            for elt in client.find_elements(&by)? {
+                let displayed = ignore_stale(client.is_displayed(&elt))?;
+                let enabled = ignore_stale(client.is_enabled(&elt))?;
+                if displayed == Some(true) && enabled == Some(true) {
+                    return Ok(Some(elt));
+                }
+            }
+            Ok(None)
+        }
+    }
+
+    /// Holds once `elt` has been removed from the document.
+    pub fn staleness_of(elt: &Element) -> impl FnMut(&Client) -> Result<Option<()>, Error> {
+        let elt = elt.clone();
+        move |client| match ignore_stale(client.name(&elt))? {
+            Some(_) => Ok(None),
+            None => Ok(Some(())),
+        }
+    }
+
+    /// Holds once the page title is exactly `title`.
+    pub fn title_is<S: Into<String>>(title: S) -> impl FnMut(&Client) -> Result<Option<()>, Error> {
+        let title = title.into();
+        move |client| {
+            let current = client.title()?;
+            Ok(Some(()).filter(|_| current == title))
+        }
+    }
+
+    /// Holds once the page title contains `fragment`.
+    pub fn title_contains<S: Into<String>>(
+        fragment: S,
+    ) -> impl FnMut(&Client) -> Result<Option<String>, Error> {
+        let fragment = fragment.into();
+        move |client| Ok(Some(client.title()?).filter(|t| t.contains(&fragment)))
+    }
+
+    /// Holds once the page title satisfies `predicate`.
+    pub fn title_matches<P: FnMut(&str) -> bool>(
+        mut predicate: P,
+    ) -> impl FnMut(&Client) -> Result<Option<String>, Error> {
+        move |client| Ok(Some(client.title()?).filter(|t| predicate(t)))
+    }
+
+    /// Holds once the current URL contains `fragment`.
+    pub fn url_contains<S: Into<String>>(
+        fragment: S,
+    ) -> impl FnMut(&Client) -> Result<Option<String>, Error> {
+        let fragment = fragment.into();
+        move |client| Ok(Some(client.current_url()?).filter(|u| u.contains(&fragment)))
+    }
+
+    /// Holds once the current URL satisfies `predicate`.
+    pub fn url_matches<P: FnMut(&str) -> bool>(
+        mut predicate: P,
+    ) -> impl FnMut(&Client) -> Result<Option<String>, Error> {
+        move |client| Ok(Some(client.current_url()?).filter(|u| predicate(u)))
+    }
+
+    // Treats a stale element reference as `None`, as the element has gone
+    // away between finding it and querying it.
+    fn ignore_stale<T>(res: Result<T, Error>) -> Result<Option<T>, Error> {
+        match res {
+            Ok(val) => Ok(Some(val)),
+            Err(e) => match e.downcast_ref::<WdError>() {
+                Some(wd) if wd.kind() == ErrorKind::StaleElementReference => Ok(None),
+                _ => Err(e),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wait_until_stops_at_the_deadline() {
+        let deadline = time::Duration::from_millis(300);
+        let started = time::Instant::now();
+        let mut checks = 0;
+        let held = wait_until(deadline, || {
+            checks += 1;
+            Ok(false)
+        })
+        .expect("wait_until");
+        let elapsed = started.elapsed();
+
+        assert!(!held);
+        assert!(elapsed >= deadline, "{:?}", elapsed);
+        // Doubling pauses without a limit would sleep until 511ms.
+        assert!(elapsed < deadline + deadline / 3, "{:?}", elapsed);
+        assert!(checks > 2, "{}", checks);
+    }
+
+    #[test]
+    fn waits_for_partial_downloads_to_finish() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        fs::write(dir.path().join("old.txt"), b"old").expect("write");
+        let partial = dir.path().join("report.txt.part");
+        fs::write(&partial, b"rep").expect("write");
+
+        let download = before_download(dir.path()).expect("before_download");
+        let finished = dir.path().join("report.txt");
+        let writer = {
+            let (partial, finished) = (partial.clone(), finished.clone());
+            thread::spawn(move || {
+                thread::sleep(time::Duration::from_millis(200));
+                fs::write(&finished, b"report").expect("write");
+                thread::sleep(time::Duration::from_millis(200));
+                fs::remove_file(&partial).expect("remove");
+            })
+        };
+
+        let path = download.wait(time::Duration::from_secs(5)).expect("wait");
+        assert_eq!(path, finished);
+        assert!(!partial.exists());
+        writer.join().expect("join");
+    }
+
+    #[test]
+    fn finds_downloads_that_finish_before_the_wait() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        fs::write(dir.path().join("old.txt"), b"old").expect("write");
+        let download = before_download(dir.path()).expect("before_download");
+        let finished = dir.path().join("report.txt");
+        fs::write(&finished, b"report").expect("write");
+
+        let path = download.wait(time::Duration::from_secs(1)).expect("wait");
+        assert_eq!(path, finished);
+    }
+
+    #[test]
+    fn times_out_without_a_download() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let err = before_download(dir.path())
+            .expect("before_download")
+            .wait(time::Duration::from_millis(50))
+            .expect_err("nothing downloaded");
+        assert_eq!(
+            err.downcast_ref::<WdError>().map(WdError::kind),
+            Some(ErrorKind::Timeout)
+        );
+    }
+}
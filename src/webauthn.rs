@@ -0,0 +1,186 @@
+//! Types for the [WebAuthn](https://www.w3.org/TR/webauthn-2/#sctn-automation)
+//! virtual authenticator extension commands; see
+//! [`Client::add_virtual_authenticator`](crate::Client::add_virtual_authenticator).
+
+/// Handle for a virtual authenticator.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Authenticator(pub(crate) String);
+
+/// The protocol a virtual authenticator speaks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Protocol {
+    /// CTAP2, ie: FIDO2 / passkeys.
+    #[serde(rename = "ctap2")]
+    Ctap2,
+    /// CTAP1, ie: U2F.
+    #[serde(rename = "ctap1/u2f")]
+    Ctap1U2f,
+}
+
+/// How a virtual authenticator is attached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Transport {
+    /// A USB security key.
+    Usb,
+    /// An NFC security key.
+    Nfc,
+    /// A bluetooth security key.
+    Ble,
+    /// Built in to the device, eg: a fingerprint reader.
+    Internal,
+}
+
+/// Configuration for a new virtual authenticator.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthenticatorOptions {
+    protocol: Protocol,
+    transport: Transport,
+    has_resident_key: bool,
+    has_user_verification: bool,
+    is_user_consenting: bool,
+    is_user_verified: bool,
+}
+
+/// A credential stored on a virtual authenticator.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Credential {
+    /// The credential ID.
+    #[serde(with = "base64url")]
+    pub credential_id: Vec<u8>,
+    /// Whether this is a client-side discoverable (resident) credential.
+    pub is_resident_credential: bool,
+    /// The relying party this credential is scoped to, eg: `example.com`.
+    /// Required when adding resident credentials.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rp_id: Option<String>,
+    /// The private key, as a PKCS#8 encoded DER document.
+    #[serde(with = "base64url")]
+    pub private_key: Vec<u8>,
+    /// The user handle associated with the credential.
+    #[serde(
+        default,
+        with = "base64url_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub user_handle: Option<Vec<u8>>,
+    /// The signature counter.
+    pub sign_count: u64,
+}
+
+impl Default for AuthenticatorOptions {
+    fn default() -> Self {
+        AuthenticatorOptions {
+            protocol: Protocol::Ctap2,
+            transport: Transport::Usb,
+            has_resident_key: false,
+            has_user_verification: false,
+            is_user_consenting: true,
+            is_user_verified: false,
+        }
+    }
+}
+
+impl AuthenticatorOptions {
+    /// The protocol to speak; defaults to CTAP2.
+    pub fn protocol(&mut self, protocol: Protocol) -> &mut Self {
+        self.protocol = protocol;
+        self
+    }
+
+    /// How the authenticator is attached; defaults to USB.
+    pub fn transport(&mut self, transport: Transport) -> &mut Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Whether the authenticator can store resident credentials.
+    pub fn has_resident_key(&mut self, has_resident_key: bool) -> &mut Self {
+        self.has_resident_key = has_resident_key;
+        self
+    }
+
+    /// Whether the authenticator supports user verification, eg: a PIN.
+    pub fn has_user_verification(&mut self, has_user_verification: bool) -> &mut Self {
+        self.has_user_verification = has_user_verification;
+        self
+    }
+
+    /// Whether the user consents to operations, eg: by touching the key.
+    /// Defaults to `true`.
+    pub fn is_user_consenting(&mut self, is_user_consenting: bool) -> &mut Self {
+        self.is_user_consenting = is_user_consenting;
+        self
+    }
+
+    /// Whether user verification succeeds.
+    pub fn is_user_verified(&mut self, is_user_verified: bool) -> &mut Self {
+        self.is_user_verified = is_user_verified;
+        self
+    }
+}
+
+mod base64url {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], ser: S) -> Result<S::Ok, S::Error> {
+        ser.serialize_str(&base64::encode_config(bytes, base64::URL_SAFE_NO_PAD))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(de: D) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(de)?;
+        base64::decode_config(&s, base64::URL_SAFE_NO_PAD).map_err(serde::de::Error::custom)
+    }
+}
+
+mod base64url_opt {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &Option<Vec<u8>>, ser: S) -> Result<S::Ok, S::Error> {
+        match bytes {
+            Some(bytes) => super::base64url::serialize(bytes, ser),
+            None => ser.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(de: D) -> Result<Option<Vec<u8>>, D::Error> {
+        Option::<String>::deserialize(de)?
+            .map(|s| {
+                base64::decode_config(&s, base64::URL_SAFE_NO_PAD).map_err(serde::de::Error::custom)
+            })
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn credentials_use_base64url() {
+        let cred = Credential {
+            credential_id: vec![0xfb, 0xff],
+            is_resident_credential: true,
+            rp_id: Some("example.com".into()),
+            private_key: vec![1, 2, 3],
+            user_handle: Some(b"user".to_vec()),
+            sign_count: 0,
+        };
+        let json = serde_json::to_value(&cred).expect("serialize");
+        assert_eq!(
+            json,
+            json!({
+                "credentialId": "-_8",
+                "isResidentCredential": true,
+                "rpId": "example.com",
+                "privateKey": "AQID",
+                "userHandle": "dXNlcg",
+                "signCount": 0,
+            })
+        );
+        let parsed: Credential = serde_json::from_value(json).expect("parse");
+        assert_eq!(parsed, cred);
+    }
+}
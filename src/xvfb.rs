@@ -0,0 +1,151 @@
+//! Runs browsers with their UI shown (ie: not headless) on machines without
+//! a display, eg: Linux CI, by starting a virtual X server.
+//!
+//! ```no_run
+//! # #[cfg(feature = "chrome")]
+//! # fn example() -> Result<(), failure::Error> {
+//! use sulfur::{chrome, xvfb::Xvfb, DriverHolder};
+//!
+//! let xvfb = Xvfb::start()?;
+//! let driver = chrome::Driver::driver_config(
+//!     chrome::DriverConfig::default().env("DISPLAY", xvfb.display()),
+//! )?;
+//! let client = driver.new_session()?;
+//! let holder = DriverHolder::new(Box::new(driver), client);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time;
+
+use failure::{Error, ResultExt};
+
+use crate::process::{self, DEFAULT_GRACE_PERIOD};
+use crate::wait::wait_until;
+
+const START_TIMEOUT: time::Duration = time::Duration::from_secs(10);
+const DEFAULT_SCREEN: &str = "1920x1080x24";
+
+/// A running `Xvfb` server, which is stopped when dropped.
+#[derive(Debug)]
+pub struct Xvfb {
+    child: Child,
+    display: u32,
+}
+
+impl Xvfb {
+    /// Start `Xvfb` on a free display, with a single 1920x1080 screen. The
+    /// server picks the display itself, so servers started in parallel (eg:
+    /// by concurrent tests) do not race for the same one.
+    pub fn start() -> Result<Self, Error> {
+        let mut cmd = Command::new("Xvfb");
+        // Xvfb writes the display number to stdout once it is ready.
+        cmd.args(["-displayfd", "1", "-screen", "0", DEFAULT_SCREEN])
+            .args(["-nolisten", "tcp"])
+            .stdout(Stdio::piped());
+        debug!("Starting command: {:?}", cmd);
+        let mut child = process::spawn(&mut cmd).context("Spawning Xvfb")?;
+        let stdout = child.stdout.take().expect("piped stdout");
+        let mut xvfb = Xvfb { child, display: 0 };
+
+        let (tx, rx) = mpsc::channel();
+        thread::Builder::new()
+            .name("xvfb-displayfd".into())
+            .spawn(move || {
+                let mut stdout = BufReader::new(stdout);
+                let mut line = String::new();
+                let _ = tx.send(stdout.read_line(&mut line).map(|_| line));
+                // Keep reading, so that Xvfb never writes to a closed pipe.
+                let _ = io::copy(&mut stdout, &mut io::sink());
+            })?;
+        let line = match rx.recv_timeout(START_TIMEOUT) {
+            Ok(line) => line.context("Reading the display number from Xvfb")?,
+            Err(_) => bail!("Xvfb did not start within {:?}", START_TIMEOUT),
+        };
+        xvfb.display = match line.trim().parse() {
+            Ok(display) => display,
+            Err(_) if process::has_exited(&mut xvfb.child)? => bail!("Xvfb exited on startup"),
+            Err(_) => bail!("Unexpected output from Xvfb: {:?}", line),
+        };
+        info!("Xvfb running on :{}", xvfb.display);
+        Ok(xvfb)
+    }
+
+    /// Start `Xvfb` on the given display number, with a screen of the given
+    /// geometry, eg: `1280x720x24`.
+    pub fn start_on(display: u32, screen: &str) -> Result<Self, Error> {
+        let mut cmd = Command::new("Xvfb");
+        cmd.arg(format!(":{}", display))
+            .args(["-screen", "0", screen, "-nolisten", "tcp"])
+            .stdout(Stdio::null());
+        debug!("Starting command: {:?}", cmd);
+        let child = process::spawn(&mut cmd).context("Spawning Xvfb")?;
+        let mut xvfb = Xvfb { child, display };
+
+        let socket = Path::new("/tmp/.X11-unix").join(format!("X{}", display));
+        let started = wait_until(START_TIMEOUT, || {
+            if process::has_exited(&mut xvfb.child)? {
+                bail!("Xvfb on :{} exited on startup", display);
+            }
+            Ok(socket.exists())
+        })?;
+        if !started {
+            bail!(
+                "Xvfb on :{} did not start within {:?}",
+                display,
+                START_TIMEOUT
+            );
+        }
+        info!("Xvfb running on :{}", display);
+        Ok(xvfb)
+    }
+
+    /// The display name, eg: `:99`, for use as `$DISPLAY`.
+    pub fn display(&self) -> String {
+        format!(":{}", self.display)
+    }
+}
+
+impl Drop for Xvfb {
+    fn drop(&mut self) {
+        if let Err(e) = process::terminate(&mut self.child, DEFAULT_GRACE_PERIOD) {
+            error!("Stopping Xvfb on :{}: {:?}", self.display, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lock_file(display: u32) -> std::path::PathBuf {
+        Path::new("/tmp").join(format!(".X{}-lock", display))
+    }
+
+    #[test]
+    fn starts_and_stops_displays() {
+        if Command::new("Xvfb").arg("-help").output().is_err() {
+            info!("Xvfb is not installed; skipping");
+            return;
+        }
+        let starts = (0..2)
+            .map(|_| thread::spawn(|| Xvfb::start().expect("start")))
+            .collect::<Vec<_>>();
+        let servers = starts
+            .into_iter()
+            .map(|start| start.join().expect("join"))
+            .collect::<Vec<_>>();
+        assert_ne!(servers[0].display, servers[1].display);
+        for xvfb in servers {
+            let display = xvfb.display;
+            assert!(lock_file(display).exists());
+            drop(xvfb);
+            assert!(!lock_file(display).exists());
+        }
+    }
+}
@@ -0,0 +1,187 @@
+//! Procedural macros for [sulfur](https://docs.rs/sulfur). These are
+//! re-exported from `sulfur` itself when its `macros` feature is enabled, so
+//! you should not usually need to depend on this crate directly.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, ItemFn, Lit, Meta, NestedMeta};
+
+// The `By` constructors that may be used in `#[by(...)]` attributes.
+const LOCATORS: &[&str] = &[
+    "css",
+    "xpath",
+    "link_text",
+    "partial_link_text",
+    "tag_name",
+    "id",
+    "name",
+    "class_name",
+    "text",
+    "contains_text",
+];
+
+/// Marks a function taking a single `&DriverHolder` as a browser test.
+///
+/// The test is run against a freshly started headless browser, chosen via
+/// `sulfur::testing::start_session`, and artifacts are captured if it
+/// panics. See `sulfur::testing::run_browser_test` for details.
+///
+/// ```ignore
+/// #[sulfur::browser_test]
+/// fn shows_a_title(session: &sulfur::DriverHolder) -> Result<(), failure::Error> {
+///     session.visit("https://example.com/")?;
+///     assert_eq!(session.title()?, "Example Domain");
+///     Ok(())
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn browser_test(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as ItemFn);
+    match expand(attr.into(), input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand(
+    attr: proc_macro2::TokenStream,
+    input: ItemFn,
+) -> Result<proc_macro2::TokenStream, Error> {
+    if !attr.is_empty() {
+        return Err(Error::new(attr.span(), "browser_test takes no arguments"));
+    }
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = input;
+    if let Some(asyncness) = sig.asyncness {
+        return Err(Error::new(
+            asyncness.span(),
+            "browser_test functions must not be async",
+        ));
+    }
+    if sig.inputs.len() != 1 {
+        return Err(Error::new(
+            sig.inputs.span(),
+            "browser_test functions take a single `&DriverHolder` argument",
+        ));
+    }
+
+    let name = &sig.ident;
+    let inputs = &sig.inputs;
+    let output = &sig.output;
+    Ok(quote! {
+        #[test]
+        #(#attrs)*
+        #vis fn #name() #output {
+            fn #name(#inputs) #output #block
+            ::sulfur::testing::run_browser_test(stringify!(#name), #name)
+        }
+    })
+}
+
+/// Derives `sulfur::page::PageObject` for a struct with a single lifetime
+/// parameter. Fields annotated with a locator, eg: `#[by(css = ".submit")]`,
+/// must be `LazyElement`s; other fields must be page objects themselves.
+///
+/// ```ignore
+/// #[derive(sulfur::page::PageObject)]
+/// struct LoginPage<'c> {
+///     #[by(id = "username")]
+///     username: LazyElement<'c>,
+///     #[by(css = ".login .submit")]
+///     submit: LazyElement<'c>,
+/// }
+/// ```
+#[proc_macro_derive(PageObject, attributes(by))]
+pub fn derive_page_object(item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as DeriveInput);
+    match expand_page_object(input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand_page_object(input: DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+    let name = &input.ident;
+    let lifetime = match input.generics.lifetimes().next() {
+        Some(def) if input.generics.params.len() == 1 => def.lifetime.clone(),
+        _ => {
+            return Err(Error::new(
+                input.generics.span(),
+                "PageObject structs take a single lifetime parameter, eg: `struct Page<'c>`",
+            ))
+        }
+    };
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(Error::new(name.span(), "PageObject needs named fields")),
+        },
+        _ => {
+            return Err(Error::new(
+                name.span(),
+                "PageObject can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut inits = Vec::new();
+    for field in fields {
+        let ident = field.ident.as_ref().expect("named field");
+        let init = match locator(&field.attrs)? {
+            Some((ctor, value)) => quote! {
+                ::sulfur::page::LazyElement::new(client, ::sulfur::By::#ctor(#value))
+            },
+            None => quote! { ::sulfur::page::PageObject::from_client(client) },
+        };
+        inits.push(quote! { #ident: #init });
+    }
+
+    Ok(quote! {
+        impl<#lifetime> ::sulfur::page::PageObject<#lifetime> for #name<#lifetime> {
+            fn from_client(client: &#lifetime ::sulfur::Client) -> Self {
+                #name { #(#inits),* }
+            }
+        }
+    })
+}
+
+// Parses `#[by(css = "...")]` into the `By` constructor and its argument.
+fn locator(attrs: &[syn::Attribute]) -> Result<Option<(syn::Ident, syn::LitStr)>, Error> {
+    let attr = match attrs.iter().find(|a| a.path.is_ident("by")) {
+        Some(attr) => attr,
+        None => return Ok(None),
+    };
+    let invalid = || {
+        Error::new(
+            attr.span(),
+            format!(
+                "expected eg: `#[by(css = \"...\")]`, with one of: {}",
+                LOCATORS.join(", ")
+            ),
+        )
+    };
+    let list = match attr.parse_meta()? {
+        Meta::List(list) if list.nested.len() == 1 => list,
+        _ => return Err(invalid()),
+    };
+    match list.nested.first() {
+        Some(NestedMeta::Meta(Meta::NameValue(nv))) => {
+            let ctor = nv.path.get_ident().cloned().ok_or_else(invalid)?;
+            if !LOCATORS.contains(&&*ctor.to_string()) {
+                return Err(invalid());
+            }
+            match &nv.lit {
+                Lit::Str(value) => Ok(Some((ctor, value.clone()))),
+                _ => Err(invalid()),
+            }
+        }
+        _ => Err(invalid()),
+    }
+}
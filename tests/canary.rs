@@ -2,63 +2,92 @@ extern crate env_logger;
 extern crate sulfur;
 #[macro_use]
 extern crate lazy_static;
-extern crate futures;
-extern crate tokio;
 #[macro_use]
 extern crate log;
 extern crate failure;
-extern crate hyper;
-extern crate hyper_staticfile;
 extern crate tempfile;
 extern crate url;
 
-use std::collections::{BTreeMap, BTreeSet};
-use std::env;
-use std::net::SocketAddr;
-use std::path::Path;
-use std::sync::Mutex;
-use std::{thread, time};
-
-use futures::channel::oneshot;
-use futures::future::select;
-use hyper::service::make_service_fn;
-use tokio::runtime;
+use std::collections::BTreeMap;
+use std::time;
 
 use sulfur::chrome;
+use sulfur::testing::StaticServer;
+use sulfur::wait::conditions;
 use sulfur::*;
 
-const TEST_HTML_DIR: &'static str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/html");
+const TEST_HTML_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/html");
 
 lazy_static! {
-    static ref RT: Mutex<runtime::Runtime> =
-        Mutex::new(runtime::Runtime::new().expect("tokio runtime"));
-    static ref SERVER: TestServer = {
+    static ref SERVER: StaticServer = {
         debug!("Starting test server for {}", TEST_HTML_DIR);
-        let srv = TestServer::start(Path::new(TEST_HTML_DIR)).expect("Testserver");
+        let srv = StaticServer::serve_dir(TEST_HTML_DIR).expect("Testserver");
         debug!("Test server at {}", srv.url());
         srv
     };
 }
 
 fn new_session() -> Result<DriverHolder, failure::Error> {
-    let driver = env::var("DRIVER").unwrap_or_else(|e| {
-        warn!("$DRIVER not specified, using chromedriver: {:?}", e);
-        "chromedriver".into()
-    });
-    match &*driver {
-        "geckodriver" => {
-            info!("Starting instance with {:?}", driver);
-            let driver = gecko::start(gecko::Config::default().headless(true))?;
-            Ok(driver)
-        }
-        "chromedriver" | _ => {
-            info!("Starting instance with {:?}", driver);
-            let driver = chrome::start(chrome::Config::default().headless(true))?;
-            Ok(driver)
-        }
+    sulfur::testing::start_session()
+}
+
+fn is_gecko() -> bool {
+    Browser::from_env().expect("$SULFUR_BROWSER") == Browser::Firefox
+}
+
+// Starts a headless session in the browser under test, after adjusting the
+// driver and session configuration with `gecko` or `chrome` to suit.
+fn start_configured<G, C>(gecko: G, chrome: C) -> DriverHolder
+where
+    G: FnOnce(&mut gecko::DriverConfig, &mut gecko::Config),
+    C: FnOnce(&mut chrome::DriverConfig, &mut chrome::Config),
+{
+    if is_gecko() {
+        let (mut driver_config, mut config) = Default::default();
+        gecko(&mut driver_config, &mut config);
+        let driver = gecko::Driver::driver_config(&driver_config).expect("gecko driver");
+        let client = driver
+            .new_session_config(config.headless(true))
+            .expect("new_session");
+        DriverHolder::new(Box::new(driver), client)
+    } else {
+        let (mut driver_config, mut config) = Default::default();
+        chrome(&mut driver_config, &mut config);
+        let driver = chrome::Driver::driver_config(&driver_config).expect("chrome driver");
+        let client = driver
+            .new_session_config(config.headless(true))
+            .expect("new_session");
+        DriverHolder::new(Box::new(driver), client)
     }
 }
 
+#[sulfur::browser_test]
+fn browser_test_attribute(s: &DriverHolder) -> Result<(), failure::Error> {
+    s.visit(SERVER.url())?;
+    s.find_element(&By::css(".clickable-link"))?;
+    Ok(())
+}
+
+#[derive(sulfur::page::PageObject)]
+struct IndexPage<'c> {
+    #[by(id = "an-id")]
+    greeting: sulfur::page::LazyElement<'c>,
+    #[by(css = ".three-of-these")]
+    triplets: sulfur::page::LazyElement<'c>,
+    #[by(id = "i-do-not-exist")]
+    missing: sulfur::page::LazyElement<'c>,
+}
+
+#[sulfur::browser_test]
+fn page_objects(s: &DriverHolder) -> Result<(), failure::Error> {
+    let page: IndexPage = s.page();
+    s.visit(SERVER.url())?;
+    assert_eq!(page.greeting.text()?.trim(), "Hello world");
+    assert_eq!(page.triplets.find_all()?.len(), 3);
+    assert!(!page.missing.exists()?);
+    Ok(())
+}
+
 #[test]
 fn can_run_driver() {
     env_logger::try_init().unwrap_or_default();
@@ -66,58 +95,6 @@ fn can_run_driver() {
     s.close().expect("close");
 }
 
-struct TestServer {
-    drop: Option<oneshot::Sender<()>>,
-    addr: SocketAddr,
-}
-
-impl TestServer {
-    fn start(path: &Path) -> Result<Self, failure::Error> {
-        use std::net;
-
-        let (tx, rx) = oneshot::channel::<()>();
-        let path = path.to_owned();
-        let addr: net::SocketAddr = "127.0.0.1:0".parse()?;
-        let sock = net::TcpListener::bind(&addr)?;
-        let addr = sock.local_addr()?;
-
-        let content = hyper_staticfile::Static::new(&path);
-        let make_service =
-            make_service_fn(move |_| futures::future::ok::<_, hyper::Error>(content.clone()));
-
-        thread::Builder::new()
-            .name("TestServer".to_string())
-            .spawn(move || {
-                let mut rt = RT.lock().expect("lock runtime");
-                rt.block_on(async {
-                    let srv = hyper::Server::from_tcp(sock)
-                        .expect("listen on socket")
-                        .serve(make_service);
-                    debug!("Starting server");
-                    select(srv, rx).await
-                })
-            })?;
-
-        let s = TestServer {
-            drop: Some(tx),
-            addr: addr,
-        };
-        debug!("Test server listening at: {}", s.url());
-        Ok(s)
-    }
-    fn url(&self) -> String {
-        format!("http://{}:{}/", self.addr.ip(), self.addr.port())
-    }
-}
-
-impl Drop for TestServer {
-    fn drop(&mut self) {
-        if let Some(tx) = self.drop.take() {
-            tx.send(()).expect("Send shutdown signal");
-        }
-    }
-}
-
 #[test]
 fn can_navigate() {
     env_logger::try_init().unwrap_or_default();
@@ -420,8 +397,7 @@ fn form_submission() {
         .find_element(&By::css("#the-form button"))
         .expect("find button");
     let () = s.click(&button).expect("click");
-    let url = s.current_url().expect("current_url");
-    let url = url::Url::parse(&url).expect("parse url");
+    let url = s.current_url_parsed().expect("current_url_parsed");
     let q = url
         .query_pairs()
         .map(|(k, v)| (k.into_owned(), v.into_owned()))
@@ -436,6 +412,55 @@ fn form_submission() {
     )
 }
 
+#[test]
+fn wait_for_navigation() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+
+    let link = s
+        .find_element(&By::css(".clickable-link"))
+        .expect("find .clickable-link");
+    let target = s
+        .click_and_wait_for_navigation(&link)
+        .expect("click_and_wait_for_navigation");
+    assert!(target.ends_with("link-target.html"), "URL: {:?}", target);
+
+    s.back().expect("back");
+    s.wait_for_url(|u| u == url, time::Duration::from_secs(5))
+        .expect("wait_for_url");
+    let title = s
+        .wait_for_title(|t| t.contains("title"), time::Duration::from_secs(5))
+        .expect("wait_for_title");
+    assert_eq!(title, "Page title");
+}
+
+#[test]
+fn form_submit() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+    let text = s
+        .find(&By::css("#the-form input[type='text']"))
+        .expect("find text");
+    text.send_keys("Submitted").expect("send_keys");
+    text.submit().expect("submit");
+
+    let url = s
+        .wait()
+        .until(conditions::url_contains("/post"))
+        .expect("form submitted");
+    assert!(url.contains("text=Submitted"), "URL: {}", url);
+
+    s.back().expect("back");
+    let para = s.find(&By::css("#an-id")).expect("find paragraph");
+    assert!(para.submit().is_err(), "Paragraph is not in a form");
+}
+
 #[test]
 fn form_element_clearing() {
     env_logger::try_init().unwrap_or_default();
@@ -455,8 +480,7 @@ fn form_element_clearing() {
     s.clear(&text).expect("clear");
     s.click(&button).expect("click");
 
-    let url = s.current_url().expect("current_url");
-    let url = url::Url::parse(&url).expect("parse url");
+    let url = s.current_url_parsed().expect("current_url_parsed");
     let q = url
         .query_pairs()
         .map(|(k, v)| (k.into_owned(), v.into_owned()))
@@ -479,12 +503,43 @@ fn timeouts() {
     let s = new_session().expect("new_session");
     s.visit(&url).expect("visit");
 
-    s.set_timeouts(&Timeouts {
-        ..Timeouts::default()
-    })
-    .expect("set timeouts");
+    let before = s.timeouts().expect("get timeouts");
+
+    s.set_timeouts(&Timeouts::new().implicit(time::Duration::from_millis(1500)))
+        .expect("set timeouts");
+
+    let after = s.timeouts().expect("get timeouts");
+    assert_eq!(after.implicit, Some(time::Duration::from_millis(1500)));
+    assert_eq!(after.page_load, before.page_load);
+    assert_eq!(after.script, before.script);
+}
+
+#[test]
+fn window_rects_and_viewports() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
 
-    let _t = s.timeouts().expect("get timeouts");
+    let rect = s.window_rect().expect("window_rect");
+    let resized = s
+        .set_window_rect(&WindowRect {
+            width: 800,
+            height: 600,
+            ..rect
+        })
+        .expect("set_window_rect");
+    assert_eq!((resized.width, resized.height), (800, 600));
+
+    for &(width, height) in &[(375, 667), (1024, 768)] {
+        s.set_viewport(width, height, 1.0, false)
+            .expect("set_viewport");
+        let inner: (u32, u32) = s
+            .execute_script("return [window.innerWidth, window.innerHeight];", &[])
+            .expect("execute_script");
+        assert_eq!(inner, (width, height));
+    }
 }
 
 #[test]
@@ -506,20 +561,13 @@ fn window_handles() {
 
     s.click(&opener_link).expect("click link");
 
-    let known = known_windows.iter().cloned().collect::<BTreeSet<_>>();
-    wait_until(time::Duration::from_secs(10), || {
-        let current = s.windows()?.into_iter().collect::<BTreeSet<_>>();
-        Ok(current != known)
-    })
-    .expect("Wait for window open");
+    let other_window = s
+        .wait_for_new_window(&known_windows, time::Duration::from_secs(10))
+        .expect("Wait for window open");
 
     let known_windows = s.windows().expect("get windows");
     assert_eq!(2, known_windows.len());
-    let other_window = known_windows
-        .iter()
-        .cloned()
-        .find(|w| w != &main_window)
-        .expect("other window");
+    assert!(known_windows.contains(&other_window));
 
     // Yes, we switch to the current window. This would be easier if
     // `/session/{session}/window/new` was supported anywhere but the w3c spec.
@@ -539,6 +587,114 @@ fn window_handles() {
     assert_eq!(vec![main_window.clone()], new_handles);
 }
 
+#[test]
+fn open_in_new_tab() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+    let main_window = s.window().expect("get window");
+
+    let tab_url = format!("{}#new-tab", url);
+    let tab = s.open_in_new_tab(&tab_url).expect("open_in_new_tab");
+    assert_eq!(s.window().expect("get window"), tab);
+    assert_eq!(s.current_url().expect("current_url"), tab_url);
+    assert_eq!(s.windows().expect("get windows").len(), 2);
+
+    s.close_window().expect("close window");
+    s.switch_to_window(&main_window).expect("switch back");
+}
+
+#[test]
+fn close_other_windows() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+    let main_window = s.window().expect("get window");
+
+    for _ in 0..2 {
+        s.open_in_new_tab(&url).expect("open_in_new_tab");
+    }
+    s.switch_to_window(&main_window).expect("switch back");
+    assert_eq!(s.windows().expect("get windows").len(), 3);
+
+    s.close_other_windows().expect("close_other_windows");
+    assert_eq!(s.windows().expect("get windows"), vec![main_window.clone()]);
+    assert_eq!(s.window().expect("get window"), main_window);
+}
+
+#[test]
+fn switch_to_window_matching() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+    let main_window = s.window().expect("get window");
+
+    let popup = s
+        .open_in_new_tab(&format!("{}#popup", url))
+        .expect("open_in_new_tab");
+    s.switch_to_window(&main_window).expect("switch back");
+
+    let info = s
+        .switch_to_window_matching(|w| w.url.ends_with("#popup"), time::Duration::from_secs(10))
+        .expect("switch_to_window_matching");
+    assert_eq!(info.window, popup);
+    assert_eq!(info.title, "Page title");
+    assert_eq!(s.window().expect("get window"), popup);
+
+    let err = s
+        .switch_to_window_matching(
+            |w| w.title == "No such title",
+            time::Duration::from_millis(100),
+        )
+        .expect_err("no match");
+    assert_eq!(
+        err.downcast_ref::<WdError>().map(WdError::kind),
+        Some(ErrorKind::Timeout)
+    );
+    assert_eq!(s.window().expect("get window"), popup);
+}
+
+#[test]
+fn scoped_windows() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+
+    let main_window = s.window().expect("get window");
+    let known_windows = s.windows().expect("get windows");
+    s.find(&By::css(".new-window"))
+        .and_then(|link| link.click())
+        .expect("click link");
+    let popup = s
+        .wait_for_new_window(&known_windows, time::Duration::from_secs(10))
+        .expect("Wait for window open");
+
+    let popup_url = s
+        .with_window(&popup, |c| c.current_url())
+        .expect("with_window");
+    assert!(popup_url.contains("#new-window"), "{:?}", popup_url);
+    assert_eq!(s.window().expect("get window"), main_window);
+
+    {
+        let guard = s.enter_window(&popup).expect("enter_window");
+        assert_eq!(guard.window().expect("get window"), popup);
+    }
+    assert_eq!(s.window().expect("get window"), main_window);
+
+    s.with_window(&popup, |c| c.close_window().map(drop))
+        .expect("close popup");
+    assert_eq!(s.windows().expect("get windows"), vec![main_window.clone()]);
+    assert_eq!(s.window().expect("get window"), main_window);
+}
+
 #[test]
 fn frames_by_ref() {
     env_logger::try_init().unwrap_or_default();
@@ -580,6 +736,35 @@ fn frames_by_ref() {
     )
 }
 
+#[test]
+fn frame_guards() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+
+    let inner = By::css("#inner-content");
+    let iframe = s.find_element(&By::css("iframe")).expect("find iframe");
+    {
+        let frame = s.enter_frame(&iframe).expect("enter frame");
+        assert_eq!(frame.find_elements(&inner).expect("find").len(), 1);
+    }
+    assert!(s.find_elements(&inner).expect("find").is_empty());
+
+    let err = s
+        .with_frame(&iframe, |c| {
+            assert_eq!(c.find_elements(&inner)?.len(), 1);
+            c.find_element(&By::css("#missing-element"))
+        })
+        .expect_err("missing element");
+    assert_eq!(
+        err.downcast_ref::<WdError>().map(WdError::kind),
+        Some(ErrorKind::NoSuchElement)
+    );
+    assert!(s.find_elements(&inner).expect("find").is_empty());
+}
+
 #[test]
 fn frames_parent() {
     env_logger::try_init().unwrap_or_default();
@@ -627,7 +812,8 @@ fn should_include_message_in_errors() {
         wd_error.message.contains("thing-that-is-not-present"),
         "Error contains the name of the missing tag: {:?}",
         wd_error
-    )
+    );
+    assert_eq!(wd_error.http_status(), Some(404));
 }
 
 #[test]
@@ -650,9 +836,6 @@ fn should_get_page_source() {
 
 #[test]
 fn should_get_document_screenshot() {
-    use std::fs;
-    use std::io::Write;
-
     env_logger::try_init().unwrap_or_default();
 
     let url = SERVER.url();
@@ -661,21 +844,34 @@ fn should_get_document_screenshot() {
 
     let ss = s.screenshot().expect("document screenshot");
 
-    assert!(ss.len() > 0, "Returns non-empty set of bytes");
+    assert!(!ss.as_bytes().is_empty(), "Returns non-empty set of bytes");
+    assert!(ss.dimensions().is_some(), "Returns a PNG image");
 
-    let path = tempfile::tempdir().expect("tempdir").into_path();
+    let path = tempfile::tempdir().expect("tempdir").keep();
     let ss_path = path.join("document.png");
-    let mut w = fs::File::create(&ss_path).expect("document.png");
-    w.write_all(&ss).expect("write_all");
-    w.flush().expect("flush");
-    println!("Wrote {} bytes of image to {:?}", ss.len(), ss_path);
+    ss.save(&ss_path).expect("save");
+    println!(
+        "Wrote {} bytes of image to {:?}",
+        ss.as_bytes().len(),
+        ss_path
+    );
+
+    #[cfg(feature = "image")]
+    {
+        let img = s.screenshot_image().expect("screenshot_image");
+        assert_eq!(
+            Some(image::GenericImageView::dimensions(&img)),
+            ss.dimensions()
+        );
+    }
+
+    let ss_path = path.join("document-direct.png");
+    s.screenshot_to(&ss_path).expect("screenshot_to");
+    assert!(ss_path.exists(), "Saved screenshot to {:?}", ss_path);
 }
 
 #[test]
 fn should_get_element_screenshot() {
-    use std::fs;
-    use std::io::Write;
-
     env_logger::try_init().unwrap_or_default();
 
     let url = SERVER.url();
@@ -688,27 +884,1227 @@ fn should_get_element_screenshot() {
 
     let ss = s.element_screenshot(&elt).expect("element screenshot");
 
-    assert!(ss.len() > 0, "Returns non-empty set of bytes");
+    assert!(!ss.as_bytes().is_empty(), "Returns non-empty set of bytes");
 
-    let path = tempfile::tempdir().expect("tempdir").into_path();
+    let path = tempfile::tempdir().expect("tempdir").keep();
     let ss_path = path.join("document.png");
-    let mut w = fs::File::create(&ss_path).expect("document.png");
-    w.write_all(&ss).expect("write_all");
-    w.flush().expect("flush");
-    println!("Wrote {} bytes of image to {:?}", ss.len(), ss_path);
-}
-
-fn wait_until<F: FnMut() -> Result<bool, failure::Error>>(
-    deadline: time::Duration,
-    mut check: F,
-) -> Result<bool, failure::Error> {
-    let mut pause_time = time::Duration::from_millis(1);
-    let started_at = time::Instant::now();
-    while started_at.elapsed() < deadline && !check()? {
-        debug!("Pausing for {:?}", pause_time);
-        thread::sleep(pause_time);
-        pause_time *= 2;
+    ss.save(&ss_path).expect("save");
+    println!(
+        "Wrote {} bytes of image to {:?}",
+        ss.as_bytes().len(),
+        ss_path
+    );
+}
+
+#[test]
+fn cookies() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+
+    s.add_cookie(&Cookie {
+        name: "canary".into(),
+        value: "tweet".into(),
+        ..Cookie::default()
+    })
+    .expect("add_cookie");
+
+    let cookie = s.cookie("canary").expect("cookie");
+    assert_eq!(cookie.value, "tweet");
+
+    let names = s
+        .cookies()
+        .expect("cookies")
+        .into_iter()
+        .map(|c| c.name)
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["canary".to_string()]);
+
+    s.delete_cookie("canary").expect("delete_cookie");
+    assert_eq!(s.cookies().expect("cookies"), Vec::<Cookie>::new());
+}
+
+#[test]
+fn command_hooks_observe_commands() {
+    use std::sync::{Arc, Mutex};
+
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let mut s = new_session().expect("new_session");
+
+    let before = Arc::new(Mutex::new(Vec::new()));
+    let after = Arc::new(Mutex::new(Vec::new()));
+    {
+        let before = before.clone();
+        let after = after.clone();
+        s.on_command(
+            move |_, cmd| before.lock().unwrap().push(cmd.name),
+            move |_, cmd, outcome| {
+                after
+                    .lock()
+                    .unwrap()
+                    .push((cmd.name, outcome.result.is_ok()))
+            },
+        );
     }
 
-    Ok(check()?)
+    s.visit(&url).expect("visit");
+    let _ = s.find_element(&By::css("#i-do-not-exist"));
+
+    assert_eq!(*before.lock().unwrap(), vec!["visit", "find_element"]);
+    assert_eq!(
+        *after.lock().unwrap(),
+        vec![("visit", true), ("find_element", false)]
+    );
+}
+
+#[test]
+fn explicit_waits() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+
+    let elt = s
+        .wait()
+        .at_most(time::Duration::from_secs(5))
+        .until(conditions::element_visible(&By::css("#an-id")))
+        .expect("wait for #an-id");
+    assert_eq!(s.text(&elt).expect("text").trim(), "Hello world");
+
+    s.wait()
+        .until(conditions::title_is("Page title"))
+        .expect("wait for title");
+
+    let err = s
+        .wait()
+        .at_most(time::Duration::from_millis(200))
+        .until(conditions::element_present(&By::css("#i-do-not-exist")))
+        .expect_err("missing element");
+    let wd_error = err.downcast_ref::<WdError>().expect("Extract WdError");
+    assert_eq!(wd_error.error, "timeout");
+}
+
+#[test]
+fn find_element_waiting_for_late_element() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+
+    let parent = s
+        .find_element(&By::css("#delayed-parent"))
+        .expect("find #delayed-parent");
+    let elt = s
+        .find_element_from_waiting(
+            &parent,
+            &By::css("#appears-later"),
+            time::Duration::from_secs(5),
+        )
+        .expect("find #appears-later");
+    assert_eq!(s.text(&elt).expect("text"), "Better late than never");
+
+    let err = s
+        .find_element_waiting(
+            &By::css("#i-do-not-exist"),
+            time::Duration::from_millis(200),
+        )
+        .expect_err("missing element");
+    let wd_error = err.downcast_ref::<WdError>().expect("Extract WdError");
+    assert_eq!(wd_error.kind(), ErrorKind::NoSuchElement);
+}
+
+#[test]
+fn bound_elements() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+
+    let parent = s.find(&By::css("#with-children")).expect("find");
+    let child = parent.find(&By::css(".a-child")).expect("find child");
+    assert_eq!(child.text().expect("text").trim(), "Hello world");
+    assert_eq!(child.name().expect("name"), "p");
+
+    let children = parent
+        .find_all(&By::css(".two-of-these"))
+        .expect("find children");
+    assert_eq!(children.len(), 2);
+}
+
+#[test]
+fn select_helper() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+
+    let selected_values = |select: &Select| {
+        select
+            .selected_options()
+            .expect("selected_options")
+            .iter()
+            .map(|o| o.attribute("value").expect("value").expect("has value"))
+            .collect::<Vec<_>>()
+    };
+
+    let single = Select::new(
+        &s,
+        s.find_element(&By::css("#single-select")).expect("find"),
+    )
+    .expect("single select");
+    assert!(!single.is_multiple());
+    assert_eq!(single.options().expect("options").len(), 3);
+
+    single.select_by_visible_text("Banana").expect("by text");
+    assert_eq!(selected_values(&single), vec!["b"]);
+    single.select_by_value("c").expect("by value");
+    assert_eq!(selected_values(&single), vec!["c"]);
+    single.select_by_index(0).expect("by index");
+    assert_eq!(selected_values(&single), vec!["a"]);
+    assert!(single.select_by_value("nope").is_err());
+
+    let multi = Select::new(&s, s.find_element(&By::css("#multi-select")).expect("find"))
+        .expect("multi select");
+    assert!(multi.is_multiple());
+    multi.select_by_value("z").expect("by value");
+    assert_eq!(selected_values(&multi), vec!["x", "z"]);
+    multi.deselect_all().expect("deselect_all");
+    assert_eq!(selected_values(&multi), Vec::<String>::new());
+}
+
+#[test]
+fn file_upload() {
+    use std::fs;
+
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+
+    let dir = tempfile::tempdir().expect("tempdir");
+    let path = dir.path().join("canary-upload.txt");
+    fs::write(&path, b"tweet").expect("write upload");
+
+    let input = s.find(&By::css("#file-input")).expect("find file input");
+    input.send_file(&path).expect("send_file");
+
+    let value = input.property("value").expect("value");
+    let value = value.as_str().expect("value is a string");
+    assert!(
+        value.ends_with("canary-upload.txt"),
+        "File input value {:?} should name the uploaded file",
+        value
+    );
+}
+
+#[test]
+fn downloads() {
+    env_logger::try_init().unwrap_or_default();
+
+    let dir = tempfile::tempdir().expect("tempdir");
+    let url = SERVER.url();
+    let s = start_configured(
+        |_, c| {
+            c.download_dir(dir.path());
+        },
+        |_, c| {
+            c.download_dir(dir.path());
+        },
+    );
+    s.visit(&url).expect("visit");
+
+    let download = sulfur::wait::before_download(dir.path()).expect("before_download");
+    s.find(&By::css("#download-link"))
+        .expect("find download link")
+        .click()
+        .expect("click");
+    let path = download
+        .wait(time::Duration::from_secs(10))
+        .expect("wait for download");
+    assert_eq!(path.file_name().expect("file name"), "report.txt");
+    assert_eq!(std::fs::read_to_string(&path).expect("read"), "report");
+}
+
+#[test]
+fn drag_and_drop() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+
+    let source = s
+        .find_element(&By::css("#drag-source"))
+        .expect("find source");
+    let target = s
+        .find_element(&By::css("#drop-target"))
+        .expect("find target");
+    s.drag_and_drop(&source, &target).expect("drag_and_drop");
+    assert_eq!(s.text(&target).expect("text"), "Dropped");
+
+    s.drag_by_offset(&source, 10, 20).expect("drag_by_offset");
+    let offset = s
+        .find_element(&By::css("#drag-offset"))
+        .expect("find offset");
+    assert_eq!(s.text(&offset).expect("text"), "10,20");
+
+    let source = s
+        .find_element(&By::css("#html5-source"))
+        .expect("find source");
+    let target = s
+        .find_element(&By::css("#html5-target"))
+        .expect("find target");
+    s.html5_drag_and_drop(&source, &target)
+        .expect("html5_drag_and_drop");
+    assert_eq!(s.text(&target).expect("text"), "Dropped payload");
+}
+
+#[test]
+fn text_modes() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+
+    let elt = s.find(&By::id("text-modes")).expect("find");
+    assert_eq!(
+        elt.text_with(TextMode::Rendered).expect("rendered"),
+        elt.text().expect("text")
+    );
+    assert_eq!(
+        elt.text_with(TextMode::TextContent).expect("textContent"),
+        "  Some   hiddentext "
+    );
+    let inner = elt.text_with(TextMode::InnerText).expect("innerText");
+    assert!(!inner.contains("hidden"), "innerText: {:?}", inner);
+    assert!(inner.contains("Some"), "innerText: {:?}", inner);
+}
+
+#[test]
+fn markup() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+
+    let elt = s.find(&By::id("text-modes")).expect("find");
+    assert_eq!(
+        elt.inner_html().expect("inner_html"),
+        "  Some   <span style=\"display: none;\">hidden</span>text "
+    );
+    let outer = elt.outer_html().expect("outer_html");
+    assert!(outer.starts_with("<div id=\"text-modes\">"), "{}", outer);
+    assert!(outer.ends_with("</div>"), "{}", outer);
+}
+
+#[test]
+fn count_elements() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+
+    for by in &[
+        By::css(".three-of-these"),
+        By::xpath("//p[@class='three-of-these']"),
+        By::tag_name("p"),
+        By::link_text("Link target"),
+        By::partial_link_text("Window"),
+        By::css("#missing-element"),
+    ] {
+        let expected = s.find_elements(by).expect("find_elements").len();
+        assert_eq!(s.count_elements(by).expect("count"), expected, "{:?}", by);
+    }
+    assert_eq!(
+        s.count_elements(&By::css(".three-of-these"))
+            .expect("count"),
+        3
+    );
+}
+
+#[test]
+fn batched_queries() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+
+    let mut batch = s.batch();
+    let text = batch.text(&By::id("an-id"));
+    let texts = batch.texts(&By::css(".three-of-these"));
+    let attribute = batch.attribute(&By::id("find-attribute-value"), "data-my-id");
+    let count = batch.count(&By::css(".two-of-these"));
+    let missing = batch.text(&By::css("#missing-element"));
+    let results = batch.run().expect("run");
+
+    assert_eq!(results.get(text).expect("text"), "Hello world");
+    assert_eq!(results.get(texts).expect("texts"), vec!["1", "2", "3"]);
+    assert_eq!(
+        results.get(attribute).expect("attribute"),
+        Some("my-id-value".to_string())
+    );
+    assert_eq!(results.get(count).expect("count"), 2);
+    let err = results.get(missing).expect_err("missing");
+    assert_eq!(
+        err.downcast_ref::<WdError>().map(WdError::kind),
+        Some(ErrorKind::NoSuchElement)
+    );
+}
+
+#[test]
+fn deep_css_queries() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+
+    assert!(s
+        .find_elements(&By::id("shadow-button"))
+        .expect("find_elements")
+        .is_empty());
+    let button = s.find_deep("#shadow-button").expect("find_deep");
+    assert_eq!(button.text().expect("text"), "Deep");
+
+    let texts = s
+        .find_elements_deep(".shadow-text")
+        .expect("find_elements_deep")
+        .iter()
+        .map(|elt| s.text(elt))
+        .collect::<Result<Vec<_>, _>>()
+        .expect("texts");
+    assert_eq!(texts, vec!["Outer", "Deep"]);
+
+    let err = s.find_deep("#missing-element").expect_err("missing");
+    assert_eq!(
+        err.downcast_ref::<WdError>().map(WdError::kind),
+        Some(ErrorKind::NoSuchElement)
+    );
+}
+
+#[test]
+fn role_locators() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+
+    let roles = s.find(&By::id("roles")).expect("find");
+    for (by, id) in &[
+        (By::role("textbox", "Email"), "role-email"),
+        (By::role("checkbox", "Subscribe"), "role-subscribe"),
+        (By::role("button", "Close"), "role-close"),
+        (By::role("button", "Save"), "role-save"),
+    ] {
+        let elt = roles.find(by).expect("find by role");
+        assert_eq!(
+            elt.attribute("id").expect("id").as_deref(),
+            Some(*id),
+            "{:?}",
+            by
+        );
+    }
+    let heading = roles.find(&By::role("heading", None)).expect("heading");
+    assert_eq!(heading.text().expect("text"), "Settings");
+    assert!(roles
+        .find_all(&By::role("button", "Settings"))
+        .expect("find_all")
+        .is_empty());
+}
+
+#[test]
+fn xpath_builder() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+
+    let by = selectors::XPath::tag("p")
+        .with_class("three-of-these")
+        .with_text("2")
+        .into();
+    let elt = s.find(&by).expect("find");
+    assert_eq!(elt.text().expect("text"), "2");
+
+    let by = selectors::XPath::tag("table")
+        .with_id("relative-form")
+        .descendant("label")
+        .containing_text("Sec")
+        .into();
+    let label = s.find(&by).expect("find");
+    assert_eq!(
+        label.attribute("id").expect("id").as_deref(),
+        Some("second-label")
+    );
+}
+
+#[test]
+fn search_scopes() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+
+    fn count(scope: &Scope, by: &By) -> usize {
+        scope.find_all(by).expect("find_all").len()
+    }
+    let p = By::css("p");
+
+    let document = s.document();
+    let parent = document.scope(&By::id("with-children")).expect("scope");
+    let shadow = s
+        .find(&By::id("shadow-host"))
+        .expect("find host")
+        .shadow_root()
+        .expect("shadow_root");
+
+    assert_eq!(count(&parent, &p), 3);
+    assert_eq!(count(&shadow, &p), 1);
+    assert!(count(&document, &p) > count(&parent, &p));
+    assert_eq!(
+        shadow
+            .find(&By::css(".shadow-text"))
+            .expect("find")
+            .text()
+            .expect("text"),
+        "Outer"
+    );
+    let shadow_text = shadow.text().expect("text");
+    assert!(shadow_text.starts_with("Outer"), "{:?}", shadow_text);
+    assert!(document.text().expect("text").contains("Hello world"));
+
+    let err = s
+        .shadow_root(s.find(&By::id("an-id")).expect("find").element())
+        .expect_err("no shadow root");
+    assert_eq!(
+        err.downcast_ref::<WdError>().map(WdError::kind),
+        Some(ErrorKind::NoSuchShadowRoot)
+    );
+}
+
+#[test]
+fn navigation_failures() {
+    env_logger::try_init().unwrap_or_default();
+
+    let s = new_session().expect("new_session");
+    let err = s.visit("http://nonexistent.invalid/").expect_err("visit");
+    let failure = err
+        .downcast_ref::<WdError>()
+        .and_then(WdError::navigation_failure);
+    assert_eq!(failure, Some(NavigationFailure::UnknownHost), "{}", err);
+}
+
+#[test]
+fn displayedness() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+
+    let displayed = |id: &str| -> bool {
+        s.find(&By::id(id))
+            .expect("find")
+            .is_displayed()
+            .expect("is_displayed")
+    };
+    assert!(displayed("shown"));
+    assert!(!displayed("hidden-by-parent"));
+    assert!(!displayed("invisible"));
+    assert!(!displayed("transparent"));
+    assert!(!displayed("hidden-input"));
+}
+
+#[test]
+fn hover() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+
+    let menu = s.find(&By::css("#hover-menu")).expect("find menu");
+    let items = s.find(&By::css("#hover-items")).expect("find items");
+    assert!(!items.is_displayed().expect("is_displayed"));
+
+    menu.hover().expect("hover");
+    assert!(items.is_displayed().expect("is_displayed"));
+}
+
+#[test]
+fn double_and_context_click() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+
+    let p = s.find(&By::css("#click-kinds")).expect("find");
+    p.double_click().expect("double_click");
+    assert_eq!(p.text().expect("text"), "Double clicked");
+
+    p.context_click().expect("context_click");
+    assert_eq!(p.text().expect("text"), "Context clicked");
+}
+
+#[test]
+fn screen_recording() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let mut s = new_session().expect("new_session");
+    let dir = tempfile::tempdir().expect("tempdir");
+    let recorder = ScreenRecorder::new(dir.path().join("frames"));
+    recorder.attach(&mut s).expect("attach");
+
+    s.visit(&url).expect("visit");
+    s.find_element(&By::css(".clickable-link"))
+        .expect("find .clickable-link");
+
+    let frames = recorder.frames().expect("frames");
+    assert_eq!(frames.len(), 2, "One frame per command: {:?}", frames);
+    assert!(frames[0].ends_with("00000.png"));
+}
+
+#[test]
+fn dom_snapshots() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let mut s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+    s.execute_script::<()>(
+        "document.getElementById('invisible').textContent = 'Changed';",
+        &[],
+    )
+    .expect("execute_script");
+
+    let snapshot = s.snapshot_dom().expect("snapshot_dom");
+    assert_eq!(snapshot.url, url);
+    assert!(
+        snapshot.html.starts_with("<html"),
+        "No doctype in the fixture"
+    );
+    assert!(
+        snapshot.html.contains(">Changed</p>"),
+        "Includes script changes"
+    );
+    assert!(snapshot.html.contains("visibility: hidden"));
+
+    let recorder = DomRecorder::new();
+    recorder.attach(&mut s);
+    s.visit(&url).expect("visit");
+    s.find_element(&By::css(".clickable-link"))
+        .expect("find .clickable-link");
+
+    let snapshots = recorder.snapshots();
+    let commands = snapshots.iter().map(|(cmd, _)| *cmd).collect::<Vec<_>>();
+    assert_eq!(commands, vec!["visit", "find_element"]);
+
+    let dir = tempfile::tempdir().expect("tempdir");
+    s.capture_artifacts(dir.path()).expect("capture_artifacts");
+    let dom = dir.path().join("dom");
+    assert!(dom.join("00000-visit.html").exists());
+    assert!(dom.join("00001-find_element.html").exists());
+    let timeline = std::fs::read_to_string(dom.join("timeline.jsonl")).expect("timeline");
+    assert_eq!(timeline.lines().count(), 2);
+}
+
+#[test]
+fn failure_artifacts() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+
+    let dir = tempfile::tempdir().expect("tempdir");
+    s.capture_artifacts(dir.path()).expect("capture_artifacts");
+    for name in &[
+        "screenshot.png",
+        "page.html",
+        "url.txt",
+        "cookies.json",
+        "commands.jsonl",
+    ] {
+        assert!(dir.path().join(name).exists(), "Should have saved {}", name);
+    }
+
+    let panicked_dir = dir.path().join("panicked");
+    let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let _guard = s.artifacts_on_panic(&panicked_dir);
+        panic!("Deliberate failure");
+    }));
+    assert!(res.is_err());
+    assert!(panicked_dir.join("screenshot.png").exists());
+
+    let fine_dir = dir.path().join("fine");
+    drop(s.artifacts_on_panic(&fine_dir));
+    assert!(!fine_dir.exists());
+
+    let owned_dir = dir.path().join("owned");
+    let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let mut s = s;
+        s.set_failure_artifacts_dir(&owned_dir);
+        panic!("Deliberate failure");
+    }));
+    assert!(res.is_err());
+    assert!(owned_dir.join("screenshot.png").exists());
+    assert!(owned_dir.join("page.html").exists());
+}
+
+#[test]
+fn execute_script_with_elements() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+
+    let elt = s
+        .find_element(&By::css("#find-attribute-value"))
+        .expect("find");
+    let id: String = s
+        .execute_script("return arguments[0].dataset.myId;", &[(&elt).into()])
+        .expect("execute_script");
+    assert_eq!(id, "my-id-value");
+
+    let found: Element = s
+        .execute_script(
+            "return document.getElementById(arguments[0]);",
+            &["find-attribute-value".into()],
+        )
+        .expect("execute_script");
+    assert!(s.elements_equal(&found, &elt).expect("elements_equal"));
+
+    let sum: u64 = s
+        .execute_async_script(
+            "var done = arguments[arguments.length - 1]; \
+             setTimeout(function () { done(arguments[0] + 1); }.bind(null, arguments[0]), 10);",
+            &[41.into()],
+        )
+        .expect("execute_async_script");
+    assert_eq!(sum, 42);
+}
+
+#[test]
+fn chrome_performance_logs() {
+    env_logger::try_init().unwrap_or_default();
+    if is_gecko() {
+        info!("Performance logs are chrome only");
+        return;
+    }
+
+    let url = SERVER.url();
+    let s = chrome::start(
+        chrome::Config::default()
+            .headless(true)
+            .performance_logging(true),
+    )
+    .expect("chrome::start");
+    s.visit(&url).expect("visit");
+
+    let events = s.performance_logs().expect("performance_logs");
+    assert!(
+        events.iter().any(|e| e.method == "Network.responseReceived"
+            && e.params["response"]["url"].as_str() == Some(&*url)),
+        "Should see the page load in {:?}",
+        events.iter().map(|e| &e.method).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn har_export() {
+    env_logger::try_init().unwrap_or_default();
+    if is_gecko() {
+        info!("HAR export is chrome only");
+        return;
+    }
+
+    let url = SERVER.url();
+    let s = chrome::start(
+        chrome::Config::default()
+            .headless(true)
+            .performance_logging(true),
+    )
+    .expect("chrome::start");
+    s.visit(&url).expect("visit");
+
+    let har = s.har().expect("har");
+    assert_eq!(har["log"]["version"], "1.2");
+    let entries = har["log"]["entries"].as_array().expect("entries");
+    let page = entries
+        .iter()
+        .find(|e| e["request"]["url"].as_str() == Some(&*url))
+        .unwrap_or_else(|| panic!("No entry for {} in {:#}", url, har));
+    assert_eq!(page["response"]["status"], 200);
+    assert_eq!(page["response"]["content"]["mimeType"], "text/html");
+}
+
+#[test]
+fn console_events() {
+    env_logger::try_init().unwrap_or_default();
+    if is_gecko() {
+        info!("Browser logs are chrome only");
+        return;
+    }
+
+    let url = SERVER.url();
+    let mut s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+    let events = s.console_events().expect("console_events");
+    s.find(&By::css("#console-error"))
+        .expect("find")
+        .click()
+        .expect("click");
+
+    let error = std::iter::from_fn(|| events.recv_timeout(time::Duration::from_secs(10)).ok())
+        .find(BrowserLogEntry::is_error)
+        .expect("console error");
+    assert!(
+        error.message.contains("Something broke"),
+        "Message: {:?}",
+        error.message
+    );
+}
+
+#[test]
+fn grant_permissions() {
+    env_logger::try_init().unwrap_or_default();
+    if is_gecko() {
+        info!("DevTools permissions are chrome only");
+        return;
+    }
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+
+    let query = "var done = arguments[arguments.length - 1]; \
+                 navigator.permissions.query({ name: arguments[0] }) \
+                 .then(function (res) { done(res.state); });";
+
+    s.grant_permissions(&[Permission::Geolocation], Some(url.trim_end_matches('/')))
+        .expect("grant_permissions");
+    let state: String = s
+        .execute_async_script(query, &["geolocation".into()])
+        .expect("query geolocation");
+    assert_eq!(state, "granted");
+
+    s.set_permission(Permission::Notifications, PermissionState::Denied)
+        .expect("set_permission");
+    let state: String = s
+        .execute_async_script(query, &["notifications".into()])
+        .expect("query notifications");
+    assert_eq!(state, "denied");
+}
+
+#[test]
+fn webauthn_virtual_authenticators() {
+    use sulfur::webauthn::{AuthenticatorOptions, Credential, Transport};
+
+    // A throwaway P-256 key, PKCS#8 encoded.
+    const PRIVATE_KEY: &str = "MIGHAgEAMBMGByqGSM49AgEGCCqGSM49AwEHBG0wawIBAQQgBVsGCsxZ2CcUXl9K52f7TXEljpvzETss9R6IS7PKrOehRANCAAS6h1YeHGcEACviQXlkLV69pYQd3yv9dKnLrQbUmHMlxxtSLvFI4sG4wCbCOJh-nQK0eT-dfAWLsCMo4hkH8cKI";
+
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+
+    let auth = s
+        .add_virtual_authenticator(
+            AuthenticatorOptions::default()
+                .transport(Transport::Internal)
+                .has_resident_key(true)
+                .has_user_verification(true)
+                .is_user_verified(true),
+        )
+        .expect("add_virtual_authenticator");
+
+    let cred = Credential {
+        credential_id: b"canary-credential".to_vec(),
+        is_resident_credential: true,
+        rp_id: Some("127.0.0.1".into()),
+        private_key: base64::decode_config(PRIVATE_KEY, base64::URL_SAFE_NO_PAD).expect("key"),
+        user_handle: Some(b"canary-user".to_vec()),
+        sign_count: 0,
+    };
+    s.add_credential(&auth, &cred).expect("add_credential");
+    let creds = s.credentials(&auth).expect("credentials");
+    assert_eq!(
+        creds.iter().map(|c| &c.credential_id).collect::<Vec<_>>(),
+        vec![&cred.credential_id]
+    );
+
+    s.set_user_verified(&auth, false)
+        .expect("set_user_verified");
+    s.remove_credential(&auth, &cred.credential_id)
+        .expect("remove_credential");
+    assert!(s.credentials(&auth).expect("credentials").is_empty());
+    s.remove_all_credentials(&auth)
+        .expect("remove_all_credentials");
+    s.remove_virtual_authenticator(&auth)
+        .expect("remove_virtual_authenticator");
+}
+
+#[test]
+fn private_browsing() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let start = || {
+        start_configured(
+            |_, c| {
+                c.private_browsing(true);
+            },
+            |_, c| {
+                c.incognito(true);
+            },
+        )
+    };
+
+    let first = start();
+    first.visit(&url).expect("visit");
+    first
+        .add_cookie(&Cookie {
+            name: "canary".into(),
+            value: "private".into(),
+            expiry: Some(4_102_444_800),
+            ..Cookie::default()
+        })
+        .expect("add_cookie");
+    assert_eq!(first.cookie("canary").expect("cookie").value, "private");
+
+    let second = start();
+    second.visit(&url).expect("visit");
+    let names = second
+        .cookies()
+        .expect("cookies")
+        .into_iter()
+        .map(|c| c.name)
+        .collect::<Vec<_>>();
+    assert!(!names.contains(&"canary".to_string()), "{:?}", names);
+}
+
+#[test]
+fn user_agent_override() {
+    env_logger::try_init().unwrap_or_default();
+    let is_gecko = is_gecko();
+
+    let url = SERVER.url();
+    let s = start_configured(
+        |_, c| {
+            c.user_agent("SulfurCanary/1.0");
+        },
+        |_, c| {
+            c.user_agent("SulfurCanary/1.0");
+        },
+    );
+    s.visit(&url).expect("visit");
+    let user_agent = |s: &Client| -> String {
+        s.execute_script("return navigator.userAgent;", &[])
+            .expect("execute_script")
+    };
+    assert_eq!(user_agent(&s), "SulfurCanary/1.0");
+
+    if !is_gecko {
+        s.set_user_agent("SulfurCanary/2.0")
+            .expect("set_user_agent");
+        s.refresh().expect("refresh");
+        assert_eq!(user_agent(&s), "SulfurCanary/2.0");
+    }
+}
+
+#[test]
+fn locale() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = start_configured(
+        |_, c| {
+            c.lang("fr-FR");
+        },
+        |_, c| {
+            c.lang("fr-FR");
+        },
+    );
+    s.visit(&url).expect("visit");
+    let language: String = s
+        .execute_script("return navigator.language;", &[])
+        .expect("execute_script");
+    assert_eq!(language, "fr-FR");
+}
+
+#[cfg(feature = "proxy")]
+#[test]
+fn recording_proxy() {
+    env_logger::try_init().unwrap_or_default();
+
+    let proxy = proxy::RecordingProxy::start().expect("proxy");
+    let url = SERVER.url();
+    let s = start_configured(
+        |_, c| {
+            c.proxy(proxy.address());
+        },
+        |_, c| {
+            c.proxy(proxy.address());
+        },
+    );
+    s.visit(&url).expect("visit");
+
+    let exchanges = proxy.exchanges();
+    let page = exchanges
+        .iter()
+        .find(|ex| ex.url == url)
+        .unwrap_or_else(|| panic!("No request for {} in {:?}", url, exchanges));
+    assert_eq!(page.method, "GET");
+    assert_eq!(page.status, 200);
+    assert!(String::from_utf8_lossy(&page.response_body).contains("Page title"));
+}
+
+#[test]
+fn host_rules() {
+    env_logger::try_init().unwrap_or_default();
+    if is_gecko() {
+        info!("Host resolver rules are chrome only");
+        return;
+    }
+
+    let mut url = url::Url::parse(&SERVER.url()).expect("parse");
+    let s = chrome::start(
+        chrome::Config::default()
+            .headless(true)
+            .host_rule("www.sulfur.example", "127.0.0.1"),
+    )
+    .expect("chrome::start");
+    url.set_host(Some("www.sulfur.example")).expect("set_host");
+    s.visit(url.as_str()).expect("visit");
+    assert_eq!(s.title().expect("title"), "Page title");
+}
+
+#[test]
+fn chrome_ci_defaults() {
+    env_logger::try_init().unwrap_or_default();
+    if is_gecko() {
+        info!("ci_defaults is chrome only");
+        return;
+    }
+
+    let s = chrome::start(chrome::Config::default().headless(true).ci_defaults())
+        .expect("chrome::start");
+    let rect = s.window_rect().expect("window_rect");
+    assert_eq!((rect.width, rect.height), (1920, 1080));
+}
+
+#[test]
+fn chromium_send_command() {
+    env_logger::try_init().unwrap_or_default();
+    if is_gecko() {
+        info!("DevTools commands are chrome only");
+        return;
+    }
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+
+    s.chromium_send_command(
+        "Emulation.setDeviceMetricsOverride",
+        serde_json::json!({
+            "width": 400,
+            "height": 300,
+            "deviceScaleFactor": 1,
+            "mobile": false,
+        }),
+    )
+    .expect("chromium_send_command");
+    let width: u64 = s
+        .execute_script("return window.innerWidth;", &[])
+        .expect("execute_script");
+    assert_eq!(width, 400);
+
+    let result = s
+        .chromium_send_command_and_get_result(
+            "Runtime.evaluate",
+            serde_json::json!({ "expression": "1 + 1" }),
+        )
+        .expect("chromium_send_command_and_get_result");
+    assert_eq!(result["result"]["value"], 2);
+}
+
+#[test]
+fn convenience_locators() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+
+    let by_id = s.find_element(&By::id("single-select")).expect("by id");
+    assert_eq!(s.name(&by_id).expect("name"), "select");
+
+    let by_name = s.find_element(&By::name("text")).expect("by name");
+    assert_eq!(s.name(&by_name).expect("name"), "input");
+
+    let by_class = s
+        .find_element(&By::class_name("clickable-link"))
+        .expect("by class");
+    assert_eq!(s.text(&by_class).expect("text"), "Link target");
+}
+
+#[test]
+fn relative_locators() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+
+    let id_of = |elt: &Element| s.attribute(elt, "id").expect("attribute");
+    let first_input = s.find_element(&By::id("first-input")).expect("find");
+    let second_label = s.find_element(&By::id("second-label")).expect("find");
+
+    let input = s
+        .find_element(&By::tag_name("input").right_of(&second_label))
+        .expect("right_of");
+    assert_eq!(id_of(&input), Some("second-input".into()));
+
+    let input = s
+        .find_element(&By::tag_name("input").below(&first_input))
+        .expect("below");
+    assert_eq!(id_of(&input), Some("second-input".into()));
+
+    let label = s
+        .find_element(
+            &By::tag_name("label")
+                .above(&second_label)
+                .near(&second_label),
+        )
+        .expect("above and near");
+    assert_eq!(id_of(&label), Some("first-label".into()));
+
+    let below = By::tag_name("input").below(&first_input);
+    assert_eq!(s.count_elements(&below).expect("count"), 1);
+    let mut batch = s.batch();
+    let text = batch.attribute(&below, "id");
+    let results = batch.run().expect("run");
+    assert_eq!(
+        results.get(text).expect("attribute"),
+        Some("second-input".to_string())
+    );
+
+    let err = s
+        .find_element(&By::tag_name("label").left_of(&second_label))
+        .expect_err("nothing left of the labels");
+    assert_eq!(
+        err.downcast_ref::<WdError>().map(WdError::kind),
+        Some(ErrorKind::NoSuchElement)
+    );
+}
+
+#[test]
+fn text_locators() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+
+    let exact = s.find_element(&By::text("Say \"don't\"")).expect("by text");
+    assert_eq!(
+        s.attribute(&exact, "id").expect("attribute"),
+        Some("quoted-button".into())
+    );
+
+    let partial = s
+        .find_element(&By::contains_text("\"don'"))
+        .expect("by partial text");
+    assert!(s.elements_equal(&partial, &exact).expect("elements_equal"));
+
+    assert!(s.find_elements(&By::text("Say")).expect("find").is_empty());
+}
+
+#[test]
+fn element_identity() {
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let s = new_session().expect("new_session");
+    s.visit(&url).expect("visit");
+
+    let by_id = s.find_element(&By::id("first-input")).expect("find");
+    let by_css = s
+        .find_element(&By::css("#relative-form input"))
+        .expect("find");
+    let other = s.find_element(&By::id("second-input")).expect("find");
+
+    assert!(s.elements_equal(&by_id, &by_css).expect("elements_equal"));
+    assert!(!s.elements_equal(&by_id, &other).expect("elements_equal"));
+}
+
+#[test]
+fn session_pool() {
+    use sulfur::pool::SessionPool;
+
+    env_logger::try_init().unwrap_or_default();
+
+    let url = SERVER.url();
+    let pool = SessionPool::new(2, new_session).expect("SessionPool::new");
+    assert_eq!(pool.idle(), 2);
+
+    {
+        let a = pool.checkout().expect("checkout");
+        let b = pool.checkout().expect("checkout");
+        assert_eq!(pool.idle(), 0);
+        a.visit(&url).expect("visit");
+        b.visit(&url).expect("visit");
+    }
+    assert_eq!(pool.idle(), 2);
+
+    let mut crashed = pool.checkout().expect("checkout");
+    crashed.close().expect("close");
+    drop(crashed);
+    assert_eq!(pool.idle(), 2, "Crashed session should be replaced");
+
+    let session = pool.checkout().expect("checkout");
+    assert_eq!(session.current_url().expect("current_url"), "about:blank");
+}
+
+#[test]
+fn driver_log_artifacts() {
+    env_logger::try_init().unwrap_or_default();
+    let dir = tempfile::tempdir().expect("tempdir");
+    let log = dir.path().join("driver-output.log");
+    let s = start_configured(
+        |d, _| {
+            d.log_file(&log);
+        },
+        |d, _| {
+            d.log_path(&log);
+        },
+    );
+    assert_eq!(s.driver_log(), Some(log.as_path()));
+    s.visit(SERVER.url()).expect("visit");
+
+    let artifacts = dir.path().join("artifacts");
+    s.capture_artifacts(&artifacts).expect("capture_artifacts");
+    let saved = std::fs::read_to_string(artifacts.join("driver.log")).expect("driver.log");
+    assert!(!saved.is_empty(), "Driver log should not be empty");
 }
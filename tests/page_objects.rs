@@ -0,0 +1,50 @@
+use sulfur::page::{LazyElement, PageObject};
+use sulfur::testing::{MockElement, MockWebDriver};
+use sulfur::By;
+
+#[derive(PageObject)]
+struct LoginForm<'c> {
+    #[by(css = ".login input[name=user]")]
+    user: LazyElement<'c>,
+    #[by(xpath = "//button[@type='submit']")]
+    submit: LazyElement<'c>,
+}
+
+#[derive(PageObject)]
+struct LoginPage<'c> {
+    form: LoginForm<'c>,
+    #[by(id = "flash")]
+    flash: LazyElement<'c>,
+}
+
+#[test]
+fn derived_page_objects_find_lazily() {
+    let mock = MockWebDriver::start().expect("start");
+    let client = mock.client().expect("client");
+
+    let page: LoginPage = client.page();
+    assert!(
+        mock.requests().is_empty(),
+        "Should not send any commands yet"
+    );
+    assert!(!page.flash.exists().expect("exists"));
+
+    mock.on_find(
+        &By::css(".login input[name=user]"),
+        vec![MockElement::new("input")],
+    );
+    mock.on_find(
+        &By::xpath("//button[@type='submit']"),
+        vec![MockElement::new("button").text("Log in")],
+    );
+    mock.on_find(&By::id("flash"), vec![MockElement::new("p").text("Hi")]);
+
+    page.form.user.send_keys("alice").expect("send_keys");
+    assert_eq!(
+        page.form.user.attribute("value").expect("value"),
+        Some("alice".to_string())
+    );
+    assert_eq!(page.form.submit.text().expect("text"), "Log in");
+    page.form.submit.click().expect("click");
+    assert_eq!(page.flash.text().expect("text"), "Hi");
+}